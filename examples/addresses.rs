@@ -67,7 +67,7 @@ fn main() {
     cont!(dbg);
 
 
-    if let Some(o) = dbg.child.stdout() {
+    if let Some(o) = dbg.child.as_mut().and_then(|c| c.stdout()) {
         println!("{}",o);
     }
 
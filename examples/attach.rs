@@ -0,0 +1,31 @@
+extern crate rdb;
+
+use std::env;
+
+use rdb::debugger::{Debugger,LogLevel};
+
+fn main() {
+
+    /* pid of an already running process */
+    let pid = env::args().nth(1)
+        .expect("usage: attach <pid>")
+        .parse::<u32>()
+        .expect("pid must be a number");
+
+    /* stops the process and reads its binary and arguments from /proc */
+    let mut dbg = Debugger::attach(pid)
+        .expect("Could not attach to process");
+
+    dbg.log = LogLevel::Commands | LogLevel::Breakpoints;
+
+    println!("attached to '{}' with args {:?}", dbg.file, dbg.args);
+
+    let regs = dbg.process.getregs()
+        .expect("failed to getregs");
+
+    println!("{}", regs);
+
+    /* hand the process back, it keeps running */
+    dbg.detach()
+        .expect("failed to detach");
+}
@@ -17,12 +17,12 @@ fn main() {
     /* set log level to show commands and breakpoints */
     dbg.log = LogLevel::Commands | LogLevel::Breakpoints;
 
-    let main_addr = 0x4005d0;
-    let foo_addr = 0x400592;
+    let foo_addr = dbg.symbol("foo")
+        .expect("no symbol foo");
     let foo_exit = 0x4005a6;
 
     /* add a breakpoint in main */
-    dbg.breakpoint(main_addr)
+    dbg.breakpoint_sym("main")
         .expect("failed to set breakpoint")
         .name("main::entry");
 
@@ -39,7 +39,7 @@ fn main() {
     cont!(dbg);
 
     /* print output */
-    if let Some(o) = dbg.child.stdout() {
+    if let Some(o) = dbg.child.as_mut().and_then(|c| c.stdout()) {
         println!("{}", o);
     }
 }
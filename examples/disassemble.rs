@@ -0,0 +1,30 @@
+extern crate rdb;
+
+use rdb::debugger::Debugger;
+use rdb::disasm::Syntax;
+
+fn main() {
+
+    let mut dbg = Debugger::new("./bin/test", vec!["./bin/test"])
+        .expect("Could not start binary");
+
+    let main = dbg.symbol("main")
+        .expect("no symbol main");
+
+    /* the breakpoint doesn't show up in the listing */
+    dbg.breakpoint(main)
+        .expect("Failed to set breakpoint");
+
+    let event = dbg.run()
+        .expect("couldnt run");
+
+    println!("{:?}", event);
+
+    let insns = dbg.disassemble(main, 12)
+        .expect("failed to disassemble");
+
+    for insn in insns.iter() {
+        println!("0x{:x} <{}>:  {:<40} {}", insn.addr, dbg.symbolize(insn.addr),
+            insn.format(Syntax::Intel), insn.format(Syntax::Att));
+    }
+}
@@ -0,0 +1,35 @@
+extern crate rdb;
+extern crate nix;
+
+use std::env;
+
+use nix::Errno;
+use rdb::debugger::Debugger;
+
+fn main() {
+
+    let file = env::args().nth(1)
+        .unwrap_or("./bin/test".to_string());
+
+    let mut dbg = Debugger::new(file.clone(), vec![file])
+        .expect("Could not start binary");
+
+    /* writes to stdout never happen and report a full disk */
+    dbg.intercept_syscall("write", |ctx| {
+        if ctx.is_entry() && ctx.arg(0) == Some(1) {
+            ctx.fail(Errno::ENOSPC);
+        }
+    }).expect("unknown syscall");
+
+    /* every other write claims to have been interrupted */
+    dbg.intercept_syscall("write", |ctx| {
+        if !ctx.is_entry() && ctx.arg(0) != Some(1) {
+            ctx.set_return(-(Errno::EINTR as i64));
+        }
+    }).expect("unknown syscall");
+
+    let event = dbg.run()
+        .expect("Failed to run binary");
+
+    println!("{:?}", event);
+}
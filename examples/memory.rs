@@ -43,7 +43,7 @@ fn main() {
         cont!(dbg);
     });
 
-    let pid = dbg.child.id() as usize;
+    let pid = dbg.process.pid() as usize;
 
     dbg.run()
         .expect("couldnt run");
@@ -0,0 +1,43 @@
+extern crate rdb;
+
+use std::env;
+
+use rdb::debugger::Debugger;
+use rdb::event::StopEvent;
+
+fn main() {
+
+    let file = env::args().nth(1)
+        .unwrap_or("./bin/test".to_string());
+
+    let mut dbg = Debugger::new(file.clone(), vec![file])
+        .expect("Could not start binary");
+
+    /* count writes without stopping for them */
+    dbg.register_syscall_action("write", |_, syscall| {
+        if !syscall.is_entry() {
+            println!("write returned {:?}", syscall.ret);
+        }
+    }).expect("unknown syscall");
+
+    /* every syscall entry and exit is reported as a stop */
+    dbg.trace_syscalls(true);
+
+    let mut event = dbg.run()
+        .expect("Failed to run binary");
+
+    while !event.is_exit() {
+
+        if let StopEvent::Syscall(ref syscall) = event {
+            /* arguments are complete by the time the call returns */
+            if !syscall.is_entry() {
+                println!("{}", syscall.describe(dbg.current_thread().pid()));
+            }
+        }
+
+        event = dbg.cont()
+            .expect("failed to continue");
+    }
+
+    println!("{:?}", event);
+}
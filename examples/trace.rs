@@ -0,0 +1,38 @@
+extern crate rdb;
+
+use std::io;
+
+use rdb::debugger::{Debugger,LogLevel};
+
+fn main() {
+
+    let mut dbg = Debugger::new("./bin/test", vec!["./bin/test"])
+        .expect("Could not start binary");
+
+    dbg.log = LogLevel::Commands;
+
+    let loop_cmp = 0x4005ef;
+    let do_stuff = dbg.symbol("do_stuff")
+        .expect("no symbol do_stuff");
+
+    /* loop counter on every compare, the loop never stops for it */
+    dbg.tracepoint(loop_cmp, "rbp, [rbp-4, 4]")
+        .expect("failed to set tracepoint")
+        .name("main::compare");
+
+    /* only the last two calls */
+    dbg.tracepoint(do_stuff, "rsp, [rsp, 16]")
+        .expect("failed to set tracepoint")
+        .condition_expr("[rbp-4, 4] >= 2")
+        .expect("failed to parse condition");
+
+    let event = dbg.run()
+        .expect("couldnt run");
+
+    println!("{:?}", event);
+
+    let records = dbg.write_trace(&mut io::stdout())
+        .expect("failed to write trace");
+
+    println!("{} records", records);
+}
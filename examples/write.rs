@@ -22,7 +22,7 @@ fn main() {
     dbg.run()
         .expect("couldnt run");
 
-    let pid = dbg.child.id();
+    let pid = dbg.process.pid();
     let mut mem = memory::Memory::load(pid as usize)
         .expect("Failed to load memory");
 
@@ -33,7 +33,7 @@ fn main() {
 
 
 
-    if let Some(o) = dbg.child.stdout() {
+    if let Some(o) = dbg.child.as_mut().and_then(|c| c.stdout()) {
         println!("{}", o);
     }
 }
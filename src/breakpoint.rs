@@ -4,6 +4,8 @@ use std::hash::{Hash, Hasher};
 
 use process::Process;
 use error::DebugError;
+use event::SigInfo;
+use condition::Condition;
 use registers::{Register,x86_64_Registers};
 
 #[derive(Debug,Clone)]
@@ -14,13 +16,14 @@ pub struct Breakpoint {
     temporary: bool,
     process: Process<x86_64_Registers>,
     restore: u64,
+    condition: Option<Condition>,
 }
 
 impl Hash for Breakpoint {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.addr.hash(state);
         self.name.hash(state);
-        self.process.pid.hash(state);
+        self.process.pid().hash(state);
     }
 }
 
@@ -28,18 +31,17 @@ impl PartialEq for Breakpoint {
     fn eq(&self, other: &Breakpoint) -> bool {
         self.addr == other.addr &&
         self.name == other.name &&
-        self.process.pid == other.process.pid
+        self.process.pid() == other.process.pid()
     }
 }
 impl Eq for Breakpoint { }
 
 impl Drop for Breakpoint {
     fn drop(&mut self) {
-        if *self.enabled.borrow() && self.process.status().running() {
-            match self.restore() {
-                Ok(_) => println!("dropped breakpoint '{}' @ 0x{:x}", self.name, self.addr),
-                Err(_) => println!("failed to drop breakpoint '{}' @ 0x{:x}", self.name, self.addr),
-            }
+        /* registers are left alone, the process may be anywhere by now */
+        /* fails quietly if the process is gone or running */
+        if *self.enabled.borrow() {
+            let _ = self.remove();
         }
     }
 }
@@ -56,6 +58,7 @@ impl Breakpoint {
             enabled: Rc::new(RefCell::new(true)),
             temporary: false,
             name: name,
+            condition: None,
         };
 
         let data = bp.trap()?;
@@ -64,6 +67,61 @@ impl Breakpoint {
         Ok(bp)
     }
 
+    /* nowhere yet, e.g. in a library that isn't loaded, see arm */
+    pub fn pending(name: String, pid: u32) -> Breakpoint {
+        Breakpoint {
+            process: Process::new(pid),
+            addr: 0,
+            restore: 0,
+            enabled: Rc::new(RefCell::new(false)),
+            temporary: false,
+            name: name,
+            condition: None,
+        }
+    }
+
+    /* put a pending breakpoint in place once its address is known */
+    pub fn arm(&mut self, addr: u64) -> Result<&mut Breakpoint, DebugError> {
+
+        self.addr = addr;
+        self.restore = self.trap()?;
+
+        Ok(self)
+    }
+
+    /* pending again, the code it was in got unmapped */
+    pub fn unarm(&mut self) {
+        self.invalidate();
+        self.addr = 0;
+        self.restore = 0;
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.addr == 0
+    }
+
+    /* the same trap in a process that already carries it, e.g. a fork child */
+    pub fn for_process(&self, pid: u32) -> Breakpoint {
+        Breakpoint {
+            process: Process::new(pid),
+            addr: self.addr,
+            restore: self.restore,
+            enabled: Rc::new(RefCell::new(self.is_enabled())),
+            temporary: self.temporary,
+            name: self.name.clone(),
+            condition: self.condition.clone(),
+        }
+    }
+
+    pub fn retarget(&self, pid: u32) {
+        self.process.retarget(pid);
+    }
+
+    /* the trap went away with the old image, forget it without writing */
+    pub fn invalidate(&self) {
+        *self.enabled.borrow_mut() = false;
+    }
+
     pub fn finish(&mut self) -> &Breakpoint {
         self
     }
@@ -74,6 +132,34 @@ impl Breakpoint {
         self
     }
 
+    /* the debugger resumes on its own while f returns false */
+    pub fn condition<F>(&mut self, f: F) -> &mut Breakpoint
+        where F: Fn(&x86_64_Registers, &Process<x86_64_Registers>) -> bool,
+        F: 'static
+    {
+        self.condition = Some(Condition::predicate(f));
+        self
+    }
+
+    /* e.g. `rdi == 0 && [rsp+8] > 10`, see condition.rs */
+    pub fn condition_expr(&mut self, expr: &str) -> Result<&mut Breakpoint, DebugError> {
+        self.condition = Some(Condition::parse(expr)?);
+        Ok(self)
+    }
+
+    pub fn clear_condition(&mut self) -> &mut Breakpoint {
+        self.condition = None;
+        self
+    }
+
+    /* thread is the one that hit the trap */
+    pub fn should_stop(&self, thread: &Process<x86_64_Registers>) -> Result<bool, DebugError> {
+        match self.condition {
+            Some(ref condition) => condition.holds(thread),
+            None => Ok(true),
+        }
+    }
+
     pub fn trap(&self) -> Result<u64, DebugError> {
 
         let data = self.process.peek(self.addr)?;
@@ -94,10 +180,14 @@ impl Breakpoint {
 
     pub fn enabled(&mut self, e: bool) -> Result<&mut Breakpoint, DebugError> {
 
+        if self.is_pending() {
+            return Err("Breakpoint is not armed yet".into());
+        }
+
         if e {
             self.trap()?;
         } else {
-            self.process.poke(self.addr, self.restore)?;
+            self.process.poke_bits(self.addr, self.restore & 0xff, 8)?;
         }
 
         *self.enabled.borrow_mut() = e;
@@ -105,21 +195,41 @@ impl Breakpoint {
         Ok(self)
     }
 
+    /* stepping over a temporary breakpoint takes it out for good */
     pub fn temporary(&mut self, e: bool) -> &mut Breakpoint {
         self.temporary = e;
+        self
+    }
 
-        if self.is_enabled() && e {
-            *self.enabled.borrow_mut() = false;
+    /* the byte under the trap */
+    pub fn original(&self) -> u8 {
+        (self.restore & 0xff) as u8
+    }
+
+    /* put the original byte back without touching registers */
+    pub fn remove(&self) -> Result<u64, DebugError> {
+
+        let data = self.process.peek(self.addr)?;
+
+        if data & 0xff == 0xcc {
+            self.process.poke_bits(self.addr, self.restore & 0xff, 8)?;
         }
 
-        self
+        *self.enabled.borrow_mut() = false;
+
+        Ok(self.addr)
     }
 
     pub fn restore(&self) -> Result<u64, DebugError> {
+        self.restore_on(&self.process)
+    }
 
-        self.process.poke(self.addr, self.restore)?;
-        let addr = self.addr;
-        self.set_ip(addr)?;
+    /* thread is the one sitting past the trap, only the trapped byte goes */
+    /* back, a later breakpoint may sit in the rest of the saved word */
+    pub fn restore_on(&self, thread: &Process<x86_64_Registers>) -> Result<u64, DebugError> {
+
+        self.process.poke_bits(self.addr, self.restore & 0xff, 8)?;
+        set_ip(thread, self.addr)?;
         *self.enabled.borrow_mut() = false;
 
         Ok(self.addr)
@@ -131,28 +241,42 @@ impl Breakpoint {
          *  Do a restore, step, trap, continue
          *  return Status if the program stops
          */
+        let sig = self.step_over()?;
+
+        /* continue */
+        self.process.cont_signal(sig.map_or(0, |info| info.signo))?;
+        self.process.wait_stop()
+    }
+
+    /* leaves the process stopped just past the trapped instruction */
+    pub fn step_over(&self) -> Result<Option<SigInfo>, DebugError> {
+        self.step_over_on(&self.process)
+    }
+
+    /*
+     *  other threads have to be stopped, they would run through the gap.
+     *  A signal that arrived during the step is held back and returned
+     */
+    pub fn step_over_on(&self, thread: &Process<x86_64_Registers>) -> Result<Option<SigInfo>, DebugError> {
 
         /* restore instruction, set pc to pc - 1 */
-        self.restore()?;
+        self.restore_on(thread)?;
 
-        if !self.temporary {
-            /* execute restored instruction */
-            /* the process will be sigtrapped */
-            self.process.step()?;
-            self.process.wait_stop()?;
+        /* execute restored instruction */
+        /* the process will be sigtrapped */
+        let sig = thread.step_deferring()?;
 
-            /* re-trap instruction */
+        /* re-trap instruction, a temporary one stays out */
+        if !self.temporary {
             self.trap()?;
         }
 
-        /* continue */
-        self.process.cont()?;
-        self.process.wait_stop()
+        Ok(sig)
     }
 
     pub fn restore_to(&self, addr: u64) -> Result<u64, DebugError> {
 
-        self.process.poke(self.addr, self.restore)?;
+        self.process.poke_bits(self.addr, self.restore & 0xff, 8)?;
         self.set_ip(addr)?;
 
         Ok(addr)
@@ -177,12 +301,16 @@ impl Breakpoint {
     }
 
     fn set_ip(&self, addr: u64) -> Result<u64, DebugError> {
-        let mut regs = self.process.getregs()?;
-        regs.set_ip(addr);
-        self.process.setregs(&regs)?;
-
-        Ok(regs.ip())
+        set_ip(&self.process, addr)
     }
 }
 
+fn set_ip(thread: &Process<x86_64_Registers>, addr: u64) -> Result<u64, DebugError> {
+    let mut regs = thread.getregs()?;
+    regs.set_ip(addr);
+    thread.setregs(&regs)?;
+
+    Ok(regs.ip())
+}
+
 
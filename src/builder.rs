@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr,OsString};
+use std::fs::{File,OpenOptions};
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command,Stdio};
+use std::result::Result;
+
+use libc;
+
+use debugger::Debugger;
+use error::DebugError;
+
+/* personality flags, see personality(2) */
+pub const ADDR_NO_RANDOMIZE: u64 = 0x0040000;
+pub const READ_IMPLIES_EXEC: u64 = 0x0400000;
+
+/* where one of the standard streams of the program goes */
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum Redirect {
+    /* readable and writable through Debugger::child */
+    Pipe,
+    Inherit,
+    Null,
+    /* read for stdin, truncated and written for the others */
+    File(PathBuf),
+}
+
+/* resource limits the program starts with */
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Resource {
+    AddressSpace,
+    Core,
+    Cpu,
+    Data,
+    FileSize,
+    NoFile,
+    Processes,
+    Stack,
+}
+
+impl Resource {
+    fn raw(&self) -> libc::c_int {
+        (match self {
+            &Resource::AddressSpace => libc::RLIMIT_AS,
+            &Resource::Core => libc::RLIMIT_CORE,
+            &Resource::Cpu => libc::RLIMIT_CPU,
+            &Resource::Data => libc::RLIMIT_DATA,
+            &Resource::FileSize => libc::RLIMIT_FSIZE,
+            &Resource::NoFile => libc::RLIMIT_NOFILE,
+            &Resource::Processes => libc::RLIMIT_NPROC,
+            &Resource::Stack => libc::RLIMIT_STACK,
+        }) as libc::c_int
+    }
+}
+
+/*
+ *  everything about how the program is started, Debugger::new is
+ *  DebuggerBuilder::new(binary).args(args).spawn()
+ */
+pub struct DebuggerBuilder {
+    binary: OsString,
+    args: Vec<OsString>,
+    env: HashMap<OsString,Option<OsString>>,
+    env_clear: bool,
+    cwd: Option<PathBuf>,
+    stdin: Redirect,
+    stdout: Redirect,
+    stderr: Redirect,
+    rlimits: Vec<(Resource,u64,u64)>,
+    personality: Option<u64>,
+}
+
+impl DebuggerBuilder {
+    pub fn new<T: AsRef<OsStr>>(binary: T) -> Self {
+        DebuggerBuilder {
+            binary: binary.as_ref().to_os_string(),
+            args: vec![],
+            env: HashMap::new(),
+            env_clear: false,
+            cwd: None,
+            stdin: Redirect::Pipe,
+            stdout: Redirect::Pipe,
+            stderr: Redirect::Inherit,
+            rlimits: vec![],
+            personality: None,
+        }
+    }
+
+    pub fn arg<T: AsRef<OsStr>>(&mut self, arg: T) -> &mut Self {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    pub fn args<T: AsRef<OsStr>>(&mut self, args: Vec<T>) -> &mut Self {
+        for arg in args.iter() {
+            self.arg(arg);
+        }
+        self
+    }
+
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, value: V) -> &mut Self {
+        self.env.insert(key.as_ref().to_os_string(), Some(value.as_ref().to_os_string()));
+        self
+    }
+
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Self {
+        self.env.insert(key.as_ref().to_os_string(), None);
+        self
+    }
+
+    /* start from an empty environment, env still adds to it */
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.env_clear = true;
+        self.env.clear();
+        self
+    }
+
+    pub fn cwd<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.cwd = Some(dir.into());
+        self
+    }
+
+    pub fn stdin(&mut self, redirect: Redirect) -> &mut Self {
+        self.stdin = redirect;
+        self
+    }
+
+    pub fn stdout(&mut self, redirect: Redirect) -> &mut Self {
+        self.stdout = redirect;
+        self
+    }
+
+    pub fn stderr(&mut self, redirect: Redirect) -> &mut Self {
+        self.stderr = redirect;
+        self
+    }
+
+    /* libc::RLIM_INFINITY for no limit */
+    pub fn rlimit(&mut self, resource: Resource, soft: u64, hard: u64) -> &mut Self {
+        self.rlimits.retain(|&(r, _, _)| r != resource);
+        self.rlimits.push((resource, soft, hard));
+        self
+    }
+
+    /* flags are added to the persona the program would have had */
+    pub fn personality(&mut self, flags: u64) -> &mut Self {
+        self.personality = Some(self.personality.unwrap_or(0) | flags);
+        self
+    }
+
+    /* same addresses every run */
+    pub fn no_aslr(&mut self) -> &mut Self {
+        self.personality(ADDR_NO_RANDOMIZE)
+    }
+
+    pub fn spawn(&self) -> Result<Debugger, DebugError> {
+
+        let mut command = self.command()?;
+
+        let file = self.binary.to_string_lossy().into_owned();
+        let args = self.args.iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+
+        Debugger::spawn_command(&mut command, file, args)
+    }
+
+    /* the command spawn runs, without ptrace enabled yet */
+    pub fn command(&self) -> Result<Command, DebugError> {
+
+        let mut command = Command::new(&self.binary);
+
+        command.args(&self.args);
+
+        if self.env_clear {
+            command.env_clear();
+        }
+
+        for (key, value) in self.env.iter() {
+            match value {
+                &Some(ref value) => { command.env(key, value); },
+                &None => { command.env_remove(key); },
+            }
+        }
+
+        if let Some(ref dir) = self.cwd {
+            command.current_dir(dir);
+        }
+
+        command.stdin(stdio(&self.stdin, true)?);
+        command.stdout(stdio(&self.stdout, false)?);
+        command.stderr(stdio(&self.stderr, false)?);
+
+        let rlimits = self.rlimits.clone();
+        let personality = self.personality;
+
+        /* runs in the child before exec, ahead of the PTRACE_TRACEME spawn_ptrace adds */
+        unsafe {
+            command.pre_exec(move || {
+
+                for &(resource, soft, hard) in rlimits.iter() {
+                    let limit = libc::rlimit {
+                        rlim_cur: soft as libc::rlim_t,
+                        rlim_max: hard as libc::rlim_t,
+                    };
+
+                    if libc::setrlimit(resource.raw() as _, &limit) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+
+                if let Some(flags) = personality {
+                    /* 0xffffffff only queries the current persona */
+                    let current = libc::personality(0xffffffff);
+
+                    if current == -1 || libc::personality(current as libc::c_ulong | flags as libc::c_ulong) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+
+                Ok(())
+            });
+        }
+
+        Ok(command)
+    }
+}
+
+fn stdio(redirect: &Redirect, input: bool) -> Result<Stdio, DebugError> {
+    Ok(match redirect {
+        &Redirect::Pipe => Stdio::piped(),
+        &Redirect::Inherit => Stdio::inherit(),
+        &Redirect::Null => Stdio::null(),
+        &Redirect::File(ref path) if input => Stdio::from(File::open(path)?),
+        &Redirect::File(ref path) => Stdio::from(
+            OpenOptions::new().write(true).create(true).truncate(true).open(path)?
+        ),
+    })
+}
@@ -0,0 +1,380 @@
+use std::fmt;
+use std::rc::Rc;
+
+use process::Process;
+use error::DebugError;
+use registers::x86_64_Registers;
+
+type Predicate = Rc<dyn Fn(&x86_64_Registers, &Process<x86_64_Registers>) -> bool>;
+
+/* checked when a breakpoint is hit, the stop is only reported when it holds */
+#[derive(Clone)]
+pub enum Condition {
+    Predicate(Predicate),
+    /* e.g. `rdi == 0 && [rsp+8] > 10` */
+    Expr(String, Expr),
+}
+
+impl fmt::Debug for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Condition::Predicate(_) => write!(f, "Predicate"),
+            &Condition::Expr(ref src, _) => write!(f, "Expr({:?})", src),
+        }
+    }
+}
+
+impl Condition {
+    pub fn predicate<F>(f: F) -> Self
+        where F: Fn(&x86_64_Registers, &Process<x86_64_Registers>) -> bool,
+        F: 'static
+    {
+        Condition::Predicate(Rc::new(f))
+    }
+
+    pub fn parse(src: &str) -> Result<Self, DebugError> {
+        Ok(Condition::Expr(src.to_string(), Expr::parse(src)?))
+    }
+
+    pub fn holds(&self, process: &Process<x86_64_Registers>) -> Result<bool, DebugError> {
+
+        let regs = process.getregs()?;
+
+        match self {
+            &Condition::Predicate(ref f) => Ok(f(&regs, process)),
+            &Condition::Expr(_, ref expr) => Ok(expr.eval(&regs, process)? != 0),
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum BinOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Sub,
+    Mul,
+}
+
+#[derive(Debug,Clone)]
+pub enum Expr {
+    Num(u64),
+    Reg(String),
+    /* address and size in bytes */
+    Deref(Box<Expr>, usize),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Bin(BinOp, Box<Expr>, Box<Expr>),
+}
+
+/* lowest binding first */
+static LEVELS: &'static [&'static [(&'static str, BinOp)]] = &[
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[("==", BinOp::Eq), ("!=", BinOp::Ne), ("<=", BinOp::Le), (">=", BinOp::Ge),
+      ("<", BinOp::Lt), (">", BinOp::Gt)],
+    &[("|", BinOp::BitOr)],
+    &[("^", BinOp::BitXor)],
+    &[("&", BinOp::BitAnd)],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+    &[("*", BinOp::Mul)],
+];
+
+impl Expr {
+    pub fn parse(src: &str) -> Result<Expr, DebugError> {
+
+        let mut parser = Parser { src: src.as_bytes(), pos: 0 };
+        let expr = parser.binary(0)?;
+
+        parser.skip_space();
+        if parser.pos != parser.src.len() {
+            return Err("Unexpected trailing input in expression".into());
+        }
+
+        Ok(expr)
+    }
+
+    /* values are 64 bit, comparisons are signed */
+    pub fn eval(&self, regs: &x86_64_Registers, process: &Process<x86_64_Registers>)
+        -> Result<u64, DebugError>
+    {
+        self.eval_with(regs, &|addr| process.peek(addr))
+    }
+
+    /* peek reads the word at an address */
+    pub fn eval_with<F>(&self, regs: &x86_64_Registers, peek: &F) -> Result<u64, DebugError>
+        where F: Fn(u64) -> Result<u64, DebugError>
+    {
+        match self {
+            &Expr::Num(n) => Ok(n),
+            &Expr::Reg(ref name) => match regs.get(name) {
+                Some(value) => Ok(value),
+                None => Err("Unknown register in expression".into()),
+            },
+            &Expr::Deref(ref addr, size) => {
+                let word = peek(addr.eval_with(regs, peek)?)?;
+
+                Ok(match size {
+                    8 => word,
+                    _ => word & ((1 << (size * 8)) - 1),
+                })
+            },
+            &Expr::Not(ref e) => Ok((e.eval_with(regs, peek)? == 0) as u64),
+            &Expr::Neg(ref e) => Ok(e.eval_with(regs, peek)?.wrapping_neg()),
+            &Expr::Bin(BinOp::Or, ref l, ref r) => {
+                Ok((l.eval_with(regs, peek)? != 0 || r.eval_with(regs, peek)? != 0) as u64)
+            },
+            &Expr::Bin(BinOp::And, ref l, ref r) => {
+                Ok((l.eval_with(regs, peek)? != 0 && r.eval_with(regs, peek)? != 0) as u64)
+            },
+            &Expr::Bin(op, ref l, ref r) => {
+                let l = l.eval_with(regs, peek)?;
+                let r = r.eval_with(regs, peek)?;
+
+                Ok(match op {
+                    BinOp::Eq => (l == r) as u64,
+                    BinOp::Ne => (l != r) as u64,
+                    BinOp::Lt => ((l as i64) < (r as i64)) as u64,
+                    BinOp::Le => ((l as i64) <= (r as i64)) as u64,
+                    BinOp::Gt => ((l as i64) > (r as i64)) as u64,
+                    BinOp::Ge => ((l as i64) >= (r as i64)) as u64,
+                    BinOp::BitOr => l | r,
+                    BinOp::BitXor => l ^ r,
+                    BinOp::BitAnd => l & r,
+                    BinOp::Add => l.wrapping_add(r),
+                    BinOp::Sub => l.wrapping_sub(r),
+                    BinOp::Mul => l.wrapping_mul(r),
+                    BinOp::Or | BinOp::And => unreachable!(),
+                })
+            },
+        }
+    }
+}
+
+/* recursive descent over the bytes of the expression */
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_space(&mut self) {
+        while self.pos < self.src.len() && (self.src[self.pos] as char).is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+
+        if self.src[self.pos..].starts_with(token.as_bytes()) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /* don't take `&` out of `&&` or `|` out of `||` */
+    fn eat_op(&mut self, token: &str) -> bool {
+        self.skip_space();
+
+        let doubled = (token == "&" || token == "|")
+            && self.src[self.pos..].starts_with(&[token.as_bytes()[0]; 2]);
+
+        !doubled && self.eat(token)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, DebugError> {
+
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+
+        'outer: loop {
+            for &(token, op) in LEVELS[level] {
+                if self.eat_op(token) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Bin(op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, DebugError> {
+        if self.eat("!") {
+            Ok(Expr::Not(Box::new(self.unary()?)))
+        } else if self.eat("-") {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, DebugError> {
+
+        if self.eat("(") {
+            let expr = self.binary(0)?;
+
+            if !self.eat(")") {
+                return Err("Missing ) in expression".into());
+            }
+
+            return Ok(expr);
+        }
+
+        /* [addr] reads 8 bytes, [addr, size] reads 1, 2, 4 or 8 */
+        if self.eat("[") {
+            let addr = self.binary(0)?;
+
+            let size = if self.eat(",") {
+                match self.number()? {
+                    n @ 1 | n @ 2 | n @ 4 | n @ 8 => n as usize,
+                    _ => { return Err("Memory reads in expressions are 1, 2, 4 or 8 bytes".into()); },
+                }
+            } else {
+                8
+            };
+
+            if !self.eat("]") {
+                return Err("Missing ] in expression".into());
+            }
+
+            return Ok(Expr::Deref(Box::new(addr), size));
+        }
+
+        self.skip_space();
+
+        match self.peek() {
+            Some(c) if (c as char).is_digit(10) => Ok(Expr::Num(self.number()?)),
+            Some(c) if (c as char).is_alphabetic() => {
+                let start = self.pos;
+
+                while self.peek().map_or(false, |c| (c as char).is_alphanumeric() || c == b'_') {
+                    self.pos += 1;
+                }
+
+                let name = String::from_utf8_lossy(&self.src[start..self.pos]).into_owned();
+
+                if x86_64_Registers::default().get(&name).is_none() {
+                    return Err("Unknown register in expression".into());
+                }
+
+                Ok(Expr::Reg(name))
+            },
+            _ => Err("Unexpected token in expression".into()),
+        }
+    }
+
+    fn number(&mut self) -> Result<u64, DebugError> {
+
+        self.skip_space();
+
+        let hex = self.src[self.pos..].starts_with(b"0x");
+        if hex {
+            self.pos += 2;
+        }
+
+        let start = self.pos;
+        while self.peek().map_or(false, |c| (c as char).is_digit(if hex { 16 } else { 10 })) {
+            self.pos += 1;
+        }
+
+        if start == self.pos {
+            return Err("Expected a number in expression".into());
+        }
+
+        let digits = String::from_utf8_lossy(&self.src[start..self.pos]).into_owned();
+
+        Ok(u64::from_str_radix(&digits, if hex { 16 } else { 10 })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn eval(src: &str) -> u64 {
+        let mut regs = x86_64_Registers::default();
+        regs.rax = 6;
+        regs.rbx = 3;
+        regs.rdi = 0x1000;
+
+        let peek = |addr: u64| -> Result<u64, DebugError> {
+            match addr {
+                0x1000 => Ok(0x1122334455667788),
+                _ => Err("unmapped".into()),
+            }
+        };
+
+        Expr::parse(src).unwrap().eval_with(&regs, &peek).unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7);
+        assert_eq!(eval("(1 + 2) * 3"), 9);
+        assert_eq!(eval("8 - 2 - 1"), 5);
+        assert_eq!(eval("1 | 6 ^ 3 & 1"), 7);
+        assert_eq!(eval("1 || 0 && 0"), 1);
+        assert_eq!(eval("rax + 1 == 7 && rbx < 4"), 1);
+        assert_eq!(eval("!0 + 1"), 2);
+    }
+
+    #[test]
+    fn bitand_and_logical_and() {
+        assert_eq!(eval("rax & rbx"), 2);
+        assert_eq!(eval("rax && rbx"), 1);
+        assert_eq!(eval("rax&1&&rbx"), 0);
+        assert_eq!(eval("rax | 1 || 0"), 1);
+
+        match Expr::parse("rax&&rbx").unwrap() {
+            Expr::Bin(BinOp::And, _, _) => {},
+            expr => panic!("parsed as {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn sized_deref() {
+        assert_eq!(eval("[rdi]"), 0x1122334455667788);
+        assert_eq!(eval("[rdi, 8]"), 0x1122334455667788);
+        assert_eq!(eval("[rdi, 4]"), 0x55667788);
+        assert_eq!(eval("[rdi, 2]"), 0x7788);
+        assert_eq!(eval("[0x1000, 1] == 0x88"), 1);
+        assert!(Expr::parse("[rdi, 3]").is_err());
+    }
+
+    #[test]
+    fn signed_comparisons_and_literals() {
+        assert_eq!(eval("-1 < 0"), 1);
+        assert_eq!(eval("0xffffffffffffffff > 0"), 0);
+        assert_eq!(eval("0x10 == 16"), 1);
+    }
+
+    #[test]
+    fn bad_input() {
+        assert!(Expr::parse("rax +").is_err());
+        assert!(Expr::parse("rax 1").is_err());
+        assert!(Expr::parse("nosuchreg == 1").is_err());
+        assert!(Expr::parse("(rax").is_err());
+    }
+}
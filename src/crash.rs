@@ -0,0 +1,448 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hasher;
+
+use nix::sys::signal::Signal;
+use vm_info::mapped_region::MemoryRegion;
+use twox_hash::XxHash;
+
+use disasm::Instruction;
+use event::SigInfo;
+use registers::{x86_64_Registers,REGISTER_NAMES};
+use signals::{signal_name,is_fault};
+use unwind::StackFrame;
+use json;
+
+/* instructions shown before and after rip */
+pub const WINDOW_BEFORE: usize = 5;
+pub const WINDOW_AFTER: usize = 6;
+
+/* frames that decide which bucket a crash goes in, deeper ones vary with the input */
+pub const BUCKET_FRAMES: usize = 5;
+
+/* signals a program dies of because of a bug, rather than being told to */
+pub fn is_crash(signo: i32) -> bool {
+    is_fault(signo) || signo == Signal::SIGABRT as i32
+}
+
+/* si_code as the headers spell it */
+pub fn code_name(signo: i32, code: i32) -> Option<&'static str> {
+
+    use self::Signal::*;
+    let name = match (Signal::from_c_int(signo), code) {
+        (_, 0) => "SI_USER",
+        (_, 0x80) => "SI_KERNEL",
+        (_, -1) => "SI_QUEUE",
+        (_, -6) => "SI_TKILL",
+        (Ok(SIGSEGV), 1) => "SEGV_MAPERR",
+        (Ok(SIGSEGV), 2) => "SEGV_ACCERR",
+        (Ok(SIGSEGV), 3) => "SEGV_BNDERR",
+        (Ok(SIGSEGV), 4) => "SEGV_PKUERR",
+        (Ok(SIGBUS), 1) => "BUS_ADRALN",
+        (Ok(SIGBUS), 2) => "BUS_ADRERR",
+        (Ok(SIGBUS), 3) => "BUS_OBJERR",
+        (Ok(SIGBUS), 4) => "BUS_MCEERR_AR",
+        (Ok(SIGBUS), 5) => "BUS_MCEERR_AO",
+        (Ok(SIGILL), 1) => "ILL_ILLOPC",
+        (Ok(SIGILL), 2) => "ILL_ILLOPN",
+        (Ok(SIGILL), 3) => "ILL_ILLADR",
+        (Ok(SIGILL), 4) => "ILL_ILLTRP",
+        (Ok(SIGILL), 5) => "ILL_PRVOPC",
+        (Ok(SIGILL), 6) => "ILL_PRVREG",
+        (Ok(SIGILL), 7) => "ILL_COPROC",
+        (Ok(SIGILL), 8) => "ILL_BADSTK",
+        (Ok(SIGFPE), 1) => "FPE_INTDIV",
+        (Ok(SIGFPE), 2) => "FPE_INTOVF",
+        (Ok(SIGFPE), 3) => "FPE_FLTDIV",
+        (Ok(SIGFPE), 4) => "FPE_FLTOVF",
+        (Ok(SIGFPE), 5) => "FPE_FLTUND",
+        (Ok(SIGFPE), 6) => "FPE_FLTRES",
+        (Ok(SIGFPE), 7) => "FPE_FLTINV",
+        (Ok(SIGFPE), 8) => "FPE_FLTSUB",
+        _ => { return None; },
+    };
+
+    Some(name)
+}
+
+/* a line of /proc/pid/maps */
+#[derive(Debug,Clone)]
+pub struct MapEntry {
+    pub start: u64,
+    pub end: u64,
+    /* r-xp */
+    pub perms: String,
+    pub offset: u64,
+    /* None for anonymous memory, [stack] and the like otherwise */
+    pub path: Option<String>,
+}
+
+impl MapEntry {
+    pub fn from_region(region: &MemoryRegion) -> Self {
+        MapEntry {
+            start: region.start_address as u64,
+            end: region.end_address as u64,
+            perms: format!("{:?}", region.permissions),
+            offset: region.offset as u64,
+            path: region.pathname.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        json::object(&[
+            ("start", json::string(&format!("0x{:x}", self.start))),
+            ("end", json::string(&format!("0x{:x}", self.end))),
+            ("perms", json::string(&self.perms)),
+            ("offset", json::string(&format!("0x{:x}", self.offset))),
+            ("path", self.path.as_ref().map_or("null".to_string(), |path| json::string(path))),
+        ])
+    }
+}
+
+impl fmt::Display for MapEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:x}-{:x} {} {:08x} {}", self.start, self.end, self.perms,
+               self.offset, self.path.as_ref().map_or("", |path| path.as_str()))
+    }
+}
+
+/* everything triage needs about a fatal signal, see Debugger::crash_report */
+#[derive(Debug,Clone)]
+pub struct CrashReport {
+    pub pid: u32,
+    pub tid: u32,
+    pub info: SigInfo,
+    /* None for signals that don't come from an instruction, e.g. abort */
+    pub fault_addr: Option<u64>,
+    pub regs: x86_64_Registers,
+    /* around rip, rip is the instruction at regs.rip */
+    pub disassembly: Vec<Instruction>,
+    pub backtrace: Vec<StackFrame>,
+    /* mapping the fault address falls in, None if it is unmapped */
+    pub mapping: Option<MapEntry>,
+}
+
+impl CrashReport {
+    pub fn signal(&self) -> String {
+        signal_name(self.info.signo)
+    }
+
+    /* one line of JSON, addresses are hex strings */
+    pub fn to_json(&self) -> String {
+
+        let hex = |value: u64| json::string(&format!("0x{:x}", value));
+
+        let regs: Vec<(&str, String)> = REGISTER_NAMES.iter()
+            .map(|&name| (name, hex(self.regs.get(name).unwrap_or(0))))
+            .collect();
+
+        let disassembly: Vec<String> = self.disassembly.iter()
+            .map(|insn| json::object(&[
+                ("addr", hex(insn.addr)),
+                ("bytes", json::bytes(&insn.bytes)),
+                ("text", json::string(&insn.to_string())),
+                ("current", (insn.addr == self.regs.rip).to_string()),
+            ]))
+            .collect();
+
+        let backtrace: Vec<String> = self.backtrace.iter()
+            .map(|frame| json::object(&[
+                ("index", frame.index.to_string()),
+                ("pc", hex(frame.pc)),
+                ("cfa", hex(frame.cfa)),
+                ("symbol", json::string(&frame.symbol)),
+                ("module", frame.module.as_ref().map_or("null".to_string(), |module| json::string(module))),
+                ("offset", hex(frame.offset)),
+                ("location", frame.location.as_ref()
+                    .map_or("null".to_string(), |location| json::string(&location.to_string()))),
+            ]))
+            .collect();
+
+        json::object(&[
+            ("pid", self.pid.to_string()),
+            ("tid", self.tid.to_string()),
+            ("signal", json::string(&self.signal())),
+            ("signo", self.info.signo.to_string()),
+            ("code", self.info.code.to_string()),
+            ("code_name", code_name(self.info.signo, self.info.code)
+                .map_or("null".to_string(), json::string)),
+            ("fault_addr", self.fault_addr.map_or("null".to_string(), &hex)),
+            ("registers", json::object(&regs)),
+            ("disassembly", json::array(&disassembly)),
+            ("backtrace", json::array(&backtrace)),
+            ("mapping", self.mapping.as_ref().map_or("null".to_string(), |map| map.to_json())),
+            ("bucket", json::string(&format!("{:016x}", self.bucket(BUCKET_FRAMES)))),
+        ])
+    }
+
+    /*
+     *  xxHash of the signal and the innermost frames as module+offset, so
+     *  the same bug hashes the same no matter where ASLR put things
+     */
+    pub fn bucket(&self, frames: usize) -> u64 {
+
+        let mut hasher = XxHash::default();
+        hasher.write_i32(self.info.signo);
+
+        for frame in self.backtrace.iter().take(frames) {
+            match frame.module {
+                /* the length keeps one name running into the next offset apart */
+                Some(ref module) => {
+                    hasher.write_u64(module.len() as u64);
+                    hasher.write(module.as_bytes());
+                    hasher.write_u64(frame.offset);
+                },
+                /* no length is this long, the pc would differ every run */
+                None => { hasher.write_u64(u64::MAX); },
+            }
+        }
+
+        hasher.finish()
+    }
+}
+
+/* crashes with the same bucket, the first one stands in for the rest */
+#[derive(Debug,Clone)]
+pub struct Bucket {
+    pub hash: u64,
+    pub count: usize,
+    pub first: CrashReport,
+}
+
+/* unique crashes out of many runs */
+#[derive(Debug,Clone)]
+pub struct CrashBuckets {
+    /* frames hashed, see CrashReport::bucket */
+    pub frames: usize,
+    buckets: Vec<Bucket>,
+    index: HashMap<u64, usize>,
+}
+
+impl CrashBuckets {
+    pub fn new(frames: usize) -> Self {
+        CrashBuckets {
+            frames: frames,
+            buckets: vec![],
+            index: HashMap::new(),
+        }
+    }
+
+    /* true for a crash not seen before */
+    pub fn add(&mut self, report: CrashReport) -> bool {
+
+        let hash = report.bucket(self.frames);
+
+        if let Some(&i) = self.index.get(&hash) {
+            self.buckets[i].count += 1;
+            return false;
+        }
+
+        self.index.insert(hash, self.buckets.len());
+        self.buckets.push(Bucket {
+            hash: hash,
+            count: 1,
+            first: report,
+        });
+
+        true
+    }
+
+    pub fn get(&self, hash: u64) -> Option<&Bucket> {
+        self.index.get(&hash).map(|&i| &self.buckets[i])
+    }
+
+    /* in the order they were first seen */
+    pub fn buckets(&self) -> &[Bucket] {
+        &self.buckets
+    }
+
+    /* unique crashes */
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /* every crash added, duplicates included */
+    pub fn total(&self) -> usize {
+        self.buckets.iter().map(|bucket| bucket.count).sum()
+    }
+
+    pub fn to_json(&self) -> String {
+
+        let buckets: Vec<String> = self.buckets.iter()
+            .map(|bucket| json::object(&[
+                ("bucket", json::string(&format!("{:016x}", bucket.hash))),
+                ("count", bucket.count.to_string()),
+                ("signal", json::string(&bucket.first.signal())),
+                ("top", json::string(&bucket.first.backtrace.first()
+                    .map_or(String::new(), |frame| frame.symbol.clone()))),
+                ("first", bucket.first.to_json()),
+            ]))
+            .collect();
+
+        json::object(&[
+            ("frames", self.frames.to_string()),
+            ("unique", self.len().to_string()),
+            ("total", self.total().to_string()),
+            ("buckets", json::array(&buckets)),
+        ])
+    }
+}
+
+/* what a terminal user reads */
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        write!(f, "thread {} crashed with {}", self.tid, self.signal())?;
+
+        if let Some(name) = code_name(self.info.signo, self.info.code) {
+            write!(f, " ({})", name)?;
+        }
+
+        if let Some(addr) = self.fault_addr {
+            write!(f, " at 0x{:x}", addr)?;
+        }
+
+        writeln!(f)?;
+
+        match self.mapping {
+            Some(ref map) => writeln!(f, "  in {}", map)?,
+            None if self.fault_addr.is_some() => writeln!(f, "  in no mapping")?,
+            None => {},
+        }
+
+        for (i, name) in REGISTER_NAMES.iter().enumerate() {
+            write!(f, "{:>8} 0x{:016x}{}", name, self.regs.get(name).unwrap_or(0),
+                   if i % 3 == 2 { "\n" } else { "" })?;
+        }
+
+        for insn in self.disassembly.iter() {
+            let marker = if insn.addr == self.regs.rip { "=>" } else { "  " };
+            writeln!(f, "{} 0x{:x}: {}", marker, insn.addr, insn)?;
+        }
+
+        for frame in self.backtrace.iter() {
+            writeln!(f, "{}", frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const SIGSEGV: i32 = Signal::SIGSEGV as i32;
+
+    /* module, offset into it, symbol */
+    const FRAMES: &[(&str, u64, &str)] = &[
+        ("crash", 0x1149, "fault+0x10"),
+        ("crash", 0x1180, "main+0x1b"),
+        ("libc.so.6", 0x2724a, "libc.so.6!__libc_start_call_main+0x7a"),
+        ("libc.so.6", 0x27305, "libc.so.6!__libc_start_main+0x85"),
+        ("crash", 0x1065, "_start+0x25"),
+    ];
+
+    /* the same crash with the executable at exe and libc at libc */
+    fn report(signo: i32, exe: u64, libc: u64) -> CrashReport {
+
+        let backtrace = FRAMES.iter().enumerate()
+            .map(|(i, &(module, offset, symbol))| {
+                let base = if module == "crash" { exe } else { libc };
+                let mut regs = x86_64_Registers::default();
+                regs.rip = base + offset;
+
+                StackFrame {
+                    index: i,
+                    pc: base + offset,
+                    cfa: 0x7ffc_0000_0000 + exe % 0x10000 + i as u64 * 0x10,
+                    symbol: symbol.to_string(),
+                    module: Some(module.to_string()),
+                    offset: offset,
+                    location: None,
+                    regs: regs,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        CrashReport {
+            pid: exe as u32,
+            tid: exe as u32,
+            info: SigInfo { signo: signo, errno: 0, code: 1, addr: 0 },
+            fault_addr: Some(0),
+            regs: backtrace[0].regs,
+            disassembly: vec![],
+            backtrace: backtrace,
+            mapping: None,
+        }
+    }
+
+    #[test]
+    fn stable_across_load_addresses() {
+        let first = report(SIGSEGV, 0x5555_5555_4000, 0x7f12_3456_7000);
+        let second = report(SIGSEGV, 0x5600_dead_0000, 0x7fab_cdef_0000);
+
+        assert_eq!(first.bucket(BUCKET_FRAMES), second.bucket(BUCKET_FRAMES));
+        assert_eq!(first.bucket(1), second.bucket(1));
+    }
+
+    #[test]
+    fn different_crashes() {
+        let first = report(SIGSEGV, 0x5555_5555_4000, 0x7f12_3456_7000);
+
+        let other = report(Signal::SIGBUS as i32, 0x5555_5555_4000, 0x7f12_3456_7000);
+        assert!(first.bucket(BUCKET_FRAMES) != other.bucket(BUCKET_FRAMES));
+
+        let mut other = first.clone();
+        other.backtrace[1].offset += 4;
+        assert!(first.bucket(BUCKET_FRAMES) != other.bucket(BUCKET_FRAMES));
+        /* deeper than the frames hashed */
+        assert_eq!(first.bucket(1), other.bucket(1));
+
+        let mut other = first.clone();
+        other.backtrace[0].module = Some("libfoo.so".to_string());
+        assert!(first.bucket(BUCKET_FRAMES) != other.bucket(BUCKET_FRAMES));
+    }
+
+    #[test]
+    fn frames_outside_modules() {
+        let mut first = report(SIGSEGV, 0x5555_5555_4000, 0x7f12_3456_7000);
+        let mut second = report(SIGSEGV, 0x5600_dead_0000, 0x7fab_cdef_0000);
+
+        /* e.g. JIT code, offset is the pc */
+        for report in [&mut first, &mut second].iter_mut() {
+            let frame = &mut report.backtrace[1];
+            frame.module = None;
+            frame.offset = frame.pc;
+        }
+
+        assert!(first.backtrace[1].pc != second.backtrace[1].pc);
+        assert_eq!(first.bucket(BUCKET_FRAMES), second.bucket(BUCKET_FRAMES));
+
+        /* still apart from the crash with a module there */
+        let with_module = report(SIGSEGV, 0x5555_5555_4000, 0x7f12_3456_7000);
+        assert!(first.bucket(BUCKET_FRAMES) != with_module.bucket(BUCKET_FRAMES));
+    }
+
+    #[test]
+    fn buckets() {
+        let mut buckets = CrashBuckets::new(BUCKET_FRAMES);
+
+        assert!(buckets.add(report(SIGSEGV, 0x5555_5555_4000, 0x7f12_3456_7000)));
+        assert!(!buckets.add(report(SIGSEGV, 0x5600_dead_0000, 0x7fab_cdef_0000)));
+        assert!(buckets.add(report(Signal::SIGABRT as i32, 0x5555_5555_4000, 0x7f12_3456_7000)));
+
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets.total(), 3);
+
+        let hash = report(SIGSEGV, 0, 0).bucket(BUCKET_FRAMES);
+        let bucket = buckets.get(hash).unwrap();
+        assert_eq!(bucket.count, 2);
+        assert_eq!(bucket.first.pid, 0x5555_4000);
+    }
+}
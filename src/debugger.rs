@@ -1,19 +1,52 @@
 use spawn_ptrace::CommandPtraceSpawn;
 
-use std::process::{Child,Stdio,Command};
+use std::process::{Child,Command};
 use std::collections::HashMap;
 use std::result::Result;
-use std::cell::RefCell;
+use std::cell::{Cell,Ref,RefCell};
 use std::rc::Rc;
 use std::boxed::Box;
 use std::ffi::OsStr;
+use std::fs::{self,File};
+use std::mem;
+use std::io::{Read,Write};
+
+use libc::{
+    TRAP_TRACE,
+    TRAP_BRKPT,
+    PTRACE_O_TRACEFORK,
+    PTRACE_O_TRACEVFORK,
+    PTRACE_O_TRACECLONE,
+    PTRACE_O_TRACEEXEC,
+    PTRACE_O_TRACEVFORKDONE,
+    PTRACE_O_TRACESYSGOOD,
+    ENOSYS,
+};
+use nix::sys::signal::Signal;
 
 use breakpoint::Breakpoint;
-use process::Process;
-use status::Status;
+use process::{Process,SYSCALL_INFO_ENTRY,SYSCALL_INFO_EXIT};
+use status::{Status,PtraceEvent};
+use event::{StopEvent,SigInfo};
+use signals::{SignalPolicy,signal_name,is_fault};
+use syscall::{Syscall,SyscallContext,syscall_number};
 use registers::{Register,x86_64_Registers};
+use fpregs::{FpRegisters,Vector};
 use error::DebugError;
 use phantom::PhantomManager;
+use thread::{ThreadManager,Resume};
+use memory::Memory;
+use watchpoint::{self,Watchpoint,WatchKind,DEBUG_SLOTS,fits_debugreg};
+use tracepoint::{TraceSpec,TraceRecord};
+use disasm::{self,Instruction};
+use elf::Elf;
+use module::{self,Module};
+use solib::{RDebug,Rendezvous,Library,LibraryChange};
+use line::{LineTable,Location};
+use debuginfo::{self,DebugInfo,Frame,Place,Variable};
+use unwind::{self,CallFrameInfo,UnwindRow,Rule,StackFrame,MAX_FRAMES};
+use crash::{self,CrashReport,MapEntry,is_crash};
+use builder::DebuggerBuilder;
 
 #[macro_export]
 macro_rules! pc {
@@ -29,12 +62,14 @@ macro_rules! pc {
 
 #[macro_export]
 macro_rules! bp {
-    ($dbg: expr, $addr:expr $(, name: $name:expr)* $(, enabled: $enabled:expr)*) => {
+    ($dbg: expr, $addr:expr $(, name: $name:expr)* $(, enabled: $enabled:expr)* $(, cond: $cond:expr)*) => {
         $dbg.breakpoint($addr)
             .expect("failed to set breakpoint")
             $(.name($name))*
             $(.enabled($enabled)
-            .expect("failed to disable breakpoint"))*;
+            .expect("failed to disable breakpoint"))*
+            $(.condition_expr($cond)
+            .expect("failed to parse breakpoint condition"))*;
     }
 }
 
@@ -47,7 +82,12 @@ macro_rules! cont {
 }
 
 type OptionCell<T> = Rc<RefCell<Option<T>>>;
-type BoxedDebuggerFn = Box<Fn(&Debugger)>;
+type BoxedDebuggerFn = Box<dyn Fn(&Debugger)>;
+type BoxedSyscallFn = Box<dyn Fn(&Debugger, &Syscall)>;
+type BoxedInterceptFn = Box<dyn Fn(&mut SyscallContext)>;
+
+/* threads are traced from birth, syscall stops are told apart from traps */
+const BASE_OPTIONS: i32 = PTRACE_O_TRACECLONE | PTRACE_O_TRACESYSGOOD;
 
 bitflags! {
     pub struct LogLevel: u32 {
@@ -56,220 +96,2291 @@ bitflags! {
         #[allow(non_upper_case_globals)]
         const Commands = 0b10;
         #[allow(non_upper_case_globals)]
+        const Events = 0b100;
+        #[allow(non_upper_case_globals)]
         const Silent = 0b00;
     }
-}
+}
+
+/* what software watchpoints compare against while stepping */
+struct SoftWatch {
+    memory: Memory,
+    snapshots: HashMap<u64,Vec<u8>>,
+    pcs: HashMap<u32,u64>,
+}
+
+/* which side of a fork the debugger stays with */
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Follow {
+    Parent,
+    Child,
+    /* children get their own debugger, see Debugger::forked */
+    Both,
+}
+
+
+pub struct Debugger {
+    pub process: Process<x86_64_Registers>,
+    pub breakpoints: RefCell<HashMap<u64,Breakpoint>>,
+    pub watchpoints: HashMap<u64,Watchpoint>,
+    /* keyed like breakpoints, the trap itself lives there */
+    pub tracepoints: RefCell<HashMap<u64,TraceSpec>>,
+    pub file: String,
+    pub args: Vec<String>,
+    pub child: Option<Child>,
+    pub pc: OptionCell<u64>,
+    pub log: LogLevel,
+    phantom_mgr: Rc<RefCell<PhantomManager<x86_64_Registers>>>,
+    actions_at: HashMap<u64,Vec<BoxedDebuggerFn>>,
+    watch_actions: HashMap<u64,Vec<BoxedDebuggerFn>>,
+    actions: Vec<BoxedDebuggerFn>,
+    init_state: bool,
+    follow: Option<Follow>,
+    signals: HashMap<i32,SignalPolicy>,
+    syscalls: Cell<bool>,
+    syscall_actions: HashMap<u64,Vec<BoxedSyscallFn>>,
+    interceptors: HashMap<u64,Vec<BoxedInterceptFn>>,
+    /* syscall number and faked return of calls skipped on entry, by thread */
+    skipped: RefCell<HashMap<u32,(u64,i64)>>,
+    forked: RefCell<Vec<Debugger>>,
+    lifted: RefCell<Vec<u64>>,
+    threads: RefCell<ThreadManager>,
+    current: Cell<u32>,
+    /* thread, address and stack pointer run_to is waiting for */
+    until: Cell<Option<(u32,u64,Option<u64>)>>,
+    /* thread sitting just past one of our traps */
+    trapped: Cell<Option<u32>>,
+    trace: RefCell<Vec<TraceRecord>>,
+    /* symbols of the running image, None if it couldn't be read */
+    elf: RefCell<Option<Rc<Elf>>>,
+    /* libraries by path, None for mapped files that aren't ELF */
+    elves: RefCell<HashMap<String,Option<Rc<Elf>>>>,
+    /* .debug_line of the executable, parsed when first needed */
+    lines: RefCell<Option<Rc<LineTable>>>,
+    /* .debug_info of the executable, same */
+    debug_info: RefCell<Option<Rc<DebugInfo>>>,
+    /* .eh_frame and .debug_frame by module path, None if there are none */
+    frames: RefCell<HashMap<String,Option<Rc<CallFrameInfo>>>>,
+    /* the last fatal signal, kept after the process is gone */
+    crash: RefCell<Option<CrashReport>>,
+    /* None for a static binary */
+    rendezvous: RefCell<Option<Rendezvous>>,
+    /* the trap on r_brk, apart from the breakpoints the user set */
+    rendezvous_trap: RefCell<Option<Breakpoint>>,
+    library_stops: Cell<bool>,
+    /* breakpoints waiting for a library, by location */
+    pending: RefCell<Vec<(String,Breakpoint)>>,
+    /* location of breakpoints armed from pending, they go back on dlclose */
+    locations: RefCell<HashMap<u64,String>>,
+    /* last number given to a breakpoint or watchpoint */
+    numbered: Cell<usize>,
+}
+
+impl Debugger {
+
+    pub fn new<T: AsRef<OsStr>>(binary: T, args: Vec<T>)
+        -> Result<Self,DebugError>
+    {
+        DebuggerBuilder::new(binary)
+            .args(args)
+            .spawn()
+    }
+
+    /* starts a command prepared by DebuggerBuilder::command under ptrace */
+    pub fn spawn_command(command: &mut Command, file: String, args: Vec<String>)
+        -> Result<Self,DebugError>
+    {
+        let child = command.spawn_ptrace()?;
+        let process = Process::<x86_64_Registers>::new(child.id());
+
+        let d = Debugger::init(process, Some(child), file, args)?;
+        d.track_libraries()?;
+
+        Ok(d)
+    }
+
+    pub fn attach(pid: u32) -> Result<Self, DebugError> {
+
+        let process = Process::<x86_64_Registers>::new(pid);
+
+        process.attach()?;
+        /* attaching sends a SIGSTOP, wait for it to land */
+        process.wait_stop()?;
+
+        let file = fs::read_link(format!("/proc/{}/exe", pid))?
+            .to_string_lossy().into_owned();
+
+        let mut cmdline = Vec::new();
+        File::open(format!("/proc/{}/cmdline", pid))?
+            .read_to_end(&mut cmdline)?;
+
+        /* cmdline is a nul terminated list starting with argv[0], args may be empty */
+        if cmdline.last() == Some(&0) {
+            cmdline.pop();
+        }
+
+        let args = cmdline.split(|&b| b == 0)
+            .skip(1)
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+
+        let d = Debugger::init(process, None, file, args)?;
+
+        /* every other thread has to be attached on its own, and stopped */
+        /* before the trap on r_brk goes in */
+        for task in fs::read_dir(format!("/proc/{}/task", pid))? {
+            let tid = task?.file_name().to_string_lossy().parse::<u32>()?;
+
+            if tid != pid {
+                let thread = Process::<x86_64_Registers>::new(tid);
+                thread.attach()?;
+                thread.wait_stop()?;
+                thread.set_options(BASE_OPTIONS)?;
+
+                d.threads.borrow_mut().add(tid);
+            }
+        }
+
+        d.track_libraries()?;
+
+        d.log_command(&format!("attached to process {}", pid));
+
+        Ok(d)
+    }
+
+    fn init(process: Process<x86_64_Registers>, child: Option<Child>, file: String, args: Vec<String>)
+        -> Result<Self, DebugError>
+    {
+        let pid = process.pid();
+        let pc = pc!(process);
+
+        process.set_options(BASE_OPTIONS)?;
+
+        let elf = Debugger::load_elf(pid, &file);
+
+        Ok(Debugger {
+            process: process,
+            breakpoints: RefCell::new(HashMap::new()),
+            watchpoints: HashMap::new(),
+            tracepoints: RefCell::new(HashMap::new()),
+            actions: vec![],
+            file: file,
+            args: args,
+            child: child,
+            phantom_mgr: Rc::new(RefCell::new(PhantomManager::new(pid.into()))),
+            init_state: false,
+            log: LogLevel::Silent,
+            pc: Rc::new(RefCell::new(Some(pc))),
+            actions_at: HashMap::new(),
+            watch_actions: HashMap::new(),
+            follow: None,
+            signals: HashMap::new(),
+            syscalls: Cell::new(false),
+            syscall_actions: HashMap::new(),
+            interceptors: HashMap::new(),
+            skipped: RefCell::new(HashMap::new()),
+            forked: RefCell::new(vec![]),
+            lifted: RefCell::new(vec![]),
+            threads: RefCell::new(ThreadManager::new(pid)),
+            current: Cell::new(pid),
+            until: Cell::new(None),
+            trapped: Cell::new(None),
+            trace: RefCell::new(vec![]),
+            elf: RefCell::new(elf),
+            elves: RefCell::new(HashMap::new()),
+            lines: RefCell::new(None),
+            debug_info: RefCell::new(None),
+            frames: RefCell::new(HashMap::new()),
+            crash: RefCell::new(None),
+            rendezvous: RefCell::new(None),
+            rendezvous_trap: RefCell::new(None),
+            library_stops: Cell::new(false),
+            pending: RefCell::new(vec![]),
+            locations: RefCell::new(HashMap::new()),
+            numbered: Cell::new(0),
+        })
+    }
+
+    /* the binary may have come from PATH, the kernel knows which one runs */
+    fn load_elf(pid: u32, file: &str) -> Option<Rc<Elf>> {
+        Elf::load(&format!("/proc/{}/exe", pid))
+            .or_else(|_| Elf::load(file))
+            .ok()
+            .map(Rc::new)
+    }
+
+    pub fn elf(&self) -> Option<Rc<Elf>> {
+        self.elf.borrow().clone()
+    }
+
+    /* runtime minus link time addresses of the executable, 0 unless PIE */
+    pub fn load_bias(&self) -> Result<u64, DebugError> {
+        let elf = self.elf().ok_or("No symbols loaded")?;
+
+        if !elf.is_pie() {
+            return Ok(0);
+        }
+
+        module::exe_bias(self.process.pid(), &elf)
+    }
+
+    /* the executable and every library mapped right now */
+    pub fn modules(&self) -> Result<Vec<Module>, DebugError> {
+
+        let pid = self.process.pid();
+        let exe = fs::read_link(format!("/proc/{}/exe", pid))?
+            .to_string_lossy().into_owned();
+
+        module::modules(pid, |path| {
+            if path == exe {
+                return self.elf();
+            }
+
+            self.elves.borrow_mut().entry(path.to_string())
+                .or_insert_with(|| Elf::load(path).ok().map(Rc::new))
+                .clone()
+        })
+    }
+
+    pub fn module(&self, name: &str) -> Result<Module, DebugError> {
+        self.modules()?.into_iter()
+            .find(|module| module.is(name))
+            .ok_or("No such module loaded".into())
+    }
+
+    fn is_exe(&self, module: &Module) -> bool {
+        match (module.elf.as_ref(), self.elf()) {
+            (Some(elf), Some(exe)) => Rc::ptr_eq(elf, &exe),
+            _ => false,
+        }
+    }
+
+    /* runtime address of a function or object, the executable goes first */
+    pub fn symbol(&self, name: &str) -> Result<u64, DebugError> {
+
+        if let Some(sym) = self.elf().as_ref().and_then(|elf| elf.symbol(name)) {
+            return Ok(sym.addr.wrapping_add(self.load_bias()?));
+        }
+
+        self.modules()?.iter()
+            .filter_map(|module| module.symbol(name))
+            .next()
+            .ok_or("Unknown symbol".into())
+    }
+
+    /*
+     *  main+0x1b in the executable, libc.so.6!malloc+0x5 in a library,
+     *  libc.so.6+0x98935 without a symbol, else just the address
+     */
+    pub fn symbolize(&self, addr: u64) -> String {
+        self.symbolize_in(&self.modules().unwrap_or(vec![]), addr)
+    }
+
+    /* symbolize for many addresses without reading the mappings each time */
+    fn symbolize_in(&self, modules: &[Module], addr: u64) -> String {
+        match modules.iter().find(|module| module.contains(addr)) {
+            Some(module) => match module.symbolize(addr) {
+                Some(sym) if self.is_exe(module) => sym,
+                Some(sym) => format!("{}!{}", module.name(), sym),
+                None => format!("{}+0x{:x}", module.name(), addr - module.base),
+            },
+            None => format!("0x{:x}", addr),
+        }
+    }
+
+    /*
+     *  0x4005d0, main, main+0x1b, module+offset, e.g. test+0x5d0, or a
+     *  symbol of one module, e.g. libc.so.6!malloc or libc!malloc+0x5
+     */
+    pub fn resolve(&self, location: &str) -> Result<u64, DebugError> {
+
+        let location = location.trim();
+
+        if location.starts_with("0x") {
+            return Ok(u64::from_str_radix(&location[2..], 16)?);
+        }
+
+        if let Some(at) = location.find('!') {
+            let module = self.module(location[..at].trim())?;
+
+            let (name, offset) = match location[at+1..].rfind('+') {
+                Some(plus) => (location[at+1..at+1+plus].trim(), parse_offset(location[at+2+plus..].trim())?),
+                None => (location[at+1..].trim(), 0),
+            };
+
+            return module.symbol(name)
+                .map(|addr| addr + offset)
+                .ok_or("Unknown symbol in module".into());
+        }
+
+        let (name, offset) = match location.rfind('+') {
+            Some(at) => (location[..at].trim(), parse_offset(location[at+1..].trim())?),
+            None => {
+                return self.symbol(location);
+            },
+        };
+
+        /* a module name beats a symbol of the same name */
+        match self.modules()?.into_iter().find(|module| module.is(name)) {
+            Some(module) => Ok(module.base + offset),
+            None => Ok(self.symbol(name)? + offset),
+        }
+    }
+
+    /* line table of the executable, needs -g */
+    pub fn lines(&self) -> Result<Rc<LineTable>, DebugError> {
+
+        if let Some(ref lines) = *self.lines.borrow() {
+            return Ok(lines.clone());
+        }
+
+        let elf = self.elf().ok_or("No symbols loaded")?;
+        let lines = Rc::new(LineTable::parse(&elf)?);
+
+        *self.lines.borrow_mut() = Some(lines.clone());
+
+        Ok(lines)
+    }
+
+    /* file and line of a runtime address in the executable */
+    pub fn location(&self, addr: u64) -> Option<Location> {
+        let bias = self.load_bias().ok()?;
+
+        self.lines().ok()
+            .and_then(|lines| lines.location(addr.wrapping_sub(bias)))
+    }
+
+    /* variables and types of the executable, needs -g */
+    pub fn debug_info(&self) -> Result<Rc<DebugInfo>, DebugError> {
+
+        if let Some(ref info) = *self.debug_info.borrow() {
+            return Ok(info.clone());
+        }
+
+        let elf = self.elf().ok_or("No symbols loaded")?;
+        let info = Rc::new(DebugInfo::parse(&elf)?);
+
+        *self.debug_info.borrow_mut() = Some(info.clone());
+
+        Ok(info)
+    }
+
+    /*
+     *  value of a local, parameter or global as seen from where the current
+     *  thread is stopped, locals shadow globals like they do in C
+     */
+    pub fn read_var(&self, name: &str) -> Result<Variable, DebugError> {
+
+        let info = self.debug_info()?;
+        let bias = self.load_bias()?;
+        let thread = self.current_thread();
+
+        let regs = thread.getregs()?;
+        let pc = self.thread_pc(&thread)?;
+
+        let die = info.variable(name, pc.wrapping_sub(bias))
+            .ok_or("No such variable in scope")?;
+
+        let frame = Frame {
+            pc: pc.wrapping_sub(bias),
+            regs: regs,
+            cfa: self.frame_cfa(&thread)?,
+        };
+
+        let ty = info.type_of(die);
+        let size = info.size(ty).ok_or("Variable has no known size")? as usize;
+
+        let mut memory = Memory::load(self.process.pid() as usize)?;
+
+        let (addr, bytes) = match info.location(die, &frame, bias)? {
+            Place::Memory(addr) => (Some(addr), memory.read(addr as usize, size)?),
+            Place::Register(number) => {
+                let value = debuginfo::register(&regs, number).ok_or("Unsupported DWARF register")?;
+                (None, value.to_le_bytes().iter().take(size).cloned().collect())
+            },
+            Place::Value(value) => (None, value.to_le_bytes().iter().take(size).cloned().collect()),
+        };
+
+        Ok(Variable {
+            name: name.to_string(),
+            type_name: info.type_name(ty),
+            addr: addr,
+            value: info.value(ty, &bytes, &mut memory)?,
+        })
+    }
+
+    /*
+     *  frames of the current thread, innermost first, unwound with call
+     *  frame information where a module has some and along the rbp chain
+     *  Process::retn assumes where it doesn't
+     */
+    pub fn backtrace(&self) -> Result<Vec<StackFrame>, DebugError> {
+
+        let thread = self.current_thread();
+        let modules = self.modules()?;
+
+        let mut regs = thread.getregs()?;
+        regs.rip = self.thread_pc(&thread)?;
+
+        let mut frames: Vec<StackFrame> = vec![];
+
+        while frames.len() < MAX_FRAMES {
+            let pc = regs.rip;
+            let innermost = frames.is_empty();
+
+            /* return addresses are past the call, which may end the function */
+            let lookup = if innermost { pc } else { pc.wrapping_sub(1) };
+
+            let unwound = self.unwind_row(&modules, lookup)
+                .ok_or(DebugError::from("No call frame information"))
+                .and_then(|(row, pc)| {
+                    let cfa = unwind::cfa(&row, &regs, pc)?;
+                    let caller = unwind::caller(&row, &regs, cfa, |addr| self.process.peek(addr))?;
+                    Ok((cfa, caller))
+                });
+
+            let (cfa, caller) = match unwound {
+                Ok(unwound) => unwound,
+                Err(_) => {
+                    /* the return address is on top until the prologue ran */
+                    let slot = match self.prologue_slot(&thread)? {
+                        Some(slot) if innermost => slot,
+                        _ => regs.rbp.wrapping_add(8),
+                    };
+
+                    let mut caller = regs;
+                    caller.rsp = slot.wrapping_add(8);
+
+                    match self.process.peek(slot) {
+                        Ok(ret) => {
+                            caller.rip = ret;
+                            /* push rbp went right below it */
+                            if slot != regs.rsp {
+                                caller.rbp = self.process.peek(slot - 8).unwrap_or(0);
+                            }
+                            (caller.rsp, Some(caller))
+                        },
+                        Err(_) => (caller.rsp, None),
+                    }
+                },
+            };
+
+            /* the stack grows down, callers sit higher */
+            if frames.last().map_or(false, |frame| cfa <= frame.cfa) {
+                break;
+            }
+
+            let module = modules.iter().find(|module| module.contains(pc));
+
+            let location = module
+                .filter(|module| self.is_exe(module))
+                .and_then(|_| self.location(lookup));
+
+            frames.push(StackFrame {
+                index: frames.len(),
+                pc: pc,
+                cfa: cfa,
+                symbol: self.frame_symbol(&modules, pc, lookup),
+                module: module.map(|module| module.name().to_string()),
+                offset: module.map_or(pc, |module| pc - module.base),
+                location: location,
+                regs: regs,
+            });
+
+            match caller {
+                Some(caller) if caller.rip != 0 => { regs = caller; },
+                _ => { break; },
+            }
+        }
+
+        Ok(frames)
+    }
+
+    /*
+     *  the last SIGSEGV, SIGBUS, SIGILL, SIGFPE or SIGABRT any thread got,
+     *  whether or not the signal policy stopped for it
+     */
+    pub fn crash_report(&self) -> Option<CrashReport> {
+        self.crash.borrow().clone()
+    }
+
+    /* the current thread is the one that got the signal */
+    fn build_crash_report(&self, info: SigInfo) -> Result<CrashReport, DebugError> {
+
+        let thread = self.current_thread();
+        let regs = thread.getregs()?;
+
+        /* si_addr only means something for faults */
+        let fault_addr = if is_fault(info.signo) { Some(info.addr) } else { None };
+
+        let mapping = match fault_addr {
+            Some(addr) => Memory::load(self.process.pid() as usize)?.maps.iter()
+                .find(|region| addr >= region.start_address as u64 && addr < region.end_address as u64)
+                .map(MapEntry::from_region),
+            None => None,
+        };
+
+        Ok(CrashReport {
+            pid: self.process.pid(),
+            tid: thread.pid(),
+            info: info,
+            fault_addr: fault_addr,
+            regs: regs,
+            disassembly: self.disassemble_around(regs.rip, crash::WINDOW_BEFORE, crash::WINDOW_AFTER),
+            backtrace: self.backtrace().unwrap_or(vec![]),
+            mapping: mapping,
+        })
+    }
+
+    /*
+     *  instructions before addr can only be found decoding from somewhere
+     *  known to be an instruction, the start of the function addr is in
+     */
+    fn disassemble_around(&self, addr: u64, before: usize, after: usize) -> Vec<Instruction> {
+
+        let start = self.modules().ok()
+            .and_then(|modules| modules.into_iter().find(|module| module.contains(addr)))
+            .and_then(|module| module.elf.as_ref()
+                .and_then(|elf| elf.lookup(addr.wrapping_sub(module.bias)))
+                .map(|(_, offset)| addr - offset))
+            .filter(|&start| addr - start <= 0x1000);
+
+        let mut insns = vec![];
+
+        if let Some(start) = start {
+            let count = (addr - start) as usize + 1;
+
+            for insn in self.disassemble(start, count).unwrap_or(vec![]) {
+                if insn.addr >= addr {
+                    break;
+                }
+                insns.push(insn);
+            }
+
+            /* lost track of the instruction boundaries, show what's at addr */
+            if insns.last().map_or(false, |insn| insn.addr + insn.len() as u64 != addr) {
+                insns.clear();
+            }
+
+            let skip = insns.len().saturating_sub(before);
+            insns.drain(..skip);
+        }
+
+        insns.extend(self.disassemble(addr, after).unwrap_or(vec![]));
+        insns
+    }
+
+    /* a call as the last instruction of a function returns past its end */
+    fn frame_symbol(&self, modules: &[Module], pc: u64, lookup: u64) -> String {
+
+        let module = match modules.iter().find(|module| module.contains(lookup)) {
+            Some(module) if lookup != pc => module,
+            _ => { return self.symbolize_in(modules, pc); },
+        };
+
+        let sym = module.elf.as_ref()
+            .and_then(|elf| elf.lookup(lookup.wrapping_sub(module.bias)))
+            .map(|(sym, offset)| format!("{}+0x{:x}", sym.name, offset + (pc - lookup)));
+
+        match sym {
+            Some(sym) if self.is_exe(module) => sym,
+            Some(sym) => format!("{}!{}", module.name(), sym),
+            None => self.symbolize_in(modules, pc),
+        }
+    }
+
+    /* call frame information of a module, parsed when first needed */
+    fn call_frame_info(&self, module: &Module) -> Option<Rc<CallFrameInfo>> {
+
+        let elf = module.elf.as_ref()?;
+
+        self.frames.borrow_mut().entry(module.path.clone())
+            .or_insert_with(|| CallFrameInfo::parse(elf).ok().map(Rc::new))
+            .clone()
+    }
+
+    /* unwind rules at a runtime pc and the link time pc they are for */
+    fn unwind_row(&self, modules: &[Module], pc: u64) -> Option<(UnwindRow, u64)> {
+
+        let module = modules.iter().find(|module| module.contains(pc))?;
+        let cfi = self.call_frame_info(module)?;
+        let linked = pc.wrapping_sub(module.bias);
+
+        cfi.row(linked).ok()
+            .and_then(|row| row)
+            .map(|row| (row, linked))
+    }
+
+    /* canonical frame address of the function the thread is in */
+    fn frame_cfa(&self, thread: &Process<x86_64_Registers>) -> Result<u64, DebugError> {
+
+        let regs = thread.getregs()?;
+        let pc = self.thread_pc(thread)?;
+
+        let cfa = self.modules().ok()
+            .and_then(|modules| self.unwind_row(&modules, pc))
+            .and_then(|(row, linked)| unwind::cfa(&row, &regs, linked).ok());
+
+        match cfa {
+            Some(cfa) => Ok(cfa),
+            /* rsp before the call pushed the return address */
+            None => Ok(self.return_slot(thread)? + 8),
+        }
+    }
+
+    /* where the current thread is in the source */
+    pub fn source_location(&self) -> Option<Location> {
+        self.thread_pc(&self.current_thread()).ok()
+            .and_then(|pc| self.location(pc))
+    }
+
+    /*
+     *  trap where the dynamic linker reports changes to its list of
+     *  libraries, nothing to do for a static binary
+     */
+    fn track_libraries(&self) -> Result<(), DebugError> {
+
+        /* gone with the old image after an exec */
+        *self.rendezvous.borrow_mut() = None;
+        *self.rendezvous_trap.borrow_mut() = None;
+
+        let ld = self.modules()?.into_iter()
+            .find(|module| !self.is_exe(module) && module.symbol("_r_debug").is_some());
+
+        let ld = match ld {
+            Some(ld) => ld,
+            None => { return Ok(()); },
+        };
+
+        let addr = ld.symbol("_r_debug").unwrap();
+
+        /* r_brk is only filled in once the dynamic linker ran */
+        let brk = match RDebug::read(&self.process, addr)?.brk {
+            0 => ld.symbol("_dl_debug_state").ok_or("No r_brk in the dynamic linker")?,
+            brk => brk,
+        };
+
+        let rendezvous = Rendezvous::new(&self.process, addr, brk)?;
+
+        self.log_command(&format!("tracking libraries through r_brk @ 0x{:x}", brk));
+        *self.rendezvous.borrow_mut() = Some(rendezvous);
+
+        self.trap_rendezvous()
+    }
+
+    /*
+     *  a breakpoint of the user's at r_brk reports the changes just as well,
+     *  the trap goes back in once that one is gone or disabled
+     */
+    fn trap_rendezvous(&self) -> Result<(), DebugError> {
+
+        let brk = match *self.rendezvous.borrow() {
+            Some(ref rendezvous) => rendezvous.brk,
+            None => { return Ok(()); },
+        };
+
+        if self.breakpoint_at(brk+1).is_some() {
+            return Ok(());
+        }
+
+        let mut trap = self.rendezvous_trap.borrow_mut();
+
+        match *trap {
+            /* a breakpoint on the same byte took it out when it went */
+            Some(ref bp) if bp.is_enabled() => {
+                if self.process.peek(brk)? & 0xff != 0xcc {
+                    bp.trap()?;
+                }
+            },
+            /* lifted while a vfork child borrows the memory */
+            Some(_) => {},
+            None => {
+                *trap = Some(Breakpoint::new("<r_brk>".to_string(), self.process.pid(), brk)?);
+            },
+        }
+
+        Ok(())
+    }
+
+    /* the user's breakpoint takes over r_brk, it has to see the original byte */
+    fn untrap_rendezvous(&self, addr: u64) {
+
+        let mut trap = self.rendezvous_trap.borrow_mut();
+
+        if trap.as_ref().map_or(false, |bp| bp.addr == addr) {
+            *trap = None;
+        }
+    }
+
+    fn is_rendezvous_trap(&self, addr: u64) -> bool {
+        self.rendezvous_trap.borrow().as_ref()
+            .map_or(false, |bp| bp.addr == addr && bp.is_enabled())
+    }
+
+    /* user breakpoints and the trap on r_brk, whatever put an int3 in */
+    fn each_trap<F>(&self, mut f: F) -> Result<(), DebugError>
+        where F: FnMut(&Breakpoint) -> Result<(), DebugError>
+    {
+        for bp in self.breakpoints.borrow().values() {
+            f(bp)?;
+        }
+
+        if let Some(ref bp) = *self.rendezvous_trap.borrow() {
+            f(bp)?;
+        }
+
+        Ok(())
+    }
+
+    /* shared objects as the dynamic linker last reported them */
+    pub fn libraries(&self) -> Vec<Library> {
+        self.rendezvous.borrow().as_ref()
+            .map_or(vec![], |rendezvous| rendezvous.libraries.clone())
+    }
+
+    /* report every dlopen and dlclose as StopEvent::Library */
+    pub fn stop_on_libraries(&self, on: bool) {
+        self.log_command(&format!("stop on library changes {}", if on { "on" } else { "off" }));
+        self.library_stops.set(on);
+    }
+
+    /* breakpoints still waiting for their library */
+    /* location and breakpoint, don't hold on to it across a continue */
+    pub fn pending(&self) -> Ref<[(String, Breakpoint)]> {
+        Ref::map(self.pending.borrow(), |pending| pending.as_slice())
+    }
+
+    pub fn follow(&mut self, policy: Follow) -> Result<(), DebugError> {
+
+        self.log_command(&format!("follow {:?} on fork", policy));
+
+        /* options are inherited by every traced child */
+        self.threads.borrow().set_options(
+            BASE_OPTIONS |
+            PTRACE_O_TRACEFORK |
+            PTRACE_O_TRACEVFORK |
+            PTRACE_O_TRACEEXEC |
+            PTRACE_O_TRACEVFORKDONE
+        )?;
+
+        self.follow = Some(policy);
+
+        Ok(())
+    }
+
+    /* debuggers for children stopped under Follow::Both */
+    pub fn forked(&self) -> Vec<Debugger> {
+        self.forked.borrow_mut().drain(..).collect()
+    }
+
+    pub fn detach(&mut self) -> Result<(), DebugError> {
+        self.detach_with(None)
+    }
+
+    pub fn detach_with(&mut self, sig: Option<Signal>) -> Result<(), DebugError> {
+
+        self.log_command(&format!("detach from process {}", self.process.pid()));
+
+        self.threads.borrow_mut().flush()?;
+
+        for thread in self.all_threads() {
+            /* stopped just past one of our traps, step back onto the real instruction */
+            let mut regs = thread.getregs()?;
+            let ip = regs.ip();
+
+            if thread.status().trapped() && self.owns_trap(&thread, ip - 1)? {
+                regs.set_ip(ip - 1);
+                thread.setregs(&regs)?;
+            }
+        }
+
+        /* every trap has to be gone before the process runs untraced */
+        self.each_trap(|bp| bp.remove().map(|_| ()))?;
+
+        /* debug registers outlive the tracer */
+        for thread in self.all_threads() {
+            for (_, wp) in self.watchpoints.iter() {
+                wp.disarm(&thread)?;
+            }
+        }
+        self.watchpoints.clear();
+
+        /* unwinds any phantom call still in flight */
+        self.phantom_mgr.borrow_mut().clear(&self.process)?;
+
+        self.breakpoints.borrow_mut().clear();
+        self.tracepoints.borrow_mut().clear();
+        self.pending.borrow_mut().clear();
+        self.locations.borrow_mut().clear();
+        *self.rendezvous.borrow_mut() = None;
+        *self.rendezvous_trap.borrow_mut() = None;
+
+        for thread in self.all_threads().iter().filter(|t| t.pid() != self.process.pid()) {
+            thread.detach(None)?;
+        }
+
+        self.process.detach(sig)?;
+        *self.pc.borrow_mut() = None;
+
+        Ok(())
+    }
+
+    fn owns_trap(&self, thread: &Process<x86_64_Registers>, addr: u64) -> Result<bool, DebugError> {
+
+        let ours = self.breakpoints.borrow().values().any(|bp| bp.addr == addr)
+            || self.is_rendezvous_trap(addr)
+            || self.phantom_mgr.borrow().is_exit(thread);
+
+        Ok(ours && self.process.peek(addr)? & 0xff == 0xcc)
+    }
+
+    pub fn threads(&self) -> Vec<u32> {
+        self.threads.borrow().tids()
+    }
+
+    pub fn thread(&self, tid: u32) -> Option<Process<x86_64_Registers>> {
+        self.threads.borrow().get(tid)
+    }
+
+    /* thread of the last stop */
+    pub fn current_thread(&self) -> Process<x86_64_Registers> {
+        self.thread(self.current.get())
+            .unwrap_or(self.process.clone())
+    }
+
+    pub fn select_thread(&self, tid: u32) -> Result<u64, DebugError> {
+
+        let thread = match self.thread(tid) {
+            Some(thread) => thread,
+            None => { return Err("No such thread".into()); }
+        };
+
+        let pc = thread.getregs()?.ip();
+
+        self.current.set(tid);
+        self.set_pc(pc);
+
+        Ok(pc)
+    }
+
+    /* x87, SSE and AVX state of the current thread, see FpRegisters */
+    pub fn fpregs(&self) -> Result<FpRegisters, DebugError> {
+        self.current_thread().getfpstate()
+    }
+
+    pub fn set_fpregs(&self, regs: &FpRegisters) -> Result<(), DebugError> {
+        self.current_thread().setfpstate(regs)?;
+
+        Ok(())
+    }
+
+    fn all_threads(&self) -> Vec<Process<x86_64_Registers>> {
+        let threads = self.threads.borrow();
+        threads.tids().into_iter()
+            .filter_map(|tid| threads.get(tid))
+            .collect()
+    }
+
+    pub fn run(&mut self) -> Result<StopEvent, DebugError> {
+
+        self.log_command(&format!("running binary '{}' with argc {}", self.file, self.args.len()));
+
+        let event = loop {
+            let event = self.resume()?;
+
+            if let Some(event) = self.library_event(event)? {
+                break event;
+            }
+        };
+
+        self.stopped(&event);
+        self.init_state = false;
+
+        Ok(event)
+    }
+
+    fn set_pc(&self, pc: u64) {
+        *self.pc.borrow_mut() = Some(pc);
+    }
+
+    /* pending if nothing is mapped at addr yet */
+    pub fn breakpoint(&mut self, addr: u64) -> Result<&mut Breakpoint, DebugError> {
+
+        if self.process.peek(addr).is_err() && self.rendezvous.borrow().is_some() {
+            return self.pending_breakpoint(&format!("0x{:x}", addr));
+        }
+
+        let pid = self.process.pid();
+
+        self.clear_at(addr);
+
+        /* numbered once it exists, a failed one doesn't use up a number */
+        let mut bp = Breakpoint::new(String::new(), pid, addr)?;
+        bp.name = self.next_name();
+
+        self.log_command(&format!("set breakpoint {} @ 0x{:x}", bp.name, addr));
+        self.breakpoints.get_mut().insert(addr+1, bp);
+
+        Ok(self.breakpoint_at_mut(addr+1).unwrap())
+    }
+
+    /* pending if no library mapped right now has it */
+    pub fn breakpoint_sym(&mut self, name: &str) -> Result<&mut Breakpoint, DebugError> {
+        match self.symbol(name) {
+            Ok(addr) => self.breakpoint(addr),
+            Err(DebugError::Error(_)) if self.rendezvous.borrow().is_some() => self.pending_breakpoint(name),
+            Err(e) => Err(e),
+        }
+    }
+
+    /* armed at location once a library that resolves it is loaded */
+    pub fn pending_breakpoint(&mut self, location: &str) -> Result<&mut Breakpoint, DebugError> {
+
+        if self.rendezvous.borrow().is_none() {
+            return Err("No libraries get loaded into this process".into());
+        }
+
+        let pid = self.process.pid();
+        let bp = Breakpoint::pending(self.next_name(), pid);
+
+        self.log_command(&format!("set pending breakpoint {} @ {}", bp.name, location));
+        let pending = self.pending.get_mut();
+        pending.push((location.to_string(), bp));
+
+        Ok(&mut pending.last_mut().unwrap().1)
+    }
+
+    /* arm whatever the libraries loaded so far resolve */
+    fn arm_pending(&self) -> Result<(), DebugError> {
+
+        let pending = mem::replace(&mut *self.pending.borrow_mut(), vec![]);
+
+        for (location, mut bp) in pending {
+            /* a module can be listed before all of it is mapped */
+            let addr = match self.resolve(&location) {
+                Ok(addr) if self.process.peek(addr).is_ok() => addr,
+                _ => {
+                    self.pending.borrow_mut().push((location, bp));
+                    continue;
+                },
+            };
+
+            /* arming on top of another trap would save the trap as the original */
+            if self.breakpoints.borrow().contains_key(&(addr+1)) {
+                self.log_command(&format!("pending breakpoint {} @ {} is already set", bp.name, location));
+                continue;
+            }
+
+            self.untrap_rendezvous(addr);
+            bp.arm(addr)?;
+
+            self.log_command(&format!("armed pending breakpoint {} @ 0x{:x} <{}>", bp.name, addr, location));
+            self.breakpoints.borrow_mut().insert(addr+1, bp);
+            self.locations.borrow_mut().insert(addr+1, location);
+        }
+
+        Ok(())
+    }
+
+    /* the traps went away with the mappings, ones from a location wait for it again */
+    fn unmapped(&self) -> Result<(), DebugError> {
+
+        let gone: Vec<u64> = self.breakpoints.borrow().iter()
+            .filter(|&(_, bp)| bp.is_enabled() && self.process.peek(bp.addr).is_err())
+            .map(|(&key, _)| key)
+            .collect();
+
+        for key in gone {
+            let location = self.locations.borrow_mut().remove(&key);
+
+            match location {
+                Some(location) => {
+                    let mut bp = self.breakpoints.borrow_mut().remove(&key).unwrap();
+                    self.tracepoints.borrow_mut().remove(&key);
+                    bp.unarm();
+
+                    self.log_command(&format!("breakpoint {} @ {} is pending again", bp.name, location));
+                    self.pending.borrow_mut().push((location, bp));
+                },
+                None => {
+                    self.breakpoints.borrow()[&key].invalidate();
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /* a new breakpoint replaces what was at addr, it has to see the original byte */
+    fn clear_at(&mut self, addr: u64) {
+        self.untrap_rendezvous(addr);
+        self.breakpoints.get_mut().remove(&(addr+1));
+        self.tracepoints.get_mut().remove(&(addr+1));
+    }
+
+    /* breakpoints and watchpoints share the numbers, none is used twice, */
+    /* internal traps like <r_brk> and <until> aren't numbered */
+    fn next_name(&self) -> String {
+        self.numbered.set(self.numbered.get() + 1);
+        self.numbered.get().to_string()
+    }
+
+    /* first instruction of a line, or of the next one with code, e.g. ("main.c", 42) */
+    pub fn breakpoint_line(&mut self, file: &str, line: u64) -> Result<&mut Breakpoint, DebugError> {
+
+        let addr = self.lines()?.addresses(file, line).into_iter()
+            .min()
+            .ok_or("No code at or after that line")?;
+
+        let addr = addr.wrapping_add(self.load_bias()?);
+        self.breakpoint(addr)
+    }
+
+    /*
+     *  anything resolve understands, pending if it names a module or symbol
+     *  that isn't loaded yet, e.g. libfoo.so!init_plugin or libfoo.so+0x1234
+     */
+    pub fn breakpoint_loc(&mut self, location: &str) -> Result<&mut Breakpoint, DebugError> {
+        match self.resolve(location) {
+            Ok(addr) => self.breakpoint(addr),
+            Err(DebugError::Error(_)) if self.rendezvous.borrow().is_some() => self.pending_breakpoint(location),
+            Err(e) => Err(e),
+        }
+    }
+
+    /* gone once continued from */
+    pub fn tmp_breakpoint(&mut self, addr: u64) -> Result<&mut Breakpoint, DebugError> {
+
+        let pid = self.process.pid();
+
+        self.clear_at(addr);
+
+        let mut bp = Breakpoint::new(String::new(), pid, addr)?;
+        bp.name = self.next_name();
+        bp.temporary(true);
+
+        self.log_command(&format!("set temporary breakpoint {} @ 0x{:x}", bp.name, addr));
+        self.breakpoints.get_mut().insert(addr+1, bp);
+
+        Ok(self.breakpoint_at_mut(addr+1).unwrap())
+    }
+
+    /* a breakpoint that records spec and resumes, e.g. `rdi, [rdi, 32]` */
+    pub fn tracepoint(&mut self, addr: u64, spec: &str) -> Result<&mut Breakpoint, DebugError> {
+
+        let spec = TraceSpec::parse(spec)?;
+        let pid = self.process.pid();
+
+        self.clear_at(addr);
+
+        let mut bp = Breakpoint::new(String::new(), pid, addr)?;
+        bp.name = self.next_name();
+
+        self.log_command(&format!("set tracepoint {} @ 0x{:x}", bp.name, addr));
+        self.breakpoints.get_mut().insert(addr+1, bp);
+        self.tracepoints.get_mut().insert(addr+1, spec);
+
+        Ok(self.breakpoint_at_mut(addr+1).unwrap())
+    }
+
+    /* the breakpoint under it goes too, what it recorded stays in the buffer */
+    pub fn remove_tracepoint(&mut self, addr: u64) -> Result<(), DebugError> {
+
+        if self.tracepoints.get_mut().remove(&(addr+1)).is_none() {
+            return Err("No tracepoint found at given address".into());
+        }
+
+        /* dropping takes the trap out */
+        if let Some(bp) = self.breakpoints.get_mut().remove(&(addr+1)) {
+            self.log_command(&format!("remove tracepoint {} @ 0x{:x}", bp.name, addr));
+        }
+
+        Ok(())
+    }
+
+    /* everything recorded so far, oldest first */
+    pub fn drain_trace(&self) -> Vec<TraceRecord> {
+        self.trace.borrow_mut().drain(..).collect()
+    }
+
+    /* drains the buffer as JSON Lines, returns the number of records */
+    pub fn write_trace<W: Write>(&self, out: &mut W) -> Result<usize, DebugError> {
+
+        let records = self.drain_trace();
+
+        for record in records.iter() {
+            writeln!(out, "{}", record.to_json())?;
+        }
+
+        Ok(records.len())
+    }
+
+    /* debug registers take 1, 2, 4 or 8 aligned bytes, four at a time, */
+    /* anything else falls back to single stepping which is slow */
+    pub fn watchpoint(&mut self, addr: u64, len: usize, kind: WatchKind)
+        -> Result<&mut Watchpoint, DebugError>
+    {
+
+        if self.watchpoints.contains_key(&addr) {
+            self.remove_watchpoint(addr)?;
+        }
+
+        let free = (0..DEBUG_SLOTS)
+            .find(|&slot| !self.watchpoints.values().any(|wp| wp.slot() == Some(slot)));
+
+        let slot = if fits_debugreg(addr, len, kind) { free } else { None };
+
+        let mut wp = Watchpoint::new(String::new(), slot, addr, len, kind)?;
+
+        for thread in self.all_threads() {
+            wp.arm(&thread)?;
+        }
+
+        /* only one that got armed uses up a number */
+        wp.name = self.next_name();
+
+        self.log_command(&format!("set {} {:?} watchpoint {} @ 0x{:x} len {}",
+            if wp.is_hardware() { "hardware" } else { "software" }, kind, wp.name, addr, wp.len));
+        self.watchpoints.insert(addr, wp);
+
+        Ok(self.watchpoints.get_mut(&addr).unwrap())
+    }
+
+    pub fn remove_watchpoint(&mut self, addr: u64) -> Result<(), DebugError> {
+
+        let wp = match self.watchpoints.remove(&addr) {
+            Some(wp) => wp,
+            None => { return Err("No watchpoint found at given address".into()); },
+        };
+
+        self.log_command(&format!("remove watchpoint {} @ 0x{:x}", wp.name, addr));
+
+        for thread in self.all_threads() {
+            wp.disarm(&thread)?;
+        }
+
+        Ok(())
+    }
+
+    /* the thread ends up past the instruction under the trap, see deferred_signal */
+    fn step_over_breakpoint(&self, addr: u64, thread: &Process<x86_64_Registers>)
+        -> Result<Option<StopEvent>, DebugError>
+    {
+        self.trapped.set(None);
+
+        /* a temporary one is stepped over once, then it is gone */
+        let (info, temporary) = match self.breakpoint_at(addr+1) {
+            Some(bp) => {
+                let temporary = if bp.is_temporary() { Some(bp.name.clone()) } else { None };
+                (bp.step_over_on(thread)?, temporary)
+            },
+            None => {
+                let trap = self.rendezvous_trap.borrow();
+                let trap = trap.as_ref()
+                    .filter(|bp| bp.addr == addr && bp.is_enabled())
+                    .ok_or("No breakpoint found at given address")?;
+
+                (trap.step_over_on(thread)?, None)
+            },
+        };
+
+        if let Some(name) = temporary {
+            self.log_command(&format!("removed temporary breakpoint {} @ 0x{:x}", name, addr));
+            self.breakpoints.borrow_mut().remove(&(addr+1));
+            self.tracepoints.borrow_mut().remove(&(addr+1));
+            self.trap_rendezvous()?;
+        }
+
+        Ok(self.deferred_signal(thread.pid(), info))
+    }
+
+    /* a signal held back while stepping goes through its policy, Some if it stops */
+    fn deferred_signal(&self, tid: u32, info: Option<SigInfo>) -> Option<StopEvent> {
+        match info {
+            Some(info) if self.apply_signal_policy(tid, info.signo) => {
+                Some(StopEvent::Signal { signo: info.signo, info: info })
+            },
+            _ => None,
+        }
+    }
+
+    /* execute watchpoints fault before the instruction runs */
+    fn exec_watchpoint_at(&self, thread: &Process<x86_64_Registers>)
+        -> Result<Option<&Watchpoint>, DebugError>
+    {
+        if self.watchpoints.is_empty() {
+            return Ok(None);
+        }
+
+        let pc = thread.getregs()?.ip();
+
+        Ok(self.watchpoints.values()
+            .find(|wp| wp.kind == WatchKind::Execute && wp.addr == pc))
+    }
+
+    fn step_over_watchpoint(&self, thread: &Process<x86_64_Registers>, wp: &Watchpoint)
+        -> Result<Option<StopEvent>, DebugError>
+    {
+        wp.disarm(thread)?;
+        let info = thread.step_deferring()?;
+        wp.arm(thread)?;
+
+        Ok(self.deferred_signal(thread.pid(), info))
+    }
+
+    /* DR6 says which slot fired */
+    fn watchpoint_hit(&self, thread: &Process<x86_64_Registers>)
+        -> Result<Option<StopEvent>, DebugError>
+    {
+        if self.watchpoints.is_empty() {
+            return Ok(None);
+        }
+
+        let slots = watchpoint::triggered(thread)?;
+
+        Ok(self.watchpoints.values()
+            .find(|wp| wp.slot().map_or(false, |slot| slots.contains(&slot)))
+            .map(|wp| StopEvent::Watchpoint { name: wp.name.clone(), addr: wp.addr, kind: wp.kind }))
+    }
+
+    pub fn cont(&self) -> Result<StopEvent, DebugError> {
+
+        self.log_command("continue");
+
+        let event = self.cont_libraries()?;
+        self.stopped(&event);
+
+        Ok(event)
+    }
+
+    /* cont_event past the library changes nobody stops for */
+    fn cont_libraries(&self) -> Result<StopEvent, DebugError> {
+        loop {
+            let event = self.cont_event()?;
+
+            if let Some(event) = self.library_event(event)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /* cont without the logging and actions */
+    fn cont_event(&self) -> Result<StopEvent, DebugError> {
+
+        self.trap_rendezvous()?;
+
+        let thread = self.current_thread();
+        let mut watch = self.soft_watch()?;
+
+        self.threads.borrow_mut().settle(thread.pid())?;
+
+        let current = self.current_breakpoint().map(|bp| bp.addr);
+
+        let stepped = if let Ok(addr) = current {
+            Some(self.step_over_breakpoint(addr, &thread)?)
+        } else if let Some(wp) = self.exec_watchpoint_at(&thread)? {
+            Some(self.step_over_watchpoint(&thread, wp)?)
+        } else {
+            None
+        };
+
+        if let Some(Some(event)) = stepped {
+            return Ok(event);
+        }
+
+        let stepped = stepped.is_some();
+
+        /* the instruction stepped over may have hit a watchpoint */
+        let mut hit = self.watchpoint_hit(&thread)?;
+
+        if hit.is_none() && stepped {
+            if let Some(ref mut watch) = watch {
+                let pc = thread.getregs()?.ip();
+                hit = self.soft_watch_hit(watch, thread.pid(), pc)?;
+            }
+        }
+
+        if let Some(event) = hit {
+            return Ok(event);
+        }
+
+        self.resume_with(watch)
+    }
+
+    /* continue every thread until one of them stops */
+    fn resume(&self) -> Result<StopEvent, DebugError> {
+        let watch = self.soft_watch()?;
+        self.resume_with(watch)
+    }
+
+    /* software watchpoints compare memory after every instruction, */
+    /* syscall stops don't happen while stepping */
+    fn resume_with(&self, mut watch: Option<SoftWatch>) -> Result<StopEvent, DebugError> {
+
+        let mode = if watch.is_some() {
+            Resume::Step
+        } else if self.wants_syscalls() {
+            /* actions need the stops even when the caller doesn't */
+            Resume::Syscall
+        } else {
+            Resume::Continue
+        };
+
+        loop {
+            let (tid, status) = {
+                let mut threads = self.threads.borrow_mut();
+
+                threads.resume(mode)?;
+                let (tid, status) = threads.wait()?;
+
+                /* all-stop, nobody runs while the caller looks around */
+                if status.stopped() {
+                    threads.stop(tid)?;
+                }
+
+                (tid, status)
+            };
+
+            self.current.set(tid);
+            let event = self.classify(status)?;
+
+            if let StopEvent::Signal { signo, info } = event {
+                if is_crash(signo) {
+                    *self.crash.borrow_mut() = self.build_crash_report(info).ok();
+                }
+
+                if !self.apply_signal_policy(tid, signo) {
+                    continue;
+                }
+            }
+
+            if let StopEvent::Syscall(ref syscall) = event {
+                self.on_syscall(syscall);
+
+                if !self.syscalls.get() {
+                    continue;
+                }
+            }
+
+            if let StopEvent::Step { addr } = event {
+                if let Some(ref mut watch) = watch {
+                    match self.soft_watch_hit(watch, tid, addr)? {
+                        Some(event) => { return Ok(event); },
+                        None => { continue; },
+                    }
+                }
+            }
+
+            /* a false condition resumes right here, actions never see it */
+            if let StopEvent::Breakpoint { addr, .. } = event {
+                if let Some(event) = self.skip_breakpoint(tid, addr, &mut watch)? {
+                    return Ok(event);
+                }
+
+                continue;
+            }
+
+            return Ok(event);
+        }
+    }
+
+    /* Some(event) if the stop at addr is the caller's after all */
+    fn skip_breakpoint(&self, tid: u32, addr: u64, watch: &mut Option<SoftWatch>)
+        -> Result<Option<StopEvent>, DebugError>
+    {
+        if let Some(event) = self.reached(tid, addr)? {
+            return Ok(Some(event));
+        }
+
+        let bp = self.breakpoint_at(addr+1);
+
+        if bp.is_none() && !self.is_rendezvous_trap(addr) {
+            return Ok(None);
+        }
+
+        let thread = self.current_thread();
+
+        /* the dynamic linker is done changing its list of libraries */
+        let change = match *self.rendezvous.borrow_mut() {
+            Some(ref mut rendezvous) if rendezvous.brk == addr => rendezvous.update(&thread)?,
+            _ => None,
+        };
+
+        if let Some(bp) = bp {
+            /* a condition that can't be evaluated stops, the caller can look into it */
+            let stop = match bp.should_stop(&thread) {
+                Ok(stop) => stop,
+                Err(e) => {
+                    self.log_event(&format!("condition of breakpoint {} failed: {}", bp.name, e.description()));
+                    true
+                },
+            };
+
+            if stop {
+                let spec = self.tracepoints.borrow().get(&(addr+1)).cloned();
+
+                match spec {
+                    Some(spec) => self.trace_hit(&bp, &spec, &thread),
+                    None => {
+                        let event = StopEvent::Breakpoint { name: bp.name.clone(), addr: bp.addr };
+                        drop(bp);
+
+                        /* the user's breakpoint on r_brk, the libraries still change */
+                        if let Some(change) = change {
+                            self.log_libraries(&change);
+                            self.library_event(StopEvent::Library(change))?;
+                        }
+
+                        return Ok(Some(event));
+                    },
+                }
+            }
+        }
+
+        /* stepping off borrows the breakpoints again */
+        self.threads.borrow_mut().settle(tid)?;
+
+        if let Some(event) = self.step_over_breakpoint(addr, &thread)? {
+            return Ok(Some(event));
+        }
+
+        if let Some(change) = change {
+            self.log_libraries(&change);
+            return Ok(Some(StopEvent::Library(change)));
+        }
+
+        /* the stepped instruction may have hit a watchpoint */
+        if let Some(event) = self.watchpoint_hit(&thread)? {
+            return Ok(Some(event));
+        }
+
+        if let Some(ref mut watch) = *watch {
+            let pc = thread.getregs()?.ip();
+            return self.soft_watch_hit(watch, tid, pc);
+        }
+
+        Ok(None)
+    }
+
+    fn trace_hit(&self, bp: &Breakpoint, spec: &TraceSpec, thread: &Process<x86_64_Registers>) {
+
+        self.log_event(&format!("tracepoint {} hit in thread {}", bp.name, thread.pid()));
+
+        /* only fails if the thread is gone, which the next wait reports */
+        if let Ok(record) = spec.record(&bp.name, bp.addr, thread) {
+            self.trace.borrow_mut().push(record);
+        }
+    }
+
+    /* memory and thread pcs as they are before stepping */
+    fn soft_watch(&self) -> Result<Option<SoftWatch>, DebugError> {
+
+        if self.watchpoints.values().all(|wp| wp.is_hardware()) {
+            return Ok(None);
+        }
+
+        let mut memory = Memory::load(self.process.pid() as usize)?;
+        let mut snapshots = HashMap::new();
+
+        for wp in self.watchpoints.values().filter(|wp| !wp.is_hardware()) {
+            if wp.kind == WatchKind::Write {
+                snapshots.insert(wp.addr, memory.read(wp.addr as usize, wp.len)?);
+            }
+        }
+
+        let mut pcs = HashMap::new();
+
+        for thread in self.all_threads() {
+            pcs.insert(thread.pid(), thread.getregs()?.ip());
+        }
+
+        Ok(Some(SoftWatch {
+            memory: memory,
+            snapshots: snapshots,
+            pcs: pcs,
+        }))
+    }
+
+    /* tid just executed one instruction and now sits at pc */
+    fn soft_watch_hit(&self, watch: &mut SoftWatch, tid: u32, pc: u64)
+        -> Result<Option<StopEvent>, DebugError>
+    {
+
+        /* with several threads stepping, the writer is the best guess */
+        let prev = watch.pcs.insert(tid, pc).unwrap_or(pc);
+
+        for wp in self.watchpoints.values().filter(|wp| !wp.is_hardware()) {
+            match wp.kind {
+                WatchKind::Execute if wp.addr == pc => {
+                    return Ok(Some(StopEvent::Watchpoint { name: wp.name.clone(), addr: wp.addr, kind: wp.kind }));
+                },
+                WatchKind::Write => {
+                    let new = watch.memory.read(wp.addr as usize, wp.len)?;
+                    let old = watch.snapshots.insert(wp.addr, new.clone()).unwrap_or(vec![]);
+
+                    if old != new {
+                        return Ok(Some(StopEvent::WatchChange {
+                            name: wp.name.clone(),
+                            addr: wp.addr,
+                            pc: prev,
+                            old: old,
+                            new: new,
+                        }));
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        Ok(None)
+    }
+
+    /* returns whether the caller should see the stop */
+    fn apply_signal_policy(&self, tid: u32, signo: i32) -> bool {
+
+        let policy = self.signals.get(&signo).cloned()
+            .unwrap_or(SignalPolicy::default_for(signo));
+
+        if policy.print && self.log.contains(LogLevel::Events) {
+            println!("thread {} received {}", tid, signal_name(signo));
+
+            if is_crash(signo) {
+                if let Some(ref report) = *self.crash.borrow() {
+                    print!("{}", report);
+                }
+            }
+        }
+
+        if policy.pass {
+            self.threads.borrow_mut().set_signal(tid, Some(signo));
+        }
+
+        policy.stop
+    }
+
+    pub fn handle(&mut self, sig: Signal, policy: SignalPolicy) {
+        self.log_command(&format!("handle {:?} {:?}", sig, policy));
+        self.signals.insert(sig as i32, policy);
+    }
+
+    pub fn signal_policy(&self, sig: Signal) -> SignalPolicy {
+        let signo = sig as i32;
+
+        self.signals.get(&signo).cloned()
+            .unwrap_or(SignalPolicy::default_for(signo))
+    }
+
+    /* signal for the current thread on the next continue, None drops a pending one */
+    pub fn set_signal(&self, sig: Option<Signal>) {
+        self.threads.borrow_mut()
+            .set_signal(self.current.get(), sig.map(|s| s as i32));
+    }
+
+    /* turn the status of the current thread into a stop event */
+    fn classify(&self, status: Status) -> Result<StopEvent, DebugError> {
+
+        let thread = self.current_thread();
+        self.trapped.set(None);
+
+        if thread.pid() == self.process.pid() {
+            self.process.status.set(status);
+        }
+
+        if let Some(code) = status.exitcode() {
+            return Ok(StopEvent::Exited(code));
+        } else if let Some(sig) = status.termsig() {
+            return Ok(StopEvent::Killed(sig));
+        } else if !status.stopped() {
+            return Err(DebugError::from(status));
+        }
+
+        if status.syscall_stop() {
+            let entry = self.syscall_entry(&thread)?;
+            let syscall = self.intercept(&thread, entry)?;
+
+            return Ok(StopEvent::Syscall(syscall));
+        }
+
+        if let Some(event) = thread.event()? {
+            self.handle_event(event)?;
+            return Ok(StopEvent::Ptrace(event));
+        }
+
+        let pc = thread.getregs()?.ip();
+        let info = thread.getsiginfo()?;
+
+        if !status.trapped() {
+            return Ok(StopEvent::Signal { signo: info.signo, info: info });
+        }
+
+        if self.phantom_mgr.borrow().is_exit(&thread) {
+            let retval = thread.getregs()?.rax;
+            /* only a float call returns in xmm0, an unreadable one is just None */
+            let fretval = if self.phantom_mgr.borrow().is_float() {
+                thread.getfpregs().ok()
+                    .and_then(|fp| fp.xmm(0).ok())
+                    .map(|xmm| xmm.f64x2()[0])
+            } else {
+                None
+            };
+
+            self.phantom_mgr.borrow_mut().clean(&thread)?;
+
+            /* back where the call was made from, past its breakpoint */
+            if self.breakpoint_at(thread.getregs()?.ip()).is_some() {
+                self.trapped.set(Some(thread.pid()));
+            }
+
+            return Ok(StopEvent::PhantomReturn { retval: retval, fretval: fretval });
+        }
+
+        if let Some(event) = self.watchpoint_hit(&thread)? {
+            return Ok(event);
+        }
+
+        /* single steps report TRAP_TRACE, or TRAP_BRKPT after a syscall */
+        /* instruction, int3 reports SI_KERNEL */
+        if info.code == TRAP_TRACE || info.code == TRAP_BRKPT {
+            return Ok(StopEvent::Step { addr: pc });
+        }
+
+        match self.breakpoint_at(pc) {
+            Some(bp) => {
+                self.trapped.set(Some(thread.pid()));
+                Ok(StopEvent::Breakpoint { name: bp.name.clone(), addr: bp.addr })
+            },
+            /* never reaches the caller, skip_breakpoint steps over it */
+            None if self.is_rendezvous_trap(pc - 1) => {
+                self.trapped.set(Some(thread.pid()));
+                Ok(StopEvent::Breakpoint { name: "<r_brk>".to_string(), addr: pc - 1 })
+            },
+            None => Ok(StopEvent::Signal { signo: info.signo, info: info }),
+        }
+    }
+
+    fn handle_event(&self, event: PtraceEvent) -> Result<(), DebugError> {
+
+        self.log_event(&format!("{:?} in thread {}", event, self.current.get()));
+
+        match event {
+            PtraceEvent::Fork(pid) | PtraceEvent::Clone(pid) => {
+                self.follow_fork(pid, false)?;
+            },
+            PtraceEvent::Vfork(pid) => {
+                self.follow_fork(pid, true)?;
+            },
+            PtraceEvent::VforkDone => {
+                /* memory is ours again */
+                let lifted: Vec<u64> = self.lifted.borrow_mut().drain(..).collect();
+
+                self.each_trap(|bp| {
+                    if lifted.contains(&bp.addr) {
+                        bp.trap()?;
+                    }
+                    Ok(())
+                })?;
+            },
+            PtraceEvent::Exec => {
+                /* new image, none of the traps exist anymore */
+                self.each_trap(|bp| {
+                    bp.invalidate();
+                    Ok(())
+                })?;
+
+                /* the kernel cleared the debug registers too, watchpoints */
+                /* stay listed until removed but won't fire */
+                self.lifted.borrow_mut().clear();
+                self.phantom_mgr.borrow_mut().forget();
+                self.threads.borrow_mut().reset();
+                self.current.set(self.process.pid());
+
+                *self.elf.borrow_mut() = Debugger::load_elf(self.process.pid(), &self.file);
+                *self.lines.borrow_mut() = None;
+                *self.debug_info.borrow_mut() = None;
+                self.frames.borrow_mut().clear();
+            },
+            PtraceEvent::Exit => {},
+        }
+
+        Ok(())
+    }
+
+    fn follow_fork(&self, pid: u32, vfork: bool) -> Result<(), DebugError> {
+
+        let child = Process::<x86_64_Registers>::new(pid);
+
+        /* its first stop was already seen, only threads get that far */
+        if self.threads.borrow().contains(pid) {
+            return Ok(());
+        }
+
+        /* auto attached children start with a SIGSTOP */
+        child.wait();
+
+        /* a new thread shares everything with us, nothing to split */
+        if child.tgid()? == self.process.pid() {
+            /* debug registers are not inherited */
+            for (_, wp) in self.watchpoints.iter() {
+                wp.arm(&child)?;
+            }
+
+            self.threads.borrow_mut().add(pid);
+            return Ok(());
+        }
+
+        match self.follow.unwrap_or(Follow::Parent) {
+            Follow::Parent => {
+                if vfork {
+                    /* child borrows our memory until it execs or exits */
+                    self.lift()?;
+                } else {
+                    /* child got a copy of every trap */
+                    self.each_trap(|bp| bp.for_process(pid).remove().map(|_| ()))?;
+                }
+
+                child.detach(None)?;
+            },
+            Follow::Child => {
+                if vfork {
+                    /* shared memory, the parent would trap on them after the exec */
+                    self.each_trap(|bp| bp.remove().map(|_| ()))?;
+                } else {
+                    let parent = self.process.pid();
+                    self.each_trap(|bp| bp.for_process(parent).remove().map(|_| ()))?;
+                }
+
+                self.threads.borrow_mut().flush()?;
+                for thread in self.all_threads() {
+                    thread.detach(None)?;
+                }
+
+                self.threads.borrow_mut().retarget(pid);
+                self.current.set(pid);
+
+                self.process.retarget(pid);
+                self.each_trap(|bp| {
+                    bp.retarget(pid);
+                    Ok(())
+                })?;
+                for &(_, ref bp) in self.pending.borrow().iter() {
+                    bp.retarget(pid);
+                }
+                self.phantom_mgr.borrow_mut().retarget(pid.into());
+
+                for (_, wp) in self.watchpoints.iter() {
+                    wp.arm(&self.process)?;
+                }
+            },
+            Follow::Both => {
+                /* stays stopped until the caller picks it up */
+                let dbg = self.fork_debugger(pid)?;
+                self.forked.borrow_mut().push(dbg);
+            },
+        }
+
+        Ok(())
+    }
+
+    /* debugger for a stopped fork child carrying our traps */
+    fn fork_debugger(&self, pid: u32) -> Result<Debugger, DebugError> {
+
+        let process = Process::<x86_64_Registers>::new(pid);
+        let mut dbg = Debugger::init(process, None, self.file.clone(), self.args.clone())?;
+
+        dbg.log = self.log;
+
+        /* init reset the inherited options */
+        if let Some(policy) = self.follow {
+            dbg.follow(policy)?;
+        }
+
+        for (&addr, bp) in self.breakpoints.borrow().iter() {
+            dbg.breakpoints.get_mut().insert(addr, bp.for_process(pid));
+        }
+
+        for (&addr, wp) in self.watchpoints.iter() {
+            wp.arm(&dbg.process)?;
+            dbg.watchpoints.insert(addr, wp.clone());
+        }
+
+        *dbg.tracepoints.get_mut() = self.tracepoints.borrow().clone();
+
+        /* same libraries, and the same trap on r_brk */
+        *dbg.rendezvous.borrow_mut() = self.rendezvous.borrow().clone();
+        *dbg.rendezvous_trap.get_mut() = self.rendezvous_trap.borrow().as_ref()
+            .map(|bp| bp.for_process(pid));
+
+        for &(ref location, ref bp) in self.pending.borrow().iter() {
+            dbg.pending.get_mut().push((location.clone(), bp.for_process(pid)));
+        }
+
+        *dbg.locations.get_mut() = self.locations.borrow().clone();
+        dbg.numbered.set(self.numbered.get());
+
+        Ok(dbg)
+    }
+
+    /* take the traps out for a while, noting which to put back */
+    fn lift(&self) -> Result<(), DebugError> {
+
+        let mut lifted = self.lifted.borrow_mut();
+
+        self.each_trap(|bp| {
+            if bp.is_enabled() {
+                bp.remove()?;
+                lifted.push(bp.addr);
+            }
+            Ok(())
+        })
+    }
+
+    /* only the current thread moves */
+    pub fn single_step(&self) -> Result<StopEvent, DebugError> {
+        self.log_command("single step");
+
+        let event = self.step_libraries()?;
+        self.stopped(&event);
+
+        Ok(event)
+    }
 
+    fn step_event(&self) -> Result<StopEvent, DebugError> {
 
-pub struct Debugger {
-    pub process: Process<x86_64_Registers>,
-    pub breakpoints: HashMap<u64,Breakpoint>,
-    pub file: String,
-    pub args: Vec<String>,
-    pub child: Child,
-    pub pc: OptionCell<u64>,
-    pub log: LogLevel,
-    phantom_mgr: Rc<RefCell<PhantomManager<x86_64_Registers>>>,
-    actions_at: HashMap<u64,Vec<BoxedDebuggerFn>>,
-    actions: Vec<BoxedDebuggerFn>,
-    init_state: bool,
-}
+        self.trap_rendezvous()?;
 
-impl Debugger {
+        let thread = self.current_thread();
+        self.threads.borrow_mut().settle(thread.pid())?;
 
-    pub fn new<T: AsRef<OsStr> + Clone>(binary: T, args: Vec<T>)
-        -> Result<Self,DebugError>
-    {
+        /* stepping off a breakpoint is a single step already */
+        let current = self.current_breakpoint().map(|bp| bp.addr);
 
-        let child = Debugger::spawn(binary.clone(), args.clone())?;
-        let pid = child.id();
-        let process = Process::<x86_64_Registers>::new(pid);
-        let pc = pc!(process);
+        let deferred = if let Ok(addr) = current {
+            self.step_over_breakpoint(addr, &thread)?
+        } else if let Some(wp) = self.exec_watchpoint_at(&thread)? {
+            self.step_over_watchpoint(&thread, wp)?
+        } else {
+            let sig = self.threads.borrow_mut().take_signal(thread.pid());
 
-        let args = args.into_iter().map(|s|
-            s.as_ref().to_string_lossy().into_owned()
-        ).collect();
+            thread.step_signal(sig.unwrap_or(0))?;
+            thread.wait();
 
-        let d = Debugger {
-            process: process,
-            breakpoints: HashMap::new(),
-            actions: vec![],
-            file: binary.as_ref().to_string_lossy().into_owned(),
-            args: args,
-            child: child,
-            phantom_mgr: Rc::new(RefCell::new(PhantomManager::new(pid.into()))),
-            init_state: false,
-            log: LogLevel::Silent,
-            pc: Rc::new(RefCell::new(Some(pc))),
-            actions_at: HashMap::new(),
+            None
         };
 
-        Ok(d)
+        if let Some(event) = deferred {
+            return Ok(event);
+        }
+
+        let mut event = self.classify(thread.status())?;
+
+        if let StopEvent::Signal { signo, .. } = event {
+            self.apply_signal_policy(thread.pid(), signo);
+        }
+
+        /* a trap that doesn't stop, e.g. a tracepoint, still counts as the step */
+        if let StopEvent::Breakpoint { addr, .. } = event {
+            event = match self.skip_breakpoint(thread.pid(), addr, &mut None)? {
+                Some(event) => event,
+                None => StopEvent::Step { addr: thread.getregs()?.ip() },
+            };
+        }
+
+        Ok(event)
     }
 
-    pub fn run(&mut self) -> Result<String, DebugError> {
+    /* a library change on the way is a plain step unless the caller stops for it */
+    fn step_libraries(&self) -> Result<StopEvent, DebugError> {
 
-        self.log_command(&format!("running binary '{}' with argc {}", self.file, self.args.len()));
+        let event = self.step_event()?;
 
-        self.process.cont()?;
-
-        let pc = {
-            /* returns pc on success */
-            match self.process.wait_stop() {
-                Ok(x) => x,
-                Err(DebugError::Status(stat)) => {
-                    if stat.exited() {
-                        /* ran and exited without hitting breakpoints */
-                        return Ok(self.file.clone());
-                    } else {
-                        /* ran and errored out (segfault?) */
-                        return Err(DebugError::from(stat));
-                    }
+        match self.library_event(event)? {
+            Some(event) => Ok(event),
+            None => Ok(StopEvent::Step { addr: self.current_thread().getregs()?.ip() }),
+        }
+    }
+
+    /*
+     *  new libraries arm pending breakpoints, the change reaches the caller
+     *  only if it asked for it, after an exec there is a new dynamic linker
+     */
+    fn library_event(&self, event: StopEvent) -> Result<Option<StopEvent>, DebugError> {
+
+        match event {
+            StopEvent::Library(ref change) => {
+                if !change.unloaded.is_empty() {
+                    self.unmapped()?;
+                }
+
+                if !change.loaded.is_empty() {
+                    self.arm_pending()?;
+                }
+
+                if !self.library_stops.get() {
+                    return Ok(None);
+                }
+            },
+            StopEvent::Ptrace(PtraceEvent::Exec) => {
+                self.track_libraries()?;
+            },
+            _ => {},
+        }
+
+        Ok(Some(event))
+    }
+
+    /*
+     *  single steps until the source line changes, calls into code without
+     *  line information, e.g. libc, run until they return
+     */
+    pub fn step(&mut self) -> Result<StopEvent, DebugError> {
+        self.log_command("step");
+
+        let lines = self.lines()?;
+        let bias = self.load_bias()?;
+        let thread = self.current_thread();
+
+        let line = |addr: u64| lines.row(addr.wrapping_sub(bias))
+            .map(|row| (row.file, row.line, row.is_stmt));
+
+        let from = match line(self.thread_pc(&thread)?) {
+            Some((file, line, _)) => (file, line),
+            None => { return Err("No line information for the current instruction".into()); },
+        };
+
+        let event = loop {
+            let before = thread.getregs()?;
+            let pc = self.thread_pc(&thread)?;
+
+            let mut addr = match self.step_libraries()? {
+                StopEvent::Step { addr } => addr,
+                /* stepped onto a breakpoint last time, the step already stopped there, */
+                /* current_breakpoint needs the pc past the trap to step off it */
+                StopEvent::Breakpoint { addr, .. } if addr == pc => {
+                    self.set_pc(thread.getregs()?.ip());
+                    continue;
                 },
-                Err(x) => {
-                    return Err(x);
+                event => { break event; },
+            };
+
+            let regs = thread.getregs()?;
+
+            /* only a call pushes the address of the next instruction */
+            if line(addr).is_none() && regs.rsp == before.rsp.wrapping_sub(8) {
+                let ret = thread.peek(regs.rsp)?;
+
+                if ret > pc && ret <= pc + 15 {
+                    addr = match self.run_to(ret, Some(regs.rsp))? {
+                        StopEvent::Reached { addr } => addr,
+                        event => { break event; },
+                    };
                 }
             }
-        };
 
-        self.set_pc(pc);
+            match line(addr) {
+                Some((file, line, true)) if (file, line) != from => {
+                    break StopEvent::Step { addr: addr };
+                },
+                _ => {},
+            }
+        };
 
-        self.log_breakpoint();
-        self.on_break();
-        self.init_state = false;
+        self.stopped(&event);
 
-        Ok(self.file.clone())
+        Ok(event)
     }
 
-    fn set_pc(&self, pc: u64) {
-        *self.pc.borrow_mut() = Some(pc);
-    }
+    /* a call runs until it returns, everything else is a single step */
+    pub fn step_over(&mut self) -> Result<StopEvent, DebugError> {
+        self.log_command("step over");
+
+        let thread = self.current_thread();
+        let pc = self.thread_pc(&thread)?;
 
-    fn spawn<T: AsRef<OsStr>>(fcn: T, args: Vec<T>) -> Result<Child, DebugError> {
+        let insn = self.disassemble(pc, 1)?.pop()
+            .ok_or("Can't decode the current instruction")?;
 
-        match Command::new(fcn)
-            .args(&args)
-            .stdout(Stdio::piped())
-            .stdin(Stdio::piped())
-            .spawn_ptrace() {
+        let mut event = self.step_libraries()?;
 
-            Ok(s) => {
-                Ok(s)
+        match event {
+            /* in the callee now, until it returns past the call with the stack above it */
+            StopEvent::Step { addr } if insn.is_call() && addr != insn.next() => {
+                let rsp = thread.getregs()?.rsp;
+
+                event = match self.run_to(insn.next(), Some(rsp))? {
+                    StopEvent::Reached { addr } => StopEvent::Step { addr: addr },
+                    event => event,
+                };
             },
-            Err(e) => {
-                Err(DebugError::from(e))
-            }
+            /* each step runs one iteration, a trap on the instruction itself fires */
+            /* in between, keep going until the pc leaves the string instruction */
+            StopEvent::Step { .. } if insn.is_rep() => {
+                loop {
+                    match event {
+                        StopEvent::Step { addr } | StopEvent::Breakpoint { addr, .. } if addr == pc => {},
+                        _ => break,
+                    }
+
+                    event = self.step_libraries()?;
+                }
+            },
+            _ => {},
         }
 
+        self.stopped(&event);
+
+        Ok(event)
     }
 
-    pub fn breakpoint(&mut self, addr: u64) -> Result<&mut Breakpoint, DebugError> {
+    /* run until the current function returns, reports its rax */
+    pub fn finish(&mut self) -> Result<StopEvent, DebugError> {
+        self.log_command("finish");
 
-        let pid = self.process.pid;
+        let thread = self.current_thread();
+        let slot = self.return_slot(&thread)?;
+        let ret = thread.peek(slot)?;
 
-        let name = (self.breakpoints.len()+1).to_string();
-        let bp = Breakpoint::new(name, pid, addr)?;
+        /* deeper calls of a recursive function return to the same place */
+        let event = match self.run_to(ret, Some(slot))? {
+            StopEvent::Reached { addr } => StopEvent::Return {
+                addr: addr,
+                retval: thread.getregs()?.rax,
+            },
+            event => event,
+        };
 
-        self.log_command(&format!("set breakpoint {} @ 0x{:x}", self.breakpoints.len(), addr));
-        self.breakpoints.insert(addr+1, bp);
+        self.stopped(&event);
 
-        Ok(self.breakpoint_at_mut(addr+1).unwrap())
+        Ok(event)
     }
 
-    pub fn tmp_breakpoint(&mut self, addr: u64) -> Result<&mut Breakpoint, DebugError> {
+    /* any other stop on the way is reported instead */
+    pub fn run_until(&mut self, addr: u64) -> Result<StopEvent, DebugError> {
+        self.log_command(&format!("run until 0x{:x}", addr));
 
-        let pid = self.process.pid;
+        let event = self.run_to(addr, None)?;
+        self.stopped(&event);
 
-        let name = self.breakpoints.len().to_string();
-        let bp = Breakpoint::new(name, pid, addr)?;
+        Ok(event)
+    }
 
-        self.log_command(&format!("set breakpoint {} @ 0x{:x}", self.breakpoints.len(), addr));
-        self.breakpoints.insert(addr+1, bp);
+    /* continue until the current thread gets to addr, with the stack */
+    /* pointer above sp if given, and leave it on addr */
+    fn run_to(&mut self, addr: u64, sp: Option<u64>) -> Result<StopEvent, DebugError> {
 
-        Ok(self.breakpoint_at_mut(addr+1).unwrap())
+        let tid = self.current.get();
+
+        /* an existing breakpoint there does the job, it stays */
+        let temporary = self.breakpoint_at(addr+1).is_none();
+
+        if temporary {
+            self.untrap_rendezvous(addr);
+
+            let pid = self.process.pid();
+            let mut bp = Breakpoint::new(format!("<until @ 0x{:x}>", addr), pid, addr)?;
+
+            /* only stops through until */
+            bp.condition(|_, _| false);
+            self.breakpoints.get_mut().insert(addr+1, bp);
+        }
+
+        self.until.set(Some((tid, addr, sp)));
+        let event = self.cont_libraries();
+        self.until.set(None);
+
+        if temporary {
+            /* dropping takes the trap out */
+            self.breakpoints.get_mut().remove(&(addr+1));
+
+            if let Ok(StopEvent::Reached { .. }) = event {
+                let thread = self.current_thread();
+                let mut regs = thread.getregs()?;
+
+                regs.set_ip(addr);
+                thread.setregs(&regs)?;
+                self.trapped.set(None);
+            }
+        }
+
+        event
     }
 
-    pub fn cont(&self) -> Result<Option<u64>, DebugError> {
+    /* the thread hit the trap at addr and run_to was waiting for it */
+    fn reached(&self, tid: u32, addr: u64) -> Result<Option<StopEvent>, DebugError> {
 
-        self.log_command("continue");
+        match self.until.get() {
+            Some((until_tid, until, sp)) if until_tid == tid && until == addr => {
+                let rsp = self.current_thread().getregs()?.rsp;
 
-        let pc = match self.current_breakpoint() {
-            Ok(bp) => {
-                match bp.cont() {
-                    Ok(pc) => {
-                        pc
-                    },
-                    Err(DebugError::Status(stat)) => {
-                        return self.handle_status(stat);
-                    },
-                    Err(x) => {
-                        return Err(x);
-                    }
+                if sp.map_or(true, |sp| rsp > sp) {
+                    Ok(Some(StopEvent::Reached { addr: addr }))
+                } else {
+                    Ok(None)
                 }
             },
-            Err(_) => {
-                match self.non_bp_cont()? {
-                    Some(ip) => ip,
-                    None => { return Ok(None); }
-                }
-            }
+            _ => Ok(None),
+        }
+    }
+
+    /* where the return address of the current function is, the call frame information says */
+    fn return_slot(&self, thread: &Process<x86_64_Registers>) -> Result<u64, DebugError> {
+
+        let regs = thread.getregs()?;
+        let pc = self.thread_pc(thread)?;
+
+        let unwind = self.modules().ok()
+            .and_then(|modules| self.unwind_row(&modules, pc));
+
+        let (row, linked) = match unwind {
+            Some(unwind) => unwind,
+            None => {
+                return self.prologue_slot(thread)?
+                    .ok_or("No call frame information for the current function".into());
+            },
         };
 
-        self.set_pc(pc);
-        self.log_breakpoint();
-        self.on_break();
+        let cfa = unwind::cfa(&row, &regs, linked)?;
+
+        match row.rule(row.return_address) {
+            Rule::Offset(offset) => Ok(cfa.wrapping_add(offset as u64)),
+            _ => Err("The return address isn't saved on the stack".into()),
+        }
+    }
+
+    /* without call frame information the slot is only certain at the start */
+    /* of a function, [rsp] before the prologue ran and [rsp+8] after push rbp */
+    fn prologue_slot(&self, thread: &Process<x86_64_Registers>) -> Result<Option<u64>, DebugError> {
 
-        Ok(Some(pc))
+        let regs = thread.getregs()?;
+        let code = self.read_code(self.thread_pc(thread)?, 4)?;
+
+        let slot = match code.as_slice() {
+            /* endbr64 or push rbp, nothing pushed yet */
+            &[0xf3, 0x0f, 0x1e, 0xfa] | &[0x55, ..] | &[0xc3, ..] => Some(regs.rsp),
+            /* mov rbp, rsp right after push rbp */
+            &[0x48, 0x89, 0xe5, _] => Some(regs.rsp + 8),
+            _ => None,
+        };
+
+        Ok(slot)
     }
 
-    fn non_bp_cont(&self) -> Result<Option<u64>, DebugError> {
-        println!(" (not at breakpoint)");
-        self.process.cont()?;
-        let ip = self.process.wait_stop()?;
+    /* the instruction the thread is on, the trap it sits past counts as not executed */
+    fn thread_pc(&self, thread: &Process<x86_64_Registers>) -> Result<u64, DebugError> {
+
+        let ip = thread.getregs()?.ip();
 
-        Ok(Some(ip))
+        if self.trapped.get() == Some(thread.pid()) {
+            Ok(ip - 1)
+        } else {
+            Ok(ip)
+        }
     }
 
-    pub fn single_step(&self) -> Result<Option<u64>, DebugError> {
-        self.log_command("single step");
+    /* memory with the original bytes where our traps are */
+    pub fn read_code(&self, addr: u64, len: usize) -> Result<Vec<u8>, DebugError> {
 
-        self.process.step()?;
-        let ip = self.process.wait_stop()?;
-        self.log_breakpoint();
-        self.on_break();
-        Ok(Some(ip))
+        let mut code = self.process.read(addr, len)?;
+
+        self.each_trap(|bp| {
+            if bp.is_enabled() && bp.addr >= addr && bp.addr < addr + len as u64 {
+                code[(bp.addr - addr) as usize] = bp.original();
+            }
+            Ok(())
+        })?;
+
+        Ok(code)
+    }
+
+    /* count instructions from addr, breakpoints don't show up as int3 */
+    pub fn disassemble(&self, addr: u64, count: usize) -> Result<Vec<Instruction>, DebugError> {
+
+        let len = count * disasm::MAX_LEN;
+
+        /* the code may end at a page boundary before len bytes */
+        let code = match self.read_code(addr, len) {
+            Ok(code) => code,
+            Err(_) => {
+                let end = (addr & !0xfff) + 0x1000;
+                self.read_code(addr, (end - addr) as usize)?
+            },
+        };
+
+        Ok(disasm::disassemble(&code, addr, count))
     }
 
     pub fn phantom_call(&mut self, addr: u64, args: Vec<u64>, exits: Vec<u64>)
-        -> Result<Option<u64>, DebugError>
+        -> Result<StopEvent, DebugError>
+    {
+        self.phantom_call_float(addr, args, vec![], exits)
+    }
+
+    /* float_args go in xmm0-7 as doubles, the caller's fpu state is put back on return */
+    pub fn phantom_call_float(&mut self, addr: u64, args: Vec<u64>, float_args: Vec<f64>, exits: Vec<u64>)
+        -> Result<StopEvent, DebugError>
     {
 
         self.log_command(&format!("phantom call function @ 0x{:x}", addr));
 
-        let arg_regs = x86_64_Registers::from_process(self, args)?;
+        if float_args.len() > 8 {
+            return Err("phantom calls take at most 8 floating point arguments".into());
+        }
+
+        /* calls are made from, and return to, a breakpoint */
+        self.current_breakpoint()?;
+
+        let mut arg_regs = x86_64_Registers::from_process(self, args)?;
         let reset = self.process.getregs()?;
 
-        self.phantom_mgr.borrow_mut().push(reset, exits);
+        let reset_fp = if float_args.is_empty() {
+            None
+        } else {
+            let fp = self.process.getfpstate()?;
+            let mut arg_fp = fp.clone();
+
+            for (i, &arg) in float_args.iter().enumerate() {
+                arg_fp.set_xmm(i, Vector::from_f64(arg))?;
+            }
+
+            self.process.setfpstate(&arg_fp)?;
+
+            /* varargs functions take the number of vector registers used in al */
+            arg_regs.rax = float_args.len() as u64;
+
+            Some(fp)
+        };
+
+        self.phantom_mgr.borrow_mut().push(reset, reset_fp, exits);
 
         /* set args */
         self.process.setregs_user(&arg_regs)?;
 
-        let pc = self.current_breakpoint()?
-            .phantom_call(addr)?;
+        /* jump to function */
+        let mut regs = self.process.getregs()?;
+        regs.set_ip(addr);
+        self.process.setregs(&regs)?;
 
-        self.set_pc(pc);
-        self.log_breakpoint();
+        let event = loop {
+            let event = self.resume()?;
+
+            if let Some(event) = self.library_event(event)? {
+                break event;
+            }
+        };
         /* callbacks might continue */
-        self.on_break();
+        self.stopped(&event);
 
+        Ok(event)
+    }
 
-        Ok(Some(pc))
+    /* a pc just past a trap is also where a one byte instruction under it steps to */
+    fn is_trapped(&self) -> bool {
+        self.trapped.get() == Some(self.current.get())
     }
 
     pub fn at_breakpoint(&self) -> bool {
         if let Some(pc) = *self.pc.borrow() {
             match self.breakpoint_at(pc) {
-                Some(_) => true,
+                Some(_) => self.is_trapped(),
                 None => false,
             }
         } else {
@@ -277,39 +2388,35 @@ impl Debugger {
         }
     }
 
-    pub fn current_breakpoint(&self) -> Result<&Breakpoint, DebugError> {
+    pub fn current_breakpoint(&self) -> Result<Ref<Breakpoint>, DebugError> {
 
         if let Some(pc) = *self.pc.borrow() {
             match self.breakpoint_at(pc) {
-                Some(x) => Ok(x),
-                None => Err(DebugError::from("No breakpoint found at given address")),
+                Some(x) if self.is_trapped() => Ok(x),
+                _ => Err(DebugError::from("No breakpoint found at given address")),
             }
         } else {
             Err(DebugError::from("program is not currently running"))
         }
     }
 
-    pub fn breakpoint_at(&self, addr: u64) -> Option<&Breakpoint> {
-
-        if let Some(bp) = self.breakpoints.get(&addr) {
-            if bp.is_enabled() || bp.is_temporary() {
-                Some(bp)
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+    /* don't hold on to it across a continue, which may add or remove breakpoints */
+    pub fn breakpoint_at(&self, addr: u64) -> Option<Ref<Breakpoint>> {
+        Ref::filter_map(self.breakpoints.borrow(), |breakpoints| {
+            breakpoints.get(&addr).filter(|bp| bp.is_enabled())
+        }).ok()
     }
 
     pub fn current_breakpoint_mut(&mut self) -> Result<&mut Breakpoint, String> {
 
         let ip = self.pc.borrow().clone();
 
+        let trapped = self.is_trapped();
+
         if let Some(pc) = ip {
             match self.breakpoint_at_mut(pc) {
-                Some(x) => Ok(x),
-                None => Err("No breakpoint found at given address".to_string()),
+                Some(x) if trapped => Ok(x),
+                _ => Err("No breakpoint found at given address".to_string()),
             }
         } else {
             Err("program is not currently running".to_string())
@@ -318,7 +2425,7 @@ impl Debugger {
 
     pub fn breakpoint_at_mut(&mut self, addr: u64) -> Option<&mut Breakpoint> {
 
-        if let Some(bp) = self.breakpoints.get_mut(&addr) {
+        if let Some(bp) = self.breakpoints.get_mut().get_mut(&addr) {
             if bp.is_enabled() {
                 Some(bp)
             } else {
@@ -332,40 +2439,70 @@ impl Debugger {
     fn log_breakpoint(&self) {
         if self.log.contains(LogLevel::Breakpoints) {
             if let Ok(hit) = self.current_breakpoint() {
-                println!("0x{:x}: Encountered breakpoint {}", hit.addr, hit.name);
+                match self.location(hit.addr) {
+                    Some(location) => println!("0x{:x} <{}> at {}: Encountered breakpoint {}",
+                        hit.addr, self.symbolize(hit.addr), location, hit.name),
+                    None => println!("0x{:x} <{}>: Encountered breakpoint {}",
+                        hit.addr, self.symbolize(hit.addr), hit.name),
+                }
             }
         }
     }
 
+    fn log_libraries(&self, change: &LibraryChange) {
+        for lib in change.loaded.iter() {
+            self.log_event(&format!("loaded {} @ 0x{:x}", lib.path, lib.base));
+        }
+
+        for lib in change.unloaded.iter() {
+            self.log_event(&format!("unloaded {} @ 0x{:x}", lib.path, lib.base));
+        }
+    }
+
+    fn log_event<'a>(&self, event: &'a str) {
+        if self.log.contains(LogLevel::Events) {
+            println!("{}", event);
+        }
+    }
+
     fn log_command<'a>(&self, cmd: &'a str) {
         if self.log.contains(LogLevel::Commands) {
             println!("{}",cmd);
         }
     }
 
-    fn handle_status(&self, status: Status) -> Result<Option<u64>, DebugError> {
+    /* bookkeeping shared by every way of stopping */
+    fn stopped(&self, event: &StopEvent) {
 
-        if status.exited() {
-            Ok(None)
-        } else if status.trapped() {
-            println!("at 0x{:x}, available breakpoints:", pc!(self.process));
-            for (_,bp) in self.breakpoints.iter() {
-                println!("\t'{}' -> 0x{:x}", bp.name, bp.addr);
-            }
-            Err(DebugError::from("recieved SIGTRAP but not at actions_at breakpoint!"))
-        } else {
-            Err(DebugError::from("uknown error"))
+        if event.is_exit() {
+            *self.pc.borrow_mut() = None;
+        } else if let Ok(regs) = self.current_thread().getregs() {
+            self.set_pc(regs.ip());
         }
 
+        self.log_event(&format!("stopped: {:?}", event));
+        self.log_breakpoint();
+        self.on_break(event);
     }
 
-    fn on_break(&self) {
-        let process = &self.process;
+    fn on_break(&self, event: &StopEvent) {
+
+        let watched = match event {
+            &StopEvent::Watchpoint { ref name, addr, .. } |
+            &StopEvent::WatchChange { ref name, addr, .. } => Some((name, addr)),
+            _ => None,
+        };
+
+        if let Some((name, addr)) = watched {
+            if self.log.contains(LogLevel::Breakpoints) {
+                println!("0x{:x}: Encountered watchpoint {}", addr, name);
+            }
 
-        if self.phantom_mgr.borrow().is_exit(process) {
-            let pc =self.phantom_mgr.borrow_mut().clean(process)
-                .expect("Failed to reset process after phantom call");
-            self.set_pc(pc);
+            if let Some(functions) = self.watch_actions.get(&addr) {
+                for fct in functions {
+                    fct(&self);
+                }
+            }
         }
 
         let pc = match *self.pc.borrow() {
@@ -396,10 +2533,157 @@ impl Debugger {
                 .push(Box::new(fct));
     }
 
+    /* addr is the watched address */
+    pub fn register_watch_action<F>(&mut self, addr: u64, fct: F)
+        where F: Fn(&Debugger),
+        F: 'static
+    {
+            self.watch_actions.entry(addr)
+                .or_insert(vec![])
+                .push(Box::new(fct));
+    }
+
+    pub fn clear_watch_actions(&mut self, addr: u64) {
+            self.watch_actions.remove(&addr);
+    }
+
     pub fn clear_actions_at(&mut self, addr: u64) {
             self.actions_at.remove(&(addr+1));
     }
 
+    /* stop on every syscall entry and exit */
+    pub fn trace_syscalls(&self, on: bool) {
+        self.log_command(&format!("trace syscalls {}", if on { "on" } else { "off" }));
+        self.syscalls.set(on);
+    }
+
+    fn wants_syscalls(&self) -> bool {
+        self.syscalls.get()
+            || !self.syscall_actions.is_empty()
+            || !self.interceptors.is_empty()
+            || !self.skipped.borrow().is_empty()
+    }
+
+    /*
+     *  true at a syscall entry stop, false at the exit. Without
+     *  PTRACE_GET_SYSCALL_INFO rax is -ENOSYS on entry, which a call
+     *  returning ENOSYS or one we skipped also has on the way out
+     */
+    fn syscall_entry(&self, thread: &Process<x86_64_Registers>) -> Result<bool, DebugError> {
+
+        match thread.syscall_op() {
+            Ok(SYSCALL_INFO_ENTRY) => Ok(true),
+            Ok(SYSCALL_INFO_EXIT) => Ok(false),
+            _ => {
+                let skipped = self.skipped.borrow().contains_key(&thread.pid());
+                Ok(!skipped && thread.getregs()?.rax as i64 == -(ENOSYS as i64))
+            },
+        }
+    }
+
+    /* let interceptors rewrite the call before anyone else looks at it */
+    fn intercept(&self, thread: &Process<x86_64_Registers>, entry: bool)
+        -> Result<Syscall, DebugError>
+    {
+        let tid = thread.pid();
+        let mut regs = thread.getregs()?;
+
+        /* orig_rax is -1 on the way out of a skipped call */
+        let skipped = if entry { None } else { self.skipped.borrow_mut().remove(&tid) };
+        let nr = match skipped {
+            Some((nr, ret)) => {
+                regs.rax = ret as u64;
+                nr
+            },
+            None => regs.orig_rax,
+        };
+
+        let mut ctx = SyscallContext::new(tid, nr, regs, entry);
+
+        if let Some(functions) = self.interceptors.get(&nr) {
+            for fct in functions {
+                fct(&mut ctx);
+            }
+        }
+
+        if ctx.is_dirty() || skipped.is_some() {
+            thread.setregs(ctx.regs())?;
+        }
+
+        if let Some(ret) = ctx.skipped() {
+            self.skipped.borrow_mut().insert(tid, (nr, ret));
+        }
+
+        Ok(ctx.syscall())
+    }
+
+    fn on_syscall(&self, syscall: &Syscall) {
+
+        if self.log.contains(LogLevel::Events) {
+            let line = syscall.describe(self.current.get());
+
+            if syscall.is_entry() {
+                println!("[{}] enter {}", self.current.get(), line);
+            } else {
+                println!("[{}] {}", self.current.get(), line);
+            }
+        }
+
+        if let Some(functions) = self.syscall_actions.get(&syscall.nr) {
+            for fct in functions {
+                fct(&self, syscall);
+            }
+        }
+    }
+
+    /* runs on both the entry and the exit stop */
+    pub fn register_syscall_action<F>(&mut self, name: &str, fct: F) -> Result<(), DebugError>
+        where F: Fn(&Debugger, &Syscall),
+        F: 'static
+    {
+            let nr = match syscall_number(name) {
+                Some(nr) => nr,
+                None => { return Err("Unknown syscall".into()); },
+            };
+
+            self.syscall_actions.entry(nr)
+                .or_insert(vec![])
+                .push(Box::new(fct));
+
+            Ok(())
+    }
+
+    /* interceptors see entry and exit, see SyscallContext */
+    pub fn intercept_syscall<F>(&mut self, name: &str, fct: F) -> Result<(), DebugError>
+        where F: Fn(&mut SyscallContext),
+        F: 'static
+    {
+            let nr = match syscall_number(name) {
+                Some(nr) => nr,
+                None => { return Err("Unknown syscall".into()); },
+            };
+
+            self.log_command(&format!("intercept syscall {}", name));
+
+            self.interceptors.entry(nr)
+                .or_insert(vec![])
+                .push(Box::new(fct));
+
+            Ok(())
+    }
+
+    pub fn clear_interceptors(&mut self, name: &str) {
+        if let Some(nr) = syscall_number(name) {
+            self.interceptors.remove(&nr);
+        }
+    }
+
+    pub fn clear_syscall_actions(&mut self, name: &str) {
+        if let Some(nr) = syscall_number(name) {
+            self.syscall_actions.remove(&nr);
+        }
+    }
+
     pub fn register_action<F>(&mut self, fct: F)
         where F: Fn(&Debugger),
         F: 'static
@@ -408,3 +2692,12 @@ impl Debugger {
     }
 }
 
+
+/* 0x1b or 27 */
+fn parse_offset(offset: &str) -> Result<u64, DebugError> {
+    if offset.starts_with("0x") {
+        Ok(u64::from_str_radix(&offset[2..], 16)?)
+    } else {
+        Ok(offset.parse()?)
+    }
+}
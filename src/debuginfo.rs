@@ -0,0 +1,1264 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use dwarf::{self,Reader};
+use elf::Elf;
+use error::DebugError;
+use memory::Memory;
+use registers::x86_64_Registers;
+
+/* tags */
+pub const DW_TAG_ARRAY_TYPE: u64 = 0x01;
+pub const DW_TAG_CLASS_TYPE: u64 = 0x02;
+pub const DW_TAG_ENUMERATION_TYPE: u64 = 0x04;
+pub const DW_TAG_FORMAL_PARAMETER: u64 = 0x05;
+pub const DW_TAG_LEXICAL_BLOCK: u64 = 0x0b;
+pub const DW_TAG_MEMBER: u64 = 0x0d;
+pub const DW_TAG_POINTER_TYPE: u64 = 0x0f;
+pub const DW_TAG_REFERENCE_TYPE: u64 = 0x10;
+pub const DW_TAG_COMPILE_UNIT: u64 = 0x11;
+pub const DW_TAG_STRUCTURE_TYPE: u64 = 0x13;
+pub const DW_TAG_SUBROUTINE_TYPE: u64 = 0x15;
+pub const DW_TAG_TYPEDEF: u64 = 0x16;
+pub const DW_TAG_UNION_TYPE: u64 = 0x17;
+pub const DW_TAG_INLINED_SUBROUTINE: u64 = 0x1d;
+pub const DW_TAG_SUBRANGE_TYPE: u64 = 0x21;
+pub const DW_TAG_BASE_TYPE: u64 = 0x24;
+pub const DW_TAG_CONST_TYPE: u64 = 0x26;
+pub const DW_TAG_ENUMERATOR: u64 = 0x28;
+pub const DW_TAG_SUBPROGRAM: u64 = 0x2e;
+pub const DW_TAG_VARIABLE: u64 = 0x34;
+pub const DW_TAG_VOLATILE_TYPE: u64 = 0x35;
+pub const DW_TAG_RESTRICT_TYPE: u64 = 0x37;
+pub const DW_TAG_RVALUE_REFERENCE_TYPE: u64 = 0x42;
+pub const DW_TAG_ATOMIC_TYPE: u64 = 0x47;
+
+/* attributes */
+pub const DW_AT_LOCATION: u64 = 0x02;
+pub const DW_AT_NAME: u64 = 0x03;
+pub const DW_AT_BYTE_SIZE: u64 = 0x0b;
+pub const DW_AT_BIT_SIZE: u64 = 0x0d;
+pub const DW_AT_LOW_PC: u64 = 0x11;
+pub const DW_AT_HIGH_PC: u64 = 0x12;
+pub const DW_AT_CONST_VALUE: u64 = 0x1c;
+pub const DW_AT_UPPER_BOUND: u64 = 0x2f;
+pub const DW_AT_ABSTRACT_ORIGIN: u64 = 0x31;
+pub const DW_AT_COUNT: u64 = 0x37;
+pub const DW_AT_DATA_MEMBER_LOCATION: u64 = 0x38;
+pub const DW_AT_DECLARATION: u64 = 0x3c;
+pub const DW_AT_ENCODING: u64 = 0x3e;
+pub const DW_AT_FRAME_BASE: u64 = 0x40;
+pub const DW_AT_SPECIFICATION: u64 = 0x47;
+pub const DW_AT_TYPE: u64 = 0x49;
+pub const DW_AT_DATA_BIT_OFFSET: u64 = 0x6b;
+pub const DW_AT_STR_OFFSETS_BASE: u64 = 0x72;
+pub const DW_AT_ADDR_BASE: u64 = 0x73;
+
+/* base type encodings */
+const DW_ATE_BOOLEAN: u64 = 0x02;
+const DW_ATE_FLOAT: u64 = 0x04;
+const DW_ATE_SIGNED: u64 = 0x05;
+const DW_ATE_SIGNED_CHAR: u64 = 0x06;
+const DW_ATE_UNSIGNED_CHAR: u64 = 0x08;
+const DW_ATE_UTF: u64 = 0x10;
+
+/* DWARF 5 unit types with extra header fields */
+const DW_UT_TYPE: u8 = 0x02;
+const DW_UT_SKELETON: u8 = 0x04;
+const DW_UT_SPLIT_COMPILE: u8 = 0x05;
+const DW_UT_SPLIT_TYPE: u8 = 0x06;
+
+/* elements shown of an array, bytes of a string behind a char * */
+const MAX_ELEMENTS: usize = 200;
+const MAX_STRING: usize = 200;
+
+/* DWARF register numbers, section 3.6.2 of the x86_64 psABI */
+pub fn register(regs: &x86_64_Registers, number: u64) -> Option<u64> {
+    Some(match number {
+        0 => regs.rax,
+        1 => regs.rdx,
+        2 => regs.rcx,
+        3 => regs.rbx,
+        4 => regs.rsi,
+        5 => regs.rdi,
+        6 => regs.rbp,
+        7 => regs.rsp,
+        8 => regs.r8,
+        9 => regs.r9,
+        10 => regs.r10,
+        11 => regs.r11,
+        12 => regs.r12,
+        13 => regs.r13,
+        14 => regs.r14,
+        15 => regs.r15,
+        16 => regs.rip,
+        _ => { return None; },
+    })
+}
+
+pub fn register_mut(regs: &mut x86_64_Registers, number: u64) -> Option<&mut u64> {
+    Some(match number {
+        0 => &mut regs.rax,
+        1 => &mut regs.rdx,
+        2 => &mut regs.rcx,
+        3 => &mut regs.rbx,
+        4 => &mut regs.rsi,
+        5 => &mut regs.rdi,
+        6 => &mut regs.rbp,
+        7 => &mut regs.rsp,
+        8 => &mut regs.r8,
+        9 => &mut regs.r9,
+        10 => &mut regs.r10,
+        11 => &mut regs.r11,
+        12 => &mut regs.r12,
+        13 => &mut regs.r13,
+        14 => &mut regs.r14,
+        15 => &mut regs.r15,
+        16 => &mut regs.rip,
+        _ => { return None; },
+    })
+}
+
+#[derive(Debug,Clone)]
+pub enum Attr {
+    Addr(u64),
+    Udata(u64),
+    Sdata(i64),
+    Flag(bool),
+    Str(String),
+    /* offset of the DIE in .debug_info */
+    Ref(usize),
+    /* exprloc and blocks */
+    Block(Vec<u8>),
+    SecOffset(u64),
+    /* resolved against the unit's bases once it's read */
+    Strx(u64),
+    Addrx(u64),
+}
+
+impl Attr {
+    pub fn udata(&self) -> Option<u64> {
+        match self {
+            &Attr::Udata(v) | &Attr::Addr(v) => Some(v),
+            &Attr::Sdata(v) => Some(v as u64),
+            _ => None,
+        }
+    }
+
+    pub fn sdata(&self) -> Option<i64> {
+        match self {
+            &Attr::Sdata(v) => Some(v),
+            &Attr::Udata(v) => Some(v as i64),
+            _ => None,
+        }
+    }
+}
+
+/* debugging information entry */
+#[derive(Debug,Clone)]
+pub struct Die {
+    pub offset: usize,
+    pub tag: u64,
+    pub attrs: Vec<(u64, Attr)>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+impl Die {
+    pub fn attr(&self, at: u64) -> Option<&Attr> {
+        self.attrs.iter().find(|&&(a, _)| a == at).map(|&(_, ref attr)| attr)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        match self.attr(DW_AT_NAME) {
+            Some(&Attr::Str(ref name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn udata(&self, at: u64) -> Option<u64> {
+        self.attr(at).and_then(|attr| attr.udata())
+    }
+
+    /* [low, high) in link time addresses */
+    pub fn pc_range(&self) -> Option<(u64, u64)> {
+        let low = match self.attr(DW_AT_LOW_PC) {
+            Some(&Attr::Addr(low)) => low,
+            _ => { return None; },
+        };
+
+        /* high_pc is an address, or in DWARF 4 and later a length */
+        match self.attr(DW_AT_HIGH_PC) {
+            Some(&Attr::Addr(high)) => Some((low, high)),
+            Some(attr) => attr.udata().map(|len| (low, low + len)),
+            None => Some((low, low + 1)),
+        }
+    }
+
+    pub fn contains(&self, pc: u64) -> bool {
+        self.pc_range().map_or(false, |(low, high)| pc >= low && pc < high)
+    }
+}
+
+/* where a variable lives */
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Place {
+    Memory(u64),
+    Register(u64),
+    /* DW_OP_stack_value, there is only the value */
+    Value(u64),
+}
+
+/* what location expressions need of the frame they are evaluated in */
+#[derive(Debug,Clone)]
+pub struct Frame {
+    /* link time */
+    pub pc: u64,
+    pub regs: x86_64_Registers,
+    /* canonical frame address, rsp before the call */
+    pub cfa: u64,
+}
+
+/* a decoded value of some type */
+#[derive(Debug,Clone,PartialEq)]
+pub enum Value {
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+    Bool(bool),
+    Char(u8),
+    /* char arrays, and what a char * points to */
+    Str(String),
+    Pointer { addr: u64, string: Option<String> },
+    Enum { value: i64, name: Option<String> },
+    Struct(Vec<(String, Value)>),
+    /* elements and whether there were more */
+    Array(Vec<Value>, bool),
+    /* types we don't decode, e.g. functions */
+    Bytes(Vec<u8>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Value::Int(v) => write!(f, "{}", v),
+            &Value::Uint(v) => write!(f, "{}", v),
+            &Value::Float(v) => write!(f, "{}", v),
+            &Value::Bool(v) => write!(f, "{}", v),
+            &Value::Char(c) if c >= 0x20 && c < 0x7f => write!(f, "{} '{}'", c as i8, c as char),
+            &Value::Char(c) => write!(f, "{} '\\{:03o}'", c as i8, c),
+            &Value::Str(ref s) => write!(f, "{:?}", s),
+            &Value::Pointer { addr, string: Some(ref s) } => write!(f, "0x{:x} {:?}", addr, s),
+            &Value::Pointer { addr, string: None } => write!(f, "0x{:x}", addr),
+            &Value::Enum { name: Some(ref name), .. } => write!(f, "{}", name),
+            &Value::Enum { value, name: None } => write!(f, "{}", value),
+            &Value::Struct(ref fields) => {
+                write!(f, "{{")?;
+                for (i, &(ref name, ref value)) in fields.iter().enumerate() {
+                    write!(f, "{}{} = {}", if i > 0 { ", " } else { "" }, name, value)?;
+                }
+                write!(f, "}}")
+            },
+            &Value::Array(ref values, more) => {
+                write!(f, "{{")?;
+                for (i, value) in values.iter().enumerate() {
+                    write!(f, "{}{}", if i > 0 { ", " } else { "" }, value)?;
+                }
+                write!(f, "{}}}", if more { "..." } else { "" })
+            },
+            &Value::Bytes(ref bytes) => {
+                write!(f, "<")?;
+                for b in bytes.iter() {
+                    write!(f, "{:02x}", b)?;
+                }
+                write!(f, ">")
+            },
+        }
+    }
+}
+
+/* a variable read out of the process */
+#[derive(Debug,Clone)]
+pub struct Variable {
+    pub name: String,
+    pub type_name: String,
+    /* None if it lives in a register */
+    pub addr: Option<u64>,
+    pub value: Value,
+}
+
+impl fmt::Display for Variable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}", self.name, self.value)
+    }
+}
+
+struct Abbrev {
+    tag: u64,
+    children: bool,
+    /* attribute, form and the value of implicit_const */
+    specs: Vec<(u64, u64, i64)>,
+}
+
+/* everything .debug_info says about the executable */
+pub struct DebugInfo {
+    pub dies: Vec<Die>,
+    /* compilation units, top level DIEs */
+    pub units: Vec<usize>,
+    offsets: HashMap<usize, usize>,
+}
+
+/* a unit being read */
+struct Unit<'a> {
+    version: u16,
+    dwarf64: bool,
+    addr_size: usize,
+    offset: usize,
+    abbrevs: HashMap<u64, Abbrev>,
+    strings: &'a [u8],
+    line_strings: &'a [u8],
+}
+
+impl DebugInfo {
+    pub fn parse(elf: &Elf) -> Result<DebugInfo, DebugError> {
+
+        let section = |name: &str| elf.section(name).map_or(&[][..], |s| elf.section_data(s));
+
+        let info = section(".debug_info");
+        let abbrev = section(".debug_abbrev");
+        let str_offsets = section(".debug_str_offsets");
+        let addrs = section(".debug_addr");
+
+        if info.is_empty() {
+            return Err("No .debug_info section, compile with -g".into());
+        }
+
+        let mut debug = DebugInfo {
+            dies: vec![],
+            units: vec![],
+            offsets: HashMap::new(),
+        };
+
+        let mut reader = Reader::new(info, 0);
+
+        while !reader.is_empty() {
+            let offset = reader.pos;
+            let (end, dwarf64) = reader.unit_length()?;
+            let version = reader.u16()?;
+
+            let (abbrev_offset, addr_size) = if version >= 5 {
+                let unit_type = reader.u8()?;
+                let addr_size = reader.u8()? as usize;
+                let abbrev_offset = reader.offset(dwarf64)?;
+
+                match unit_type {
+                    DW_UT_SKELETON | DW_UT_SPLIT_COMPILE => { reader.skip(8)?; },
+                    DW_UT_TYPE | DW_UT_SPLIT_TYPE => {
+                        reader.skip(8)?;
+                        reader.offset(dwarf64)?;
+                    },
+                    _ => {},
+                }
+
+                (abbrev_offset, addr_size)
+            } else {
+                let abbrev_offset = reader.offset(dwarf64)?;
+                (abbrev_offset, reader.u8()? as usize)
+            };
+
+            let mut unit = Unit {
+                version: version,
+                dwarf64: dwarf64,
+                addr_size: addr_size,
+                offset: offset,
+                abbrevs: abbrevs(abbrev, abbrev_offset as usize)?,
+                strings: section(".debug_str"),
+                line_strings: section(".debug_line_str"),
+            };
+
+            let first = debug.dies.len();
+            let mut units = Reader::new(&info[..end.min(info.len())], reader.pos);
+
+            debug.read_dies(&mut unit, &mut units)?;
+            debug.resolve_indices(first, &unit, str_offsets, addrs);
+
+            if first < debug.dies.len() {
+                debug.units.push(first);
+            }
+
+            reader.pos = end;
+        }
+
+        for (i, die) in debug.dies.iter().enumerate() {
+            debug.offsets.insert(die.offset, i);
+        }
+
+        Ok(debug)
+    }
+
+    /* a unit's DIEs as a tree, children follow their parent */
+    fn read_dies(&mut self, unit: &mut Unit, reader: &mut Reader) -> Result<(), DebugError> {
+
+        let mut parents: Vec<usize> = vec![];
+
+        while !reader.is_empty() {
+            let offset = reader.pos;
+            let code = reader.uleb()?;
+
+            /* end of a list of children */
+            if code == 0 {
+                if parents.pop().is_none() {
+                    break;
+                }
+                continue;
+            }
+
+            let (tag, children, specs) = {
+                let abbrev = unit.abbrevs.get(&code).ok_or("Unknown abbreviation code")?;
+                (abbrev.tag, abbrev.children, abbrev.specs.clone())
+            };
+
+            let mut attrs = vec![];
+
+            for &(at, form, implicit) in specs.iter() {
+                attrs.push((at, unit.attr(reader, form, implicit)?));
+            }
+
+            let index = self.dies.len();
+            let parent = parents.last().cloned();
+
+            if let Some(parent) = parent {
+                self.dies[parent].children.push(index);
+            }
+
+            self.dies.push(Die {
+                offset: offset,
+                tag: tag,
+                attrs: attrs,
+                parent: parent,
+                children: vec![],
+            });
+
+            if children {
+                parents.push(index);
+            } else if parent.is_none() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /* strx and addrx forms index tables the unit DIE gives the base of */
+    fn resolve_indices(&mut self, first: usize, unit: &Unit, str_offsets: &[u8], addrs: &[u8]) {
+
+        if first >= self.dies.len() {
+            return;
+        }
+
+        let offset_size = if unit.dwarf64 { 8 } else { 4 };
+        let str_base = self.dies[first].udata(DW_AT_STR_OFFSETS_BASE).unwrap_or(8);
+        let addr_base = self.dies[first].udata(DW_AT_ADDR_BASE).unwrap_or(8);
+
+        for die in self.dies[first..].iter_mut() {
+            for &mut (_, ref mut attr) in die.attrs.iter_mut() {
+                let resolved = match *attr {
+                    Attr::Strx(index) => {
+                        let at = (str_base + index * offset_size) as usize;
+
+                        Reader::new(str_offsets, at).uint(offset_size as usize).ok()
+                            .and_then(|offset| dwarf::string_at(unit.strings, offset).ok())
+                            .map(|s| Attr::Str(s.to_string()))
+                    },
+                    Attr::Addrx(index) => {
+                        let at = (addr_base + index * unit.addr_size as u64) as usize;
+
+                        Reader::new(addrs, at).uint(unit.addr_size).ok()
+                            .map(Attr::Addr)
+                    },
+                    _ => { continue; },
+                };
+
+                if let Some(resolved) = resolved {
+                    *attr = resolved;
+                }
+            }
+        }
+    }
+
+    pub fn die(&self, offset: usize) -> Option<&Die> {
+        self.offsets.get(&offset).map(|&i| &self.dies[i])
+    }
+
+    /* the DIE an attribute refers to */
+    pub fn reference(&self, die: &Die, at: u64) -> Option<&Die> {
+        match die.attr(at) {
+            Some(&Attr::Ref(offset)) => self.die(offset),
+            _ => None,
+        }
+    }
+
+    /* name, following the declaration or inlined original that has it */
+    pub fn name<'a>(&'a self, die: &'a Die) -> Option<&'a str> {
+        die.name().or_else(|| {
+            self.reference(die, DW_AT_SPECIFICATION)
+                .or_else(|| self.reference(die, DW_AT_ABSTRACT_ORIGIN))
+                .and_then(|origin| origin.name())
+        })
+    }
+
+    /* the function a link time pc is in */
+    pub fn function(&self, pc: u64) -> Option<&Die> {
+        self.dies.iter()
+            .find(|die| die.tag == DW_TAG_SUBPROGRAM && die.contains(pc))
+    }
+
+    /*
+     *  a variable visible at pc, locals of the innermost block first, then
+     *  parameters, then globals with the unit containing pc going first
+     */
+    pub fn variable(&self, name: &str, pc: u64) -> Option<&Die> {
+
+        if let Some(function) = self.function(pc) {
+            if let Some(local) = self.local(function, name, pc) {
+                return Some(local);
+            }
+        }
+
+        let named = |die: &&Die| {
+            die.tag == DW_TAG_VARIABLE && self.name(die) == Some(name)
+                && die.attr(DW_AT_LOCATION).is_some()
+        };
+
+        let unit = self.units.iter()
+            .map(|&i| &self.dies[i])
+            .find(|unit| unit.contains(pc));
+
+        if let Some(unit) = unit {
+            if let Some(global) = unit.children.iter().map(|&i| &self.dies[i]).find(&named) {
+                return Some(global);
+            }
+        }
+
+        self.units.iter()
+            .flat_map(|&i| self.dies[i].children.iter())
+            .map(|&i| &self.dies[i])
+            .find(named)
+    }
+
+    fn local<'a>(&'a self, scope: &'a Die, name: &str, pc: u64) -> Option<&'a Die> {
+
+        /* nested blocks shadow the outer ones */
+        for &child in scope.children.iter() {
+            let die = &self.dies[child];
+
+            if (die.tag == DW_TAG_LEXICAL_BLOCK || die.tag == DW_TAG_INLINED_SUBROUTINE)
+                && (die.contains(pc) || die.pc_range().is_none())
+            {
+                if let Some(local) = self.local(die, name, pc) {
+                    return Some(local);
+                }
+            }
+        }
+
+        scope.children.iter()
+            .map(|&i| &self.dies[i])
+            .find(|die| (die.tag == DW_TAG_VARIABLE || die.tag == DW_TAG_FORMAL_PARAMETER)
+                && self.name(die) == Some(name))
+    }
+
+    /* subprogram a DIE is declared in, for its frame base */
+    fn enclosing_function(&self, die: &Die) -> Option<&Die> {
+
+        let mut parent = die.parent;
+
+        while let Some(i) = parent {
+            if self.dies[i].tag == DW_TAG_SUBPROGRAM {
+                return Some(&self.dies[i]);
+            }
+            parent = self.dies[i].parent;
+        }
+
+        None
+    }
+
+    /* evaluate DW_AT_location, addresses come out at runtime, moved by bias */
+    pub fn location(&self, die: &Die, frame: &Frame, bias: u64) -> Result<Place, DebugError> {
+
+        let expr = match die.attr(DW_AT_LOCATION) {
+            Some(&Attr::Block(ref expr)) => expr,
+            Some(&Attr::SecOffset(_)) | Some(&Attr::Udata(_)) => {
+                return Err("Location lists are not supported, compile with -O0".into());
+            },
+            _ => { return Err("Variable was optimized out".into()); },
+        };
+
+        let frame_base = match self.enclosing_function(die).and_then(|f| f.attr(DW_AT_FRAME_BASE)) {
+            Some(&Attr::Block(ref expr)) => match evaluate(expr, frame, bias, None)? {
+                Place::Memory(addr) | Place::Value(addr) => Some(addr),
+                Place::Register(reg) => register(&frame.regs, reg),
+            },
+            _ => None,
+        };
+
+        evaluate(expr, frame, bias, frame_base)
+    }
+
+    /* what DW_AT_type leads to, without typedefs and qualifiers */
+    pub fn strip<'a>(&'a self, mut ty: Option<&'a Die>) -> Option<&'a Die> {
+        while let Some(die) = ty {
+            match die.tag {
+                DW_TAG_TYPEDEF | DW_TAG_CONST_TYPE | DW_TAG_VOLATILE_TYPE |
+                DW_TAG_RESTRICT_TYPE | DW_TAG_ATOMIC_TYPE => {
+                    ty = self.reference(die, DW_AT_TYPE);
+                },
+                _ => { return Some(die); },
+            }
+        }
+        None
+    }
+
+    pub fn type_of(&self, die: &Die) -> Option<&Die> {
+        self.reference(die, DW_AT_TYPE).or_else(|| {
+            self.reference(die, DW_AT_ABSTRACT_ORIGIN)
+                .or_else(|| self.reference(die, DW_AT_SPECIFICATION))
+                .and_then(|origin| self.reference(origin, DW_AT_TYPE))
+        })
+    }
+
+    /* bytes a value of the type takes */
+    pub fn size(&self, ty: Option<&Die>) -> Option<u64> {
+
+        let ty = ty?;
+
+        if let Some(size) = ty.udata(DW_AT_BYTE_SIZE) {
+            return Some(size);
+        }
+
+        match ty.tag {
+            DW_TAG_POINTER_TYPE | DW_TAG_REFERENCE_TYPE | DW_TAG_RVALUE_REFERENCE_TYPE => Some(8),
+            DW_TAG_TYPEDEF | DW_TAG_CONST_TYPE | DW_TAG_VOLATILE_TYPE |
+            DW_TAG_RESTRICT_TYPE | DW_TAG_ATOMIC_TYPE => self.size(self.reference(ty, DW_AT_TYPE)),
+            DW_TAG_ARRAY_TYPE => {
+                let element = self.size(self.reference(ty, DW_AT_TYPE))?;
+                Some(self.dimensions(ty).iter().fold(element, |size, &n| size * n))
+            },
+            _ => None,
+        }
+    }
+
+    /* element counts of each dimension, 0 for int a[] */
+    fn dimensions(&self, array: &Die) -> Vec<u64> {
+        array.children.iter()
+            .map(|&i| &self.dies[i])
+            .filter(|die| die.tag == DW_TAG_SUBRANGE_TYPE)
+            .map(|range| match range.udata(DW_AT_COUNT) {
+                Some(count) => count,
+                /* C arrays start at 0 */
+                None => range.attr(DW_AT_UPPER_BOUND)
+                    .and_then(|bound| bound.sdata())
+                    .map_or(0, |bound| (bound + 1).max(0) as u64),
+            })
+            .collect()
+    }
+
+    /* as C spells it, e.g. const char *, struct point, int [3] */
+    pub fn type_name(&self, ty: Option<&Die>) -> String {
+
+        let ty = match ty {
+            Some(ty) => ty,
+            None => { return "void".to_string(); },
+        };
+
+        let inner = || self.type_name(self.reference(ty, DW_AT_TYPE));
+        let name = || ty.name().unwrap_or("<anonymous>").to_string();
+
+        match ty.tag {
+            DW_TAG_BASE_TYPE | DW_TAG_TYPEDEF => name(),
+            DW_TAG_STRUCTURE_TYPE => format!("struct {}", name()),
+            DW_TAG_UNION_TYPE => format!("union {}", name()),
+            DW_TAG_CLASS_TYPE => format!("class {}", name()),
+            DW_TAG_ENUMERATION_TYPE => format!("enum {}", name()),
+            DW_TAG_POINTER_TYPE => format!("{} *", inner()),
+            DW_TAG_REFERENCE_TYPE => format!("{} &", inner()),
+            DW_TAG_RVALUE_REFERENCE_TYPE => format!("{} &&", inner()),
+            DW_TAG_CONST_TYPE => format!("const {}", inner()),
+            DW_TAG_VOLATILE_TYPE => format!("volatile {}", inner()),
+            DW_TAG_RESTRICT_TYPE => format!("{} restrict", inner()),
+            DW_TAG_ATOMIC_TYPE => format!("_Atomic {}", inner()),
+            DW_TAG_SUBROUTINE_TYPE => format!("{} ()", inner()),
+            DW_TAG_ARRAY_TYPE => {
+                let dims: String = self.dimensions(ty).iter()
+                    .map(|n| format!("[{}]", n))
+                    .collect();
+                format!("{} {}", inner(), dims)
+            },
+            _ => name(),
+        }
+    }
+
+    /* decode bytes as a ty, following char pointers into memory */
+    pub fn value(&self, ty: Option<&Die>, bytes: &[u8], memory: &mut Memory) -> Result<Value, DebugError> {
+
+        let ty = match self.strip(ty) {
+            Some(ty) => ty,
+            None => { return Ok(Value::Bytes(bytes.to_vec())); },
+        };
+
+        match ty.tag {
+            DW_TAG_BASE_TYPE => Ok(base_value(ty.udata(DW_AT_ENCODING).unwrap_or(0), bytes)),
+            DW_TAG_POINTER_TYPE | DW_TAG_REFERENCE_TYPE | DW_TAG_RVALUE_REFERENCE_TYPE => {
+                let addr = uint(bytes);
+
+                let string = if addr != 0 && self.is_char(self.reference(ty, DW_AT_TYPE)) {
+                    memory.read_str(addr as usize, MAX_STRING).ok()
+                } else {
+                    None
+                };
+
+                Ok(Value::Pointer { addr: addr, string: string })
+            },
+            DW_TAG_ENUMERATION_TYPE => {
+                let value = sint(bytes);
+
+                let name = ty.children.iter()
+                    .map(|&i| &self.dies[i])
+                    .find(|e| e.tag == DW_TAG_ENUMERATOR
+                        && e.attr(DW_AT_CONST_VALUE).and_then(|v| v.sdata()).map(|v| sign_extend(v, bytes.len())) == Some(value))
+                    .and_then(|e| e.name())
+                    .map(|name| name.to_string());
+
+                Ok(Value::Enum { value: value, name: name })
+            },
+            DW_TAG_STRUCTURE_TYPE | DW_TAG_CLASS_TYPE | DW_TAG_UNION_TYPE => {
+                let mut fields = vec![];
+
+                for &i in ty.children.iter() {
+                    let member = &self.dies[i];
+
+                    if member.tag != DW_TAG_MEMBER {
+                        continue;
+                    }
+
+                    let name = member.name().unwrap_or("<anonymous>").to_string();
+                    fields.push((name, self.member(member, bytes, memory)?));
+                }
+
+                Ok(Value::Struct(fields))
+            },
+            DW_TAG_ARRAY_TYPE => self.array(ty, bytes, memory),
+            _ => Ok(Value::Bytes(bytes.to_vec())),
+        }
+    }
+
+    fn member(&self, member: &Die, bytes: &[u8], memory: &mut Memory) -> Result<Value, DebugError> {
+
+        let ty = self.type_of(member);
+        let size = self.size(ty).unwrap_or(0) as usize;
+
+        /* bit fields are read out of the bytes they start in */
+        if let Some(bits) = member.udata(DW_AT_BIT_SIZE) {
+            let offset = member.udata(DW_AT_DATA_BIT_OFFSET)
+                .or_else(|| member.udata(DW_AT_DATA_MEMBER_LOCATION).map(|bytes| bytes * 8))
+                .unwrap_or(0);
+
+            let start = (offset / 8) as usize;
+            let end = (start + 8).min(bytes.len());
+            let word = uint(bytes.get(start..end).unwrap_or(&[])) >> (offset % 8);
+            let value = if bits >= 64 { word } else { word & ((1 << bits) - 1) };
+
+            let signed = self.strip(ty)
+                .and_then(|ty| ty.udata(DW_AT_ENCODING))
+                .map_or(false, |encoding| encoding == DW_ATE_SIGNED || encoding == DW_ATE_SIGNED_CHAR);
+
+            return Ok(if signed && bits < 64 && value >> (bits - 1) & 1 == 1 {
+                Value::Int((value | !0 << bits) as i64)
+            } else if signed {
+                Value::Int(value as i64)
+            } else {
+                Value::Uint(value)
+            });
+        }
+
+        let offset = match member.attr(DW_AT_DATA_MEMBER_LOCATION) {
+            Some(&Attr::Block(_)) => { return Err("Computed member offsets are not supported".into()); },
+            Some(attr) => attr.udata().unwrap_or(0) as usize,
+            None => 0,
+        };
+
+        let field = bytes.get(offset..offset + size).ok_or("Member outside of its struct")?;
+        self.value(ty, field, memory)
+    }
+
+    fn array(&self, ty: &Die, bytes: &[u8], memory: &mut Memory) -> Result<Value, DebugError> {
+
+        let element = self.type_of(ty);
+        let dims = self.dimensions(ty);
+        let size = self.size(element).unwrap_or(0) as usize;
+
+        /* char buf[16] reads as the string in it */
+        if dims.len() == 1 && self.is_char(element) {
+            let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            return Ok(Value::Str(String::from_utf8_lossy(&bytes[..len]).into_owned()));
+        }
+
+        /* int a[2][3] is two int [3] */
+        let (count, stride) = match dims.split_first() {
+            Some((&count, rest)) => (count as usize, rest.iter().fold(size, |s, &n| s * n as usize)),
+            None => (0, size),
+        };
+
+        let mut values = vec![];
+
+        for i in 0..count.min(MAX_ELEMENTS) {
+            let chunk = match bytes.get(i * stride..(i + 1) * stride) {
+                Some(chunk) if stride > 0 => chunk,
+                _ => { break; },
+            };
+
+            let value = if dims.len() > 1 {
+                let mut inner = ty.clone();
+                inner.children = ty.children.iter()
+                    .filter(|&&c| self.dies[c].tag == DW_TAG_SUBRANGE_TYPE)
+                    .skip(1)
+                    .cloned()
+                    .collect();
+                self.array(&inner, chunk, memory)?
+            } else {
+                self.value(element, chunk, memory)?
+            };
+
+            values.push(value);
+        }
+
+        Ok(Value::Array(values, count > MAX_ELEMENTS))
+    }
+
+    fn is_char(&self, ty: Option<&Die>) -> bool {
+        match self.strip(ty) {
+            Some(ty) if ty.tag == DW_TAG_BASE_TYPE => {
+                ty.udata(DW_AT_BYTE_SIZE) == Some(1) && match ty.udata(DW_AT_ENCODING) {
+                    Some(DW_ATE_SIGNED_CHAR) | Some(DW_ATE_UNSIGNED_CHAR) | Some(DW_ATE_UTF) => true,
+                    _ => false,
+                }
+            },
+            _ => false,
+        }
+    }
+}
+
+impl<'a> Unit<'a> {
+    fn attr(&self, reader: &mut Reader, form: u64, implicit: i64) -> Result<Attr, DebugError> {
+        Ok(match form {
+            dwarf::DW_FORM_ADDR => Attr::Addr(reader.uint(self.addr_size)?),
+            dwarf::DW_FORM_BLOCK1 => { let len = reader.u8()? as usize; Attr::Block(reader.bytes(len)?.to_vec()) },
+            dwarf::DW_FORM_BLOCK2 => { let len = reader.u16()? as usize; Attr::Block(reader.bytes(len)?.to_vec()) },
+            dwarf::DW_FORM_BLOCK4 => { let len = reader.u32()? as usize; Attr::Block(reader.bytes(len)?.to_vec()) },
+            dwarf::DW_FORM_BLOCK | dwarf::DW_FORM_EXPRLOC => {
+                let len = reader.uleb()? as usize;
+                Attr::Block(reader.bytes(len)?.to_vec())
+            },
+            dwarf::DW_FORM_DATA1 => Attr::Udata(reader.uint(1)?),
+            dwarf::DW_FORM_DATA2 => Attr::Udata(reader.uint(2)?),
+            dwarf::DW_FORM_DATA4 => Attr::Udata(reader.uint(4)?),
+            dwarf::DW_FORM_DATA8 => Attr::Udata(reader.uint(8)?),
+            dwarf::DW_FORM_DATA16 => Attr::Block(reader.bytes(16)?.to_vec()),
+            dwarf::DW_FORM_SDATA => Attr::Sdata(reader.sleb()?),
+            dwarf::DW_FORM_UDATA => Attr::Udata(reader.uleb()?),
+            dwarf::DW_FORM_IMPLICIT_CONST => Attr::Sdata(implicit),
+            dwarf::DW_FORM_STRING => Attr::Str(reader.cstr()?.to_string()),
+            dwarf::DW_FORM_STRP => {
+                let offset = reader.offset(self.dwarf64)?;
+                Attr::Str(dwarf::string_at(self.strings, offset)?.to_string())
+            },
+            dwarf::DW_FORM_LINE_STRP => {
+                let offset = reader.offset(self.dwarf64)?;
+                Attr::Str(dwarf::string_at(self.line_strings, offset)?.to_string())
+            },
+            dwarf::DW_FORM_STRX | dwarf::DW_FORM_STRX1 | dwarf::DW_FORM_STRX2 |
+            dwarf::DW_FORM_STRX3 | dwarf::DW_FORM_STRX4 => Attr::Strx(self.index(reader, form)?),
+            dwarf::DW_FORM_ADDRX | dwarf::DW_FORM_ADDRX1 | dwarf::DW_FORM_ADDRX2 |
+            dwarf::DW_FORM_ADDRX3 | dwarf::DW_FORM_ADDRX4 => Attr::Addrx(self.index(reader, form)?),
+            dwarf::DW_FORM_FLAG => Attr::Flag(reader.u8()? != 0),
+            dwarf::DW_FORM_FLAG_PRESENT => Attr::Flag(true),
+            dwarf::DW_FORM_REF1 => Attr::Ref(self.offset + reader.uint(1)? as usize),
+            dwarf::DW_FORM_REF2 => Attr::Ref(self.offset + reader.uint(2)? as usize),
+            dwarf::DW_FORM_REF4 => Attr::Ref(self.offset + reader.uint(4)? as usize),
+            dwarf::DW_FORM_REF8 => Attr::Ref(self.offset + reader.uint(8)? as usize),
+            dwarf::DW_FORM_REF_UDATA => Attr::Ref(self.offset + reader.uleb()? as usize),
+            dwarf::DW_FORM_REF_ADDR => {
+                /* DWARF 2 wrote these address sized */
+                let offset = if self.version <= 2 { reader.uint(self.addr_size)? } else { reader.offset(self.dwarf64)? };
+                Attr::Ref(offset as usize)
+            },
+            dwarf::DW_FORM_SEC_OFFSET => Attr::SecOffset(reader.offset(self.dwarf64)?),
+            dwarf::DW_FORM_STRP_SUP | dwarf::DW_FORM_REF_SUP4 => Attr::Udata(reader.offset(self.dwarf64)?),
+            dwarf::DW_FORM_REF_SIG8 | dwarf::DW_FORM_REF_SUP8 => Attr::Udata(reader.u64()?),
+            dwarf::DW_FORM_LOCLISTX | dwarf::DW_FORM_RNGLISTX => Attr::Udata(reader.uleb()?),
+            dwarf::DW_FORM_INDIRECT => {
+                let form = reader.uleb()?;
+                self.attr(reader, form, implicit)?
+            },
+            _ => { return Err("Unknown attribute form".into()); },
+        })
+    }
+
+    fn index(&self, reader: &mut Reader, form: u64) -> Result<u64, DebugError> {
+        match form {
+            dwarf::DW_FORM_STRX1 | dwarf::DW_FORM_ADDRX1 => reader.uint(1),
+            dwarf::DW_FORM_STRX2 | dwarf::DW_FORM_ADDRX2 => reader.uint(2),
+            dwarf::DW_FORM_STRX3 | dwarf::DW_FORM_ADDRX3 => reader.uint(3),
+            dwarf::DW_FORM_STRX4 | dwarf::DW_FORM_ADDRX4 => reader.uint(4),
+            _ => reader.uleb(),
+        }
+    }
+}
+
+/* abbreviation table at offset into .debug_abbrev */
+fn abbrevs(data: &[u8], offset: usize) -> Result<HashMap<u64, Abbrev>, DebugError> {
+
+    let mut reader = Reader::new(data, offset);
+    let mut abbrevs = HashMap::new();
+
+    loop {
+        let code = reader.uleb()?;
+
+        if code == 0 {
+            break;
+        }
+
+        let tag = reader.uleb()?;
+        let children = reader.u8()? != 0;
+        let mut specs = vec![];
+
+        loop {
+            let at = reader.uleb()?;
+            let form = reader.uleb()?;
+
+            if at == 0 && form == 0 {
+                break;
+            }
+
+            let implicit = if form == dwarf::DW_FORM_IMPLICIT_CONST { reader.sleb()? } else { 0 };
+            specs.push((at, form, implicit));
+        }
+
+        abbrevs.insert(code, Abbrev {
+            tag: tag,
+            children: children,
+            specs: specs,
+        });
+    }
+
+    Ok(abbrevs)
+}
+
+/*
+ *  DWARF expression stack machine, the subset compilers use for variable
+ *  locations without optimization
+ */
+pub fn evaluate(expr: &[u8], frame: &Frame, bias: u64, frame_base: Option<u64>)
+    -> Result<Place, DebugError>
+{
+    let mut reader = Reader::new(expr, 0);
+    let mut stack: Vec<u64> = vec![];
+
+    let reg = |number: u64| register(&frame.regs, number).ok_or("Unsupported DWARF register");
+
+    while !reader.is_empty() {
+        let op = reader.u8()?;
+
+        match op {
+            /* DW_OP_addr */
+            0x03 => { stack.push(reader.u64()?.wrapping_add(bias)); },
+            /* DW_OP_deref, not needed for anything read before the variable */
+            0x06 => { return Err("DW_OP_deref is not supported".into()); },
+            /* DW_OP_const1u .. const8s */
+            0x08 => { stack.push(reader.uint(1)?); },
+            0x09 => { stack.push(reader.uint(1)? as i8 as i64 as u64); },
+            0x0a => { stack.push(reader.uint(2)?); },
+            0x0b => { stack.push(reader.uint(2)? as i16 as i64 as u64); },
+            0x0c => { stack.push(reader.uint(4)?); },
+            0x0d => { stack.push(reader.uint(4)? as i32 as i64 as u64); },
+            0x0e | 0x0f => { stack.push(reader.u64()?); },
+            /* DW_OP_constu, consts */
+            0x10 => { stack.push(reader.uleb()?); },
+            0x11 => { stack.push(reader.sleb()? as u64); },
+            /* DW_OP_dup, drop */
+            0x12 => {
+                let top = *stack.last().ok_or("DWARF stack underflow")?;
+                stack.push(top);
+            },
+            0x13 => { stack.pop(); },
+            /* binary operators, PLT entries compute their CFA with these */
+            0x1a | 0x1c | 0x1e | 0x21 | 0x22 | 0x24..=0x27 | 0x29..=0x2e => {
+                let b = stack.pop().ok_or("DWARF stack underflow")?;
+                let a = stack.pop().ok_or("DWARF stack underflow")?;
+
+                stack.push(match op {
+                    0x1a => a & b,
+                    0x1c => a.wrapping_sub(b),
+                    0x1e => a.wrapping_mul(b),
+                    0x21 => a | b,
+                    0x22 => a.wrapping_add(b),
+                    0x24 => a.wrapping_shl(b as u32),
+                    0x25 => a.wrapping_shr(b as u32),
+                    0x26 => (a as i64).wrapping_shr(b as u32) as u64,
+                    0x27 => a ^ b,
+                    /* comparisons are signed */
+                    0x29 => (a == b) as u64,
+                    0x2a => (a as i64 >= b as i64) as u64,
+                    0x2b => (a as i64 > b as i64) as u64,
+                    0x2c => (a as i64 <= b as i64) as u64,
+                    0x2d => ((a as i64) < b as i64) as u64,
+                    _ => (a != b) as u64,
+                });
+            },
+            /* DW_OP_plus_uconst */
+            0x23 => {
+                let a = stack.pop().ok_or("DWARF stack underflow")?;
+                stack.push(a.wrapping_add(reader.uleb()?));
+            },
+            /* DW_OP_lit0 .. lit31 */
+            0x30..=0x4f => { stack.push((op - 0x30) as u64); },
+            /* DW_OP_reg0 .. reg31, the value is the register */
+            0x50..=0x6f => { return Ok(Place::Register((op - 0x50) as u64)); },
+            /* DW_OP_breg0 .. breg31 */
+            0x70..=0x8f => {
+                let offset = reader.sleb()?;
+                stack.push(reg((op - 0x70) as u64)?.wrapping_add(offset as u64));
+            },
+            /* DW_OP_regx */
+            0x90 => { return Ok(Place::Register(reader.uleb()?)); },
+            /* DW_OP_fbreg */
+            0x91 => {
+                let offset = reader.sleb()?;
+                let base = frame_base.ok_or("No frame base for a frame relative location")?;
+                stack.push(base.wrapping_add(offset as u64));
+            },
+            /* DW_OP_bregx */
+            0x92 => {
+                let number = reader.uleb()?;
+                let offset = reader.sleb()?;
+                stack.push(reg(number)?.wrapping_add(offset as u64));
+            },
+            /* DW_OP_call_frame_cfa */
+            0x9c => { stack.push(frame.cfa); },
+            /* DW_OP_stack_value */
+            0x9f => {
+                return Ok(Place::Value(stack.pop().ok_or("DWARF stack underflow")?));
+            },
+            _ => { return Err("Unsupported DWARF expression".into()); },
+        }
+    }
+
+    stack.pop()
+        .map(Place::Memory)
+        .ok_or("Empty DWARF expression".into())
+}
+
+fn uint(bytes: &[u8]) -> u64 {
+    bytes.iter().take(8).rev().fold(0, |value, &b| value << 8 | b as u64)
+}
+
+fn sint(bytes: &[u8]) -> i64 {
+    sign_extend(uint(bytes) as i64, bytes.len())
+}
+
+fn sign_extend(value: i64, len: usize) -> i64 {
+    if len == 0 || len >= 8 {
+        value
+    } else {
+        let shift = 64 - len * 8;
+        value << shift >> shift
+    }
+}
+
+fn base_value(encoding: u64, bytes: &[u8]) -> Value {
+    match encoding {
+        DW_ATE_BOOLEAN => Value::Bool(uint(bytes) != 0),
+        DW_ATE_FLOAT if bytes.len() == 4 => Value::Float(f32::from_bits(uint(bytes) as u32) as f64),
+        DW_ATE_FLOAT if bytes.len() == 8 => Value::Float(f64::from_bits(uint(bytes))),
+        DW_ATE_SIGNED_CHAR | DW_ATE_UNSIGNED_CHAR if bytes.len() == 1 => Value::Char(bytes[0]),
+        DW_ATE_SIGNED | DW_ATE_SIGNED_CHAR => Value::Int(sint(bytes)),
+        /* long double and friends */
+        DW_ATE_FLOAT => Value::Bytes(bytes.to_vec()),
+        _ => Value::Uint(uint(bytes)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::process;
+
+    /* tag, attributes and the index of the parent */
+    type Entry = (u64, Vec<(u64, Attr)>, Option<usize>);
+
+    /* DIEs at offsets equal to their index, children follow from the parents */
+    fn info(dies: Vec<Entry>) -> DebugInfo {
+
+        let mut built: Vec<Die> = dies.into_iter().enumerate()
+            .map(|(i, (tag, attrs, parent))| Die {
+                offset: i,
+                tag: tag,
+                attrs: attrs,
+                parent: parent,
+                children: vec![],
+            })
+            .collect();
+
+        for i in 0..built.len() {
+            if let Some(parent) = built[i].parent {
+                built[parent].children.push(i);
+            }
+        }
+
+        DebugInfo {
+            offsets: (0..built.len()).map(|i| (i, i)).collect(),
+            dies: built,
+            units: vec![0],
+        }
+    }
+
+    fn frame() -> Frame {
+        let mut regs = x86_64_Registers::default();
+        regs.rbp = 0x7ff0;
+        regs.rsp = 0x7fd0;
+
+        Frame { pc: 0x1000, regs: regs, cfa: 0x8000 }
+    }
+
+    fn memory() -> Memory {
+        Memory::load(process::id() as usize).unwrap()
+    }
+
+    #[test]
+    fn expressions() {
+        let frame = frame();
+
+        /* DW_OP_fbreg -20 */
+        assert_eq!(evaluate(&[0x91, 0x6c], &frame, 0, Some(0x8000)).unwrap(), Place::Memory(0x8000 - 20));
+        assert!(evaluate(&[0x91, 0x6c], &frame, 0, None).is_err());
+        /* DW_OP_breg6 16, rbp */
+        assert_eq!(evaluate(&[0x76, 0x10], &frame, 0, None).unwrap(), Place::Memory(0x7ff0 + 16));
+        /* DW_OP_bregx 7 -8, rsp */
+        assert_eq!(evaluate(&[0x92, 0x07, 0x78], &frame, 0, None).unwrap(), Place::Memory(0x7fd0 - 8));
+        /* DW_OP_addr moves with the bias */
+        assert_eq!(evaluate(&[0x03, 0x10, 0x40, 0, 0, 0, 0, 0, 0], &frame, 0x1000, None).unwrap(),
+            Place::Memory(0x5010));
+        /* DW_OP_lit1 DW_OP_lit2 DW_OP_plus DW_OP_stack_value */
+        assert_eq!(evaluate(&[0x31, 0x32, 0x22, 0x9f], &frame, 0, None).unwrap(), Place::Value(3));
+        /* DW_OP_reg3 */
+        assert_eq!(evaluate(&[0x53], &frame, 0, None).unwrap(), Place::Register(3));
+    }
+
+    #[test]
+    fn underflow() {
+        let frame = frame();
+
+        /* DW_OP_plus, DW_OP_stack_value and DW_OP_dup on too few entries */
+        assert!(evaluate(&[0x31, 0x22], &frame, 0, None).is_err());
+        assert!(evaluate(&[0x9f], &frame, 0, None).is_err());
+        assert!(evaluate(&[0x12], &frame, 0, None).is_err());
+        assert!(evaluate(&[0x31, 0x13], &frame, 0, None).is_err());
+    }
+
+    #[test]
+    fn frame_base() {
+        let info = info(vec![
+            (DW_TAG_COMPILE_UNIT, vec![], None),
+            /* DW_OP_call_frame_cfa */
+            (DW_TAG_SUBPROGRAM, vec![(DW_AT_FRAME_BASE, Attr::Block(vec![0x9c]))], Some(0)),
+            (DW_TAG_VARIABLE, vec![(DW_AT_LOCATION, Attr::Block(vec![0x91, 0x6c]))], Some(1)),
+        ]);
+
+        assert_eq!(info.location(&info.dies[2], &frame(), 0).unwrap(), Place::Memory(0x8000 - 20));
+    }
+
+    #[test]
+    fn bitfields() {
+        /* struct { int a:3; int b:5; unsigned c:4; int d:4; } = { -3, 7, 9, -8 } */
+        let field = |name: &str, ty: usize, bits: u64, offset: u64| (DW_TAG_MEMBER, vec![
+            (DW_AT_NAME, Attr::Str(name.to_string())),
+            (DW_AT_TYPE, Attr::Ref(ty)),
+            (DW_AT_BIT_SIZE, Attr::Udata(bits)),
+            (DW_AT_DATA_BIT_OFFSET, Attr::Udata(offset)),
+        ], Some(0));
+
+        let info = info(vec![
+            (DW_TAG_STRUCTURE_TYPE, vec![(DW_AT_BYTE_SIZE, Attr::Udata(4))], None),
+            (DW_TAG_BASE_TYPE, vec![(DW_AT_BYTE_SIZE, Attr::Udata(4)), (DW_AT_ENCODING, Attr::Udata(DW_ATE_SIGNED))], None),
+            /* DW_ATE_unsigned */
+            (DW_TAG_BASE_TYPE, vec![(DW_AT_BYTE_SIZE, Attr::Udata(4)), (DW_AT_ENCODING, Attr::Udata(0x07))], None),
+            field("a", 1, 3, 0),
+            field("b", 1, 5, 3),
+            field("c", 2, 4, 8),
+            field("d", 1, 4, 12),
+        ]);
+
+        let value = info.value(Some(&info.dies[0]), &[0x3d, 0x89, 0, 0], &mut memory()).unwrap();
+
+        assert_eq!(value, Value::Struct(vec![
+            ("a".to_string(), Value::Int(-3)),
+            ("b".to_string(), Value::Int(7)),
+            ("c".to_string(), Value::Uint(9)),
+            ("d".to_string(), Value::Int(-8)),
+        ]));
+    }
+
+    #[test]
+    fn nested_array() {
+        let info = info(vec![
+            (DW_TAG_ARRAY_TYPE, vec![(DW_AT_TYPE, Attr::Ref(1))], None),
+            (DW_TAG_BASE_TYPE, vec![
+                (DW_AT_NAME, Attr::Str("int".to_string())),
+                (DW_AT_BYTE_SIZE, Attr::Udata(4)),
+                (DW_AT_ENCODING, Attr::Udata(DW_ATE_SIGNED)),
+            ], None),
+            (DW_TAG_SUBRANGE_TYPE, vec![(DW_AT_UPPER_BOUND, Attr::Udata(1))], Some(0)),
+            (DW_TAG_SUBRANGE_TYPE, vec![(DW_AT_COUNT, Attr::Udata(3))], Some(0)),
+        ]);
+
+        let bytes: Vec<u8> = [1i32, 2, 3, 4, 5, -6].iter()
+            .flat_map(|&v| (0..4).map(move |i| (v >> (i * 8)) as u8))
+            .collect();
+
+        let ty = Some(&info.dies[0]);
+        let row = |values: &[i64]| Value::Array(values.iter().map(|&v| Value::Int(v)).collect(), false);
+
+        assert_eq!(info.size(ty), Some(24));
+        assert_eq!(info.type_name(ty), "int [2][3]");
+        assert_eq!(info.value(ty, &bytes, &mut memory()).unwrap(),
+            Value::Array(vec![row(&[1, 2, 3]), row(&[4, 5, -6])], false));
+    }
+
+    #[test]
+    fn enum_sign_extension() {
+        /* constants come as data1 / data4, unsigned until widened to the enum's size */
+        let info = info(vec![
+            (DW_TAG_ENUMERATION_TYPE, vec![(DW_AT_BYTE_SIZE, Attr::Udata(1))], None),
+            (DW_TAG_ENUMERATOR, vec![(DW_AT_NAME, Attr::Str("NEG".to_string())), (DW_AT_CONST_VALUE, Attr::Udata(0xff))], Some(0)),
+            (DW_TAG_ENUMERATOR, vec![(DW_AT_NAME, Attr::Str("POS".to_string())), (DW_AT_CONST_VALUE, Attr::Udata(1))], Some(0)),
+            (DW_TAG_ENUMERATION_TYPE, vec![(DW_AT_BYTE_SIZE, Attr::Udata(4))], None),
+            (DW_TAG_ENUMERATOR, vec![(DW_AT_NAME, Attr::Str("LOW".to_string())), (DW_AT_CONST_VALUE, Attr::Udata(0xfffffffe))], Some(3)),
+            (DW_TAG_ENUMERATOR, vec![(DW_AT_NAME, Attr::Str("MIN".to_string())), (DW_AT_CONST_VALUE, Attr::Sdata(-3))], Some(3)),
+        ]);
+
+        let mut memory = memory();
+        let small = Some(&info.dies[0]);
+        let wide = Some(&info.dies[3]);
+
+        assert_eq!(info.value(small, &[0xff], &mut memory).unwrap(),
+            Value::Enum { value: -1, name: Some("NEG".to_string()) });
+        assert_eq!(info.value(small, &[0x01], &mut memory).unwrap(),
+            Value::Enum { value: 1, name: Some("POS".to_string()) });
+        assert_eq!(info.value(small, &[0xfe], &mut memory).unwrap(),
+            Value::Enum { value: -2, name: None });
+        assert_eq!(info.value(wide, &[0xfe, 0xff, 0xff, 0xff], &mut memory).unwrap(),
+            Value::Enum { value: -2, name: Some("LOW".to_string()) });
+        assert_eq!(info.value(wide, &[0xfd, 0xff, 0xff, 0xff], &mut memory).unwrap(),
+            Value::Enum { value: -3, name: Some("MIN".to_string()) });
+    }
+}
@@ -0,0 +1,2053 @@
+use std::fmt;
+
+/*
+ *  x86_64 decoder, table driven like the opcode maps in the Intel manual.
+ *  Operand specs use the manual's notation, e.g. "Ev,Gv" is a modrm r/m
+ *  operand of operand size followed by the modrm register:
+ *
+ *      E  modrm r/m, register or memory    G  modrm reg field
+ *      M  modrm r/m, memory only           R  modrm r/m, register only
+ *      I  immediate                        J  branch target
+ *      Z  register in the low opcode bits  O  absolute address (moffs)
+ *      V  xmm in reg, W xmm or memory in r/m, U xmm in r/m, H xmm in vvvv
+ *      P  mm in reg, Q mm or memory in r/m, N mm in r/m (xmm with 66)
+ *      B  general register in vvvv         K  mask register, KG/KE/KH
+ *
+ *  and sizes b byte, w word, d dword, q qword, v operand size, y dword or
+ *  qword, z word or dword immediate, s byte sign extended, o 16 bytes,
+ *  x vector length, t 10 bytes. Every opcode decodes to the right length,
+ *  the ones without a name come out as (bad).
+ */
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Syntax {
+    Intel,
+    Att,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Mem {
+    /* in bytes, 0 where it doesn't matter, e.g. lea */
+    pub size: usize,
+    pub seg: Option<&'static str>,
+    pub base: Option<&'static str>,
+    pub index: Option<(&'static str, u8)>,
+    pub disp: i64,
+    /* rip relative, the address disp resolves to */
+    pub target: Option<u64>,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum Operand {
+    Reg(&'static str),
+    /* value and size in bytes */
+    Imm(u64, usize),
+    /* branch target */
+    Rel(u64),
+    Mem(Mem),
+}
+
+#[derive(Debug,Clone)]
+pub struct Instruction {
+    pub addr: u64,
+    pub bytes: Vec<u8>,
+    pub mnemonic: String,
+    pub operands: Vec<Operand>,
+    /* lock, rep, repz, repnz, bnd */
+    pub prefix: Option<&'static str>,
+    /* write mask of an evex destination and whether it zeroes */
+    pub mask: Option<(&'static str, bool)>,
+}
+
+impl Instruction {
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /* address of the next instruction */
+    pub fn next(&self) -> u64 {
+        self.addr + self.len() as u64
+    }
+
+    pub fn is_call(&self) -> bool {
+        self.mnemonic == "call"
+    }
+
+    /* repeated string instruction, one step runs a single iteration */
+    pub fn is_rep(&self) -> bool {
+        match self.prefix {
+            Some("rep") | Some("repz") | Some("repnz") => true,
+            _ => false,
+        }
+    }
+
+    /* where a direct jump or call goes */
+    pub fn target(&self) -> Option<u64> {
+        match self.operands.first() {
+            Some(&Operand::Rel(target)) => Some(target),
+            _ => None,
+        }
+    }
+
+    pub fn format(&self, syntax: Syntax) -> String {
+        match syntax {
+            Syntax::Intel => self.intel(),
+            Syntax::Att => self.att(),
+        }
+    }
+
+    fn intel(&self) -> String {
+
+        let mut text = String::new();
+
+        if let Some(prefix) = self.prefix {
+            text.push_str(prefix);
+            text.push(' ');
+        }
+
+        text.push_str(&self.mnemonic);
+
+        let operands: Vec<String> = self.operands.iter().enumerate()
+            .map(|(i, op)| {
+                let op = intel_operand(op);
+                if i == 0 { op + &self.mask_text("") } else { op }
+            })
+            .collect();
+
+        if !operands.is_empty() {
+            text = format!("{:<6} {}", text, operands.join(", "));
+        }
+
+        self.comment(text)
+    }
+
+    fn att(&self) -> String {
+
+        let mut text = String::new();
+
+        if let Some(prefix) = self.prefix {
+            text.push_str(prefix);
+            text.push(' ');
+        }
+
+        text.push_str(&att_mnemonic(self));
+
+        /* branches through registers or memory are starred */
+        let indirect = (self.mnemonic == "call" || self.mnemonic == "jmp") && self.target().is_none();
+
+        let mut operands: Vec<String> = self.operands.iter().enumerate()
+            .map(|(i, op)| {
+                let op = att_operand(op);
+                if i == 0 { op + &self.mask_text("%") } else { op }
+            })
+            .collect();
+
+        operands.reverse();
+
+        if !operands.is_empty() {
+            text = format!("{:<6} {}{}", text, if indirect { "*" } else { "" }, operands.join(","));
+        }
+
+        self.comment(text)
+    }
+
+    /* {k1}{z}, the register takes the syntax' sigil */
+    fn mask_text(&self, sigil: &str) -> String {
+        match self.mask {
+            Some((mask, zero)) => format!("{{{}{}}}{}", sigil, mask, if zero { "{z}" } else { "" }),
+            None => String::new(),
+        }
+    }
+
+    /* rip relative addresses are spelled out */
+    fn comment(&self, text: String) -> String {
+        for op in self.operands.iter() {
+            if let &Operand::Mem(Mem { target: Some(target), .. }) = op {
+                return format!("{}  # 0x{:x}", text, target);
+            }
+        }
+
+        text
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.intel())
+    }
+}
+
+/* decode the instruction at the start of code, None if code runs out first */
+pub fn decode(code: &[u8], addr: u64) -> Option<Instruction> {
+
+    let mut decoder = Decoder::new(code, addr);
+
+    match decoder.decode() {
+        Some(insn) => Some(insn),
+        /* ran out of bytes, or past the 15 byte limit */
+        None if code.len() >= MAX_LEN => Some(bad(code, addr)),
+        None => None,
+    }
+}
+
+/* at most count instructions, fewer if code runs out */
+pub fn disassemble(code: &[u8], addr: u64, count: usize) -> Vec<Instruction> {
+
+    let mut insns = vec![];
+    let mut offset = 0;
+
+    while insns.len() < count && offset < code.len() {
+        match decode(&code[offset..], addr + offset as u64) {
+            Some(insn) => {
+                offset += insn.len();
+                insns.push(insn);
+            },
+            None => { break; },
+        }
+    }
+
+    insns
+}
+
+pub const MAX_LEN: usize = 15;
+
+fn bad(code: &[u8], addr: u64) -> Instruction {
+    Instruction {
+        addr: addr,
+        bytes: code[..1].to_vec(),
+        mnemonic: "(bad)".to_string(),
+        operands: vec![],
+        prefix: None,
+        mask: None,
+    }
+}
+
+fn intel_operand(op: &Operand) -> String {
+    match op {
+        &Operand::Reg(reg) => reg.to_string(),
+        &Operand::Imm(value, _) => format!("0x{:x}", value),
+        &Operand::Rel(target) => format!("0x{:x}", target),
+        &Operand::Mem(ref mem) => {
+
+            let mut addr = String::new();
+
+            if let Some(base) = mem.base {
+                addr.push_str(base);
+            }
+
+            if let Some((index, scale)) = mem.index {
+                if !addr.is_empty() {
+                    addr.push('+');
+                }
+                addr.push_str(&format!("{}*{}", index, scale));
+            }
+
+            if addr.is_empty() {
+                addr = format!("0x{:x}", mem.disp as u64);
+            } else if mem.disp < 0 {
+                addr.push_str(&format!("-0x{:x}", (mem.disp as i128).abs()));
+            } else if mem.disp > 0 {
+                addr.push_str(&format!("+0x{:x}", mem.disp));
+            }
+
+            let seg = mem.seg.map_or(String::new(), |seg| format!("{}:", seg));
+
+            match size_name(mem.size) {
+                Some(size) => format!("{} ptr {}[{}]", size, seg, addr),
+                None => format!("{}[{}]", seg, addr),
+            }
+        },
+    }
+}
+
+fn att_operand(op: &Operand) -> String {
+    match op {
+        &Operand::Reg(reg) => format!("%{}", reg),
+        &Operand::Imm(value, _) => format!("$0x{:x}", value),
+        &Operand::Rel(target) => format!("0x{:x}", target),
+        &Operand::Mem(ref mem) => {
+
+            let mut text = mem.seg.map_or(String::new(), |seg| format!("%{}:", seg));
+
+            if mem.disp < 0 {
+                text.push_str(&format!("-0x{:x}", (mem.disp as i128).abs()));
+            } else if mem.disp > 0 || (mem.base.is_none() && mem.index.is_none()) {
+                text.push_str(&format!("0x{:x}", mem.disp));
+            }
+
+            match (mem.base, mem.index) {
+                (Some(base), Some((index, scale))) => text.push_str(&format!("(%{},%{},{})", base, index, scale)),
+                (None, Some((index, scale))) => text.push_str(&format!("(,%{},{})", index, scale)),
+                (Some(base), None) => text.push_str(&format!("(%{})", base)),
+                (None, None) => {},
+            }
+
+            text
+        },
+    }
+}
+
+/* intel names, fixed up where at&t spells them differently */
+fn att_mnemonic(insn: &Instruction) -> String {
+
+    let sizes: Vec<usize> = insn.operands.iter().filter_map(|op| match op {
+        &Operand::Reg(reg) => Some(reg_size(reg)),
+        _ => None,
+    }).collect();
+
+    let suffix = |size: usize| match size {
+        1 => "b",
+        2 => "w",
+        4 => "l",
+        8 => "q",
+        _ => "",
+    };
+
+    let mem_size = insn.operands.iter().filter_map(|op| match op {
+        &Operand::Mem(ref mem) => Some(mem.size),
+        _ => None,
+    }).next();
+
+    match insn.mnemonic.as_str() {
+        "cbw" => return "cbtw".to_string(),
+        "cwde" => return "cwtl".to_string(),
+        "cdqe" => return "cltq".to_string(),
+        "cwd" => return "cwtd".to_string(),
+        "cdq" => return "cltd".to_string(),
+        "cqo" => return "cqto".to_string(),
+        "movsxd" => return format!("movs{}q", suffix(mem_size.unwrap_or(4))),
+        "movzx" | "movsx" => {
+            /* source size comes first, the destination is always a register */
+            let src = mem_size.unwrap_or(*sizes.last().unwrap_or(&1));
+            let dst = *sizes.first().unwrap_or(&4);
+            return format!("{}{}{}", &insn.mnemonic[..4], suffix(src), suffix(dst));
+        },
+        "movabs" => return "movabs".to_string(),
+        _ => {},
+    }
+
+    let x87 = insn.mnemonic.starts_with("f");
+
+    /*
+     *  the at&t assemblers swap the reversed forms when the destination is
+     *  st(i), which objdump and gdb keep doing for compatibility
+     */
+    if x87 && insn.operands.len() == 2 && insn.operands[1] == Operand::Reg("st") {
+        match insn.mnemonic.as_str() {
+            "fsub" => return "fsubr".to_string(),
+            "fsubr" => return "fsub".to_string(),
+            "fsubp" => return "fsubrp".to_string(),
+            "fsubrp" => return "fsubp".to_string(),
+            "fdiv" => return "fdivr".to_string(),
+            "fdivr" => return "fdiv".to_string(),
+            "fdivp" => return "fdivrp".to_string(),
+            "fdivrp" => return "fdivp".to_string(),
+            _ => {},
+        }
+    }
+
+    /* the size is implied by the instruction */
+    let implied = ["set", "prefetch", "call", "jmp", "push", "pop", "ldmxcsr", "stmxcsr", "v"].iter()
+        .any(|prefix| insn.mnemonic.starts_with(prefix));
+
+    /* nothing else tells the size, e.g. movl $0x0,-0x4(%rbp) */
+    match mem_size {
+        Some(size) if x87 && size > 0 => {
+            let integer = insn.mnemonic.starts_with("fi");
+
+            let suffix = match (integer, size) {
+                (true, 2) => "s",
+                (true, 4) => "l",
+                (true, 8) => "ll",
+                (false, 4) => "s",
+                (false, 8) => "l",
+                (false, 10) => "t",
+                _ => "",
+            };
+
+            format!("{}{}", insn.mnemonic, suffix)
+        },
+        Some(size) if sizes.is_empty() && size <= 8 && !implied => {
+            format!("{}{}", insn.mnemonic, suffix(size))
+        },
+        _ => insn.mnemonic.clone(),
+    }
+}
+
+fn size_name(size: usize) -> Option<&'static str> {
+    match size {
+        1 => Some("byte"),
+        2 => Some("word"),
+        4 => Some("dword"),
+        8 => Some("qword"),
+        10 => Some("tbyte"),
+        16 => Some("xmmword"),
+        32 => Some("ymmword"),
+        64 => Some("zmmword"),
+        _ => None,
+    }
+}
+
+static REG8: [&'static str; 16] = [
+    "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil",
+    "r8b", "r9b", "r10b", "r11b", "r12b", "r13b", "r14b", "r15b",
+];
+static REG8_LEGACY: [&'static str; 8] = ["al", "cl", "dl", "bl", "ah", "ch", "dh", "bh"];
+static REG16: [&'static str; 16] = [
+    "ax", "cx", "dx", "bx", "sp", "bp", "si", "di",
+    "r8w", "r9w", "r10w", "r11w", "r12w", "r13w", "r14w", "r15w",
+];
+static REG32: [&'static str; 16] = [
+    "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi",
+    "r8d", "r9d", "r10d", "r11d", "r12d", "r13d", "r14d", "r15d",
+];
+static REG64: [&'static str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+];
+static SEG: [&'static str; 8] = ["es", "cs", "ss", "ds", "fs", "gs", "?", "?"];
+static XMM: [&'static str; 32] = [
+    "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7",
+    "xmm8", "xmm9", "xmm10", "xmm11", "xmm12", "xmm13", "xmm14", "xmm15",
+    "xmm16", "xmm17", "xmm18", "xmm19", "xmm20", "xmm21", "xmm22", "xmm23",
+    "xmm24", "xmm25", "xmm26", "xmm27", "xmm28", "xmm29", "xmm30", "xmm31",
+];
+static YMM: [&'static str; 32] = [
+    "ymm0", "ymm1", "ymm2", "ymm3", "ymm4", "ymm5", "ymm6", "ymm7",
+    "ymm8", "ymm9", "ymm10", "ymm11", "ymm12", "ymm13", "ymm14", "ymm15",
+    "ymm16", "ymm17", "ymm18", "ymm19", "ymm20", "ymm21", "ymm22", "ymm23",
+    "ymm24", "ymm25", "ymm26", "ymm27", "ymm28", "ymm29", "ymm30", "ymm31",
+];
+static ZMM: [&'static str; 32] = [
+    "zmm0", "zmm1", "zmm2", "zmm3", "zmm4", "zmm5", "zmm6", "zmm7",
+    "zmm8", "zmm9", "zmm10", "zmm11", "zmm12", "zmm13", "zmm14", "zmm15",
+    "zmm16", "zmm17", "zmm18", "zmm19", "zmm20", "zmm21", "zmm22", "zmm23",
+    "zmm24", "zmm25", "zmm26", "zmm27", "zmm28", "zmm29", "zmm30", "zmm31",
+];
+static MM: [&'static str; 8] = ["mm0", "mm1", "mm2", "mm3", "mm4", "mm5", "mm6", "mm7"];
+static ST: [&'static str; 8] = ["st(0)", "st(1)", "st(2)", "st(3)", "st(4)", "st(5)", "st(6)", "st(7)"];
+static MASK: [&'static str; 8] = ["k0", "k1", "k2", "k3", "k4", "k5", "k6", "k7"];
+static CR: [&'static str; 16] = [
+    "cr0", "cr1", "cr2", "cr3", "cr4", "cr5", "cr6", "cr7",
+    "cr8", "cr9", "cr10", "cr11", "cr12", "cr13", "cr14", "cr15",
+];
+static DR: [&'static str; 16] = [
+    "dr0", "dr1", "dr2", "dr3", "dr4", "dr5", "dr6", "dr7",
+    "dr8", "dr9", "dr10", "dr11", "dr12", "dr13", "dr14", "dr15",
+];
+
+fn reg_size(reg: &str) -> usize {
+    if REG64.contains(&reg) || reg == "rip" {
+        8
+    } else if REG32.contains(&reg) {
+        4
+    } else if REG16.contains(&reg) {
+        2
+    } else if REG8.contains(&reg) || REG8_LEGACY.contains(&reg) {
+        1
+    } else {
+        /* vector, x87 and the rest don't take a suffix */
+        0
+    }
+}
+
+static ALU: [&'static str; 8] = ["add", "or", "adc", "sbb", "and", "sub", "xor", "cmp"];
+static SHIFT: [&'static str; 8] = ["rol", "ror", "rcl", "rcr", "shl", "shr", "shl", "sar"];
+
+/* mnemonic and operand spec, `#` names are resolved through modrm.reg */
+type Entry = (&'static str, &'static str);
+
+fn one_byte(op: u8) -> Entry {
+
+    static ALU_SPEC: [&'static str; 6] = ["Eb,Gb", "Ev,Gv", "Gb,Eb", "Gv,Ev", "AL,Ib", "rAX,Iz"];
+
+    match op {
+        0x00..=0x3f if op & 7 < 6 => (ALU[(op >> 3) as usize], ALU_SPEC[(op & 7) as usize]),
+        0x50..=0x57 => ("push", "Zv"),
+        0x58..=0x5f => ("pop", "Zv"),
+        0x63 => ("movsxd", "Gv,Ed"),
+        0x68 => ("push", "Iz"),
+        0x69 => ("imul", "Gv,Ev,Iz"),
+        0x6a => ("push", "Is"),
+        0x6b => ("imul", "Gv,Ev,Is"),
+        0x6c => ("insb", ""),
+        0x6d => ("insw/insd/insd", ""),
+        0x6e => ("outsb", ""),
+        0x6f => ("outsw/outsd/outsd", ""),
+        0x70..=0x7f => (JCC[(op & 0xf) as usize], "Jb"),
+        0x80 => ("#1", "Eb,Ib"),
+        0x81 => ("#1", "Ev,Iz"),
+        0x83 => ("#1", "Ev,Is"),
+        0x84 => ("test", "Eb,Gb"),
+        0x85 => ("test", "Ev,Gv"),
+        0x86 => ("xchg", "Eb,Gb"),
+        0x87 => ("xchg", "Ev,Gv"),
+        0x88 => ("mov", "Eb,Gb"),
+        0x89 => ("mov", "Ev,Gv"),
+        0x8a => ("mov", "Gb,Eb"),
+        0x8b => ("mov", "Gv,Ev"),
+        0x8c => ("mov", "Ev,Sw"),
+        0x8d => ("lea", "Gv,M"),
+        0x8e => ("mov", "Sw,Ew"),
+        0x8f => ("#1a", "Eq"),
+        0x90 => ("nop", ""),
+        0x91..=0x97 => ("xchg", "Zv,rAX"),
+        0x98 => ("cbw/cwde/cdqe", ""),
+        0x99 => ("cwd/cdq/cqo", ""),
+        0x9b => ("fwait", ""),
+        0x9c => ("pushf", ""),
+        0x9d => ("popf", ""),
+        0x9e => ("sahf", ""),
+        0x9f => ("lahf", ""),
+        0xa0 => ("movabs", "AL,Ob"),
+        0xa1 => ("movabs", "rAX,Ov"),
+        0xa2 => ("movabs", "Ob,AL"),
+        0xa3 => ("movabs", "Ov,rAX"),
+        0xa4 => ("movsb", ""),
+        0xa5 => ("movsw/movsd/movsq", ""),
+        0xa6 => ("cmpsb", ""),
+        0xa7 => ("cmpsw/cmpsd/cmpsq", ""),
+        0xa8 => ("test", "AL,Ib"),
+        0xa9 => ("test", "rAX,Iz"),
+        0xaa => ("stosb", ""),
+        0xab => ("stosw/stosd/stosq", ""),
+        0xac => ("lodsb", ""),
+        0xad => ("lodsw/lodsd/lodsq", ""),
+        0xae => ("scasb", ""),
+        0xaf => ("scasw/scasd/scasq", ""),
+        0xb0..=0xb7 => ("mov", "Zb,Ib"),
+        0xb8..=0xbf => ("mov", "Zv,Iv"),
+        0xc0 => ("#2", "Eb,Ib"),
+        0xc1 => ("#2", "Ev,Ib"),
+        0xc2 => ("ret", "Iw"),
+        0xc3 => ("ret", ""),
+        0xc6 => ("#11", "Eb,Ib"),
+        0xc7 => ("#11", "Ev,Iz"),
+        0xc8 => ("enter", "Iw,Ib"),
+        0xc9 => ("leave", ""),
+        0xca => ("retf", "Iw"),
+        0xcb => ("retf", ""),
+        0xcc => ("int3", ""),
+        0xcd => ("int", "Ib"),
+        0xcf => ("iretw/iret/iretq", ""),
+        0xd0 => ("#2", "Eb,1"),
+        0xd1 => ("#2", "Ev,1"),
+        0xd2 => ("#2", "Eb,CL"),
+        0xd3 => ("#2", "Ev,CL"),
+        0xd7 => ("xlat", ""),
+        0xd8..=0xdf => ("#x87", "E"),
+        0xe0 => ("loopne", "Jb"),
+        0xe1 => ("loope", "Jb"),
+        0xe2 => ("loop", "Jb"),
+        0xe3 => ("jrcxz", "Jb"),
+        0xe4 => ("in", "AL,Ib"),
+        0xe5 => ("in", "eAX,Ib"),
+        0xe6 => ("out", "Ib,AL"),
+        0xe7 => ("out", "Ib,eAX"),
+        0xe8 => ("call", "Jz"),
+        0xe9 => ("jmp", "Jz"),
+        0xeb => ("jmp", "Jb"),
+        0xec => ("in", "AL,DX"),
+        0xed => ("in", "eAX,DX"),
+        0xee => ("out", "DX,AL"),
+        0xef => ("out", "DX,eAX"),
+        0xf1 => ("int1", ""),
+        0xf4 => ("hlt", ""),
+        0xf5 => ("cmc", ""),
+        0xf6 => ("#3", "Eb"),
+        0xf7 => ("#3", "Ev"),
+        0xf8 => ("clc", ""),
+        0xf9 => ("stc", ""),
+        0xfa => ("cli", ""),
+        0xfb => ("sti", ""),
+        0xfc => ("cld", ""),
+        0xfd => ("std", ""),
+        0xfe => ("#4", "Eb"),
+        0xff => ("#5", "Ev"),
+        /* gone in 64 bit mode */
+        _ => ("(bad)", ""),
+    }
+}
+
+static JCC: [&'static str; 16] = [
+    "jo", "jno", "jb", "jae", "je", "jne", "jbe", "ja",
+    "js", "jns", "jp", "jnp", "jl", "jge", "jle", "jg",
+];
+
+/* `|` separates the forms for no prefix, 66, f3 and f2 */
+fn two_byte(op: u8) -> Entry {
+    match op {
+        0x00 => ("#6", "Ew"),
+        0x01 => ("#7", "Ev"),
+        0x02 => ("lar", "Gv,Ew"),
+        0x03 => ("lsl", "Gv,Ew"),
+        0x05 => ("syscall", ""),
+        0x06 => ("clts", ""),
+        0x07 => ("sysret", ""),
+        0x08 => ("invd", ""),
+        0x09 => ("wbinvd", ""),
+        0x0b => ("ud2", ""),
+        0x0d => ("#p", "M"),
+        0x10 => ("movups|movupd|movss|movsd", "V,W|V,W|V,Wd|V,Wq"),
+        0x11 => ("movups|movupd|movss|movsd", "W,V|W,V|Wd,V|Wq,V"),
+        0x12 => ("#12", "V,Wq"),
+        0x13 => ("movlps|movlpd||", "Mq,V"),
+        0x14 => ("unpcklps|unpcklpd||", "V,W"),
+        0x15 => ("unpckhps|unpckhpd||", "V,W"),
+        0x16 => ("#16", "V,Wq"),
+        0x17 => ("movhps|movhpd||", "Mq,V"),
+        0x18 => ("#16p", "M"),
+        0x19..=0x1d => ("nop", "Ev"),
+        0x1e => ("#1e", "Ev"),
+        0x1f => ("nop", "Ev"),
+        0x20 => ("mov", "Rq,Cq"),
+        0x21 => ("mov", "Rq,Dq"),
+        0x22 => ("mov", "Cq,Rq"),
+        0x23 => ("mov", "Dq,Rq"),
+        0x28 => ("movaps|movapd||", "V,W"),
+        0x29 => ("movaps|movapd||", "W,V"),
+        0x2a => ("cvtpi2ps|cvtpi2pd|cvtsi2ss|cvtsi2sd", "V,Q|V,Q|V,Ey|V,Ey"),
+        0x2b => ("movntps|movntpd||", "Mx,V"),
+        0x2c => ("cvttps2pi|cvttpd2pi|cvttss2si|cvttsd2si", "P,Wq|P,W|Gy,Wd|Gy,Wq"),
+        0x2d => ("cvtps2pi|cvtpd2pi|cvtss2si|cvtsd2si", "P,Wq|P,W|Gy,Wd|Gy,Wq"),
+        0x2e => ("ucomiss|ucomisd||", "V,Wd|V,Wq"),
+        0x2f => ("comiss|comisd||", "V,Wd|V,Wq"),
+        0x30 => ("wrmsr", ""),
+        0x31 => ("rdtsc", ""),
+        0x32 => ("rdmsr", ""),
+        0x33 => ("rdpmc", ""),
+        0x34 => ("sysenter", ""),
+        0x35 => ("sysexit", ""),
+        0x37 => ("getsec", ""),
+        0x40..=0x4f => (CMOV[(op & 0xf) as usize], "Gv,Ev"),
+        0x50 => ("movmskps|movmskpd||", "Gd,U"),
+        0x51 => ("sqrtps|sqrtpd|sqrtss|sqrtsd", "V,W|V,W|V,Wd|V,Wq"),
+        0x52 => ("rsqrtps||rsqrtss|", "V,W|V,W|V,Wd|V,W"),
+        0x53 => ("rcpps||rcpss|", "V,W|V,W|V,Wd|V,W"),
+        0x54 => ("andps|andpd||", "V,W"),
+        0x55 => ("andnps|andnpd||", "V,W"),
+        0x56 => ("orps|orpd||", "V,W"),
+        0x57 => ("xorps|xorpd||", "V,W"),
+        0x58 => ("addps|addpd|addss|addsd", "V,W|V,W|V,Wd|V,Wq"),
+        0x59 => ("mulps|mulpd|mulss|mulsd", "V,W|V,W|V,Wd|V,Wq"),
+        0x5a => ("cvtps2pd|cvtpd2ps|cvtss2sd|cvtsd2ss", "V,Wq|V,W|V,Wd|V,Wq"),
+        0x5b => ("cvtdq2ps|cvtps2dq|cvttps2dq|", "V,W"),
+        0x5c => ("subps|subpd|subss|subsd", "V,W|V,W|V,Wd|V,Wq"),
+        0x5d => ("minps|minpd|minss|minsd", "V,W|V,W|V,Wd|V,Wq"),
+        0x5e => ("divps|divpd|divss|divsd", "V,W|V,W|V,Wd|V,Wq"),
+        0x5f => ("maxps|maxpd|maxss|maxsd", "V,W|V,W|V,Wd|V,Wq"),
+        0x60 => ("punpcklbw|punpcklbw||", "P,Q"),
+        0x61 => ("punpcklwd|punpcklwd||", "P,Q"),
+        0x62 => ("punpckldq|punpckldq||", "P,Q"),
+        0x63 => ("packsswb|packsswb||", "P,Q"),
+        0x64 => ("pcmpgtb|pcmpgtb||", "P,Q"),
+        0x65 => ("pcmpgtw|pcmpgtw||", "P,Q"),
+        0x66 => ("pcmpgtd|pcmpgtd||", "P,Q"),
+        0x67 => ("packuswb|packuswb||", "P,Q"),
+        0x68 => ("punpckhbw|punpckhbw||", "P,Q"),
+        0x69 => ("punpckhwd|punpckhwd||", "P,Q"),
+        0x6a => ("punpckhdq|punpckhdq||", "P,Q"),
+        0x6b => ("packssdw|packssdw||", "P,Q"),
+        0x6c => ("|punpcklqdq||", "V,W"),
+        0x6d => ("|punpckhqdq||", "V,W"),
+        0x6e => ("movd/movq|movd/movq||", "P,Ey"),
+        0x6f => ("movq|movdqa|movdqu|", "P,Q|V,W|V,W|V,W"),
+        0x70 => ("pshufw|pshufd|pshufhw|pshuflw", "P,Q,Ib|V,W,Ib|V,W,Ib|V,W,Ib"),
+        0x71 => ("#12s", "N,Ib"),
+        0x72 => ("#13s", "N,Ib"),
+        0x73 => ("#14s", "N,Ib"),
+        0x74 => ("pcmpeqb|pcmpeqb||", "P,Q"),
+        0x75 => ("pcmpeqw|pcmpeqw||", "P,Q"),
+        0x76 => ("pcmpeqd|pcmpeqd||", "P,Q"),
+        0x77 => ("emms", ""),
+        0x7c => ("|haddpd||haddps", "V,W"),
+        0x7d => ("|hsubpd||hsubps", "V,W"),
+        0x7e => ("movd/movq|movd/movq|movq|", "Ey,P|Ey,P|V,Wq|V,W"),
+        0x7f => ("movq|movdqa|movdqu|", "Q,P|W,V|W,V|W,V"),
+        0x80..=0x8f => (JCC[(op & 0xf) as usize], "Jz"),
+        0x90..=0x9f => (SETCC[(op & 0xf) as usize], "Eb"),
+        0xa0 => ("push fs", ""),
+        0xa1 => ("pop fs", ""),
+        0xa2 => ("cpuid", ""),
+        0xa3 => ("bt", "Ev,Gv"),
+        0xa4 => ("shld", "Ev,Gv,Ib"),
+        0xa5 => ("shld", "Ev,Gv,CL"),
+        0xa8 => ("push gs", ""),
+        0xa9 => ("pop gs", ""),
+        0xaa => ("rsm", ""),
+        0xab => ("bts", "Ev,Gv"),
+        0xac => ("shrd", "Ev,Gv,Ib"),
+        0xad => ("shrd", "Ev,Gv,CL"),
+        0xae => ("#15", "E"),
+        0xaf => ("imul", "Gv,Ev"),
+        0xb0 => ("cmpxchg", "Eb,Gb"),
+        0xb1 => ("cmpxchg", "Ev,Gv"),
+        0xb2 => ("lss", "Gv,M"),
+        0xb3 => ("btr", "Ev,Gv"),
+        0xb4 => ("lfs", "Gv,M"),
+        0xb5 => ("lgs", "Gv,M"),
+        0xb6 => ("movzx", "Gv,Eb"),
+        0xb7 => ("movzx", "Gv,Ew"),
+        0xb8 => ("||popcnt|", "Gv,Ev"),
+        0xb9 => ("ud1", "Gv,Ev"),
+        0xba => ("#8", "Ev,Ib"),
+        0xbb => ("btc", "Ev,Gv"),
+        0xbc => ("bsf|bsf|tzcnt|bsf", "Gv,Ev"),
+        0xbd => ("bsr|bsr|lzcnt|bsr", "Gv,Ev"),
+        0xbe => ("movsx", "Gv,Eb"),
+        0xbf => ("movsx", "Gv,Ew"),
+        0xc0 => ("xadd", "Eb,Gb"),
+        0xc1 => ("xadd", "Ev,Gv"),
+        0xc2 => ("cmpps|cmppd|cmpss|cmpsd", "V,W,Ib|V,W,Ib|V,Wd,Ib|V,Wq,Ib"),
+        0xc3 => ("movnti", "My,Gy"),
+        0xc4 => ("pinsrw|pinsrw||", "P,Ew,Ib"),
+        0xc5 => ("pextrw|pextrw||", "Gd,N,Ib"),
+        0xc6 => ("shufps|shufpd||", "V,W,Ib"),
+        0xc7 => ("#9", "E"),
+        0xc8..=0xcf => ("bswap", "Zv"),
+        0xd0 => ("|addsubpd||addsubps", "V,W"),
+        0xd1 => ("psrlw|psrlw||", "P,Q"),
+        0xd2 => ("psrld|psrld||", "P,Q"),
+        0xd3 => ("psrlq|psrlq||", "P,Q"),
+        0xd4 => ("paddq|paddq||", "P,Q"),
+        0xd5 => ("pmullw|pmullw||", "P,Q"),
+        0xd6 => ("|movq||", "Wq,V"),
+        0xd7 => ("pmovmskb|pmovmskb||", "Gd,N"),
+        0xd8 => ("psubusb|psubusb||", "P,Q"),
+        0xd9 => ("psubusw|psubusw||", "P,Q"),
+        0xda => ("pminub|pminub||", "P,Q"),
+        0xdb => ("pand|pand||", "P,Q"),
+        0xdc => ("paddusb|paddusb||", "P,Q"),
+        0xdd => ("paddusw|paddusw||", "P,Q"),
+        0xde => ("pmaxub|pmaxub||", "P,Q"),
+        0xdf => ("pandn|pandn||", "P,Q"),
+        0xe0 => ("pavgb|pavgb||", "P,Q"),
+        0xe1 => ("psraw|psraw||", "P,Q"),
+        0xe2 => ("psrad|psrad||", "P,Q"),
+        0xe3 => ("pavgw|pavgw||", "P,Q"),
+        0xe4 => ("pmulhuw|pmulhuw||", "P,Q"),
+        0xe5 => ("pmulhw|pmulhw||", "P,Q"),
+        0xe6 => ("|cvttpd2dq|cvtdq2pd|cvtpd2dq", "V,W|V,W|V,Wq|V,W"),
+        0xe7 => ("movntq|movntdq||", "Mq,P|Mx,V"),
+        0xe8 => ("psubsb|psubsb||", "P,Q"),
+        0xe9 => ("psubsw|psubsw||", "P,Q"),
+        0xea => ("pminsw|pminsw||", "P,Q"),
+        0xeb => ("por|por||", "P,Q"),
+        0xec => ("paddsb|paddsb||", "P,Q"),
+        0xed => ("paddsw|paddsw||", "P,Q"),
+        0xee => ("pmaxsw|pmaxsw||", "P,Q"),
+        0xef => ("pxor|pxor||", "P,Q"),
+        0xf0 => ("|||lddqu", "V,M"),
+        0xf1 => ("psllw|psllw||", "P,Q"),
+        0xf2 => ("pslld|pslld||", "P,Q"),
+        0xf3 => ("psllq|psllq||", "P,Q"),
+        0xf4 => ("pmuludq|pmuludq||", "P,Q"),
+        0xf5 => ("pmaddwd|pmaddwd||", "P,Q"),
+        0xf6 => ("psadbw|psadbw||", "P,Q"),
+        0xf7 => ("maskmovq|maskmovdqu||", "P,N"),
+        0xf8 => ("psubb|psubb||", "P,Q"),
+        0xf9 => ("psubw|psubw||", "P,Q"),
+        0xfa => ("psubd|psubd||", "P,Q"),
+        0xfb => ("psubq|psubq||", "P,Q"),
+        0xfc => ("paddb|paddb||", "P,Q"),
+        0xfd => ("paddw|paddw||", "P,Q"),
+        0xfe => ("paddd|paddd||", "P,Q"),
+        0xff => ("ud0", "Gv,Ev"),
+        /* no modrm */
+        0x04 | 0x0a | 0x0c | 0x0e | 0x36 | 0x38 | 0x39 | 0x3a | 0x3b |
+        0x3c | 0x3d | 0x3e | 0x3f | 0xa6 | 0xa7 => ("(bad)", ""),
+        0x0f => ("(bad)", "E,Ib"),
+        _ => ("(bad)", "E"),
+    }
+}
+
+static CMOV: [&'static str; 16] = [
+    "cmovo", "cmovno", "cmovb", "cmovae", "cmove", "cmovne", "cmovbe", "cmova",
+    "cmovs", "cmovns", "cmovp", "cmovnp", "cmovl", "cmovge", "cmovle", "cmovg",
+];
+
+static SETCC: [&'static str; 16] = [
+    "seto", "setno", "setb", "setae", "sete", "setne", "setbe", "seta",
+    "sets", "setns", "setp", "setnp", "setl", "setge", "setle", "setg",
+];
+
+/* 0f 38, all with modrm and without an immediate */
+fn three_byte_38(op: u8) -> Entry {
+    match op {
+        0x00 => ("pshufb|pshufb||", "P,Q"),
+        0x01 => ("phaddw|phaddw||", "P,Q"),
+        0x02 => ("phaddd|phaddd||", "P,Q"),
+        0x03 => ("phaddsw|phaddsw||", "P,Q"),
+        0x04 => ("pmaddubsw|pmaddubsw||", "P,Q"),
+        0x05 => ("phsubw|phsubw||", "P,Q"),
+        0x06 => ("phsubd|phsubd||", "P,Q"),
+        0x07 => ("phsubsw|phsubsw||", "P,Q"),
+        0x08 => ("psignb|psignb||", "P,Q"),
+        0x09 => ("psignw|psignw||", "P,Q"),
+        0x0a => ("psignd|psignd||", "P,Q"),
+        0x0b => ("pmulhrsw|pmulhrsw||", "P,Q"),
+        0x10 => ("|pblendvb||", "V,W,XMM0"),
+        0x14 => ("|blendvps||", "V,W,XMM0"),
+        0x15 => ("|blendvpd||", "V,W,XMM0"),
+        0x17 => ("|ptest||", "V,W"),
+        0x1c => ("pabsb|pabsb||", "P,Q"),
+        0x1d => ("pabsw|pabsw||", "P,Q"),
+        0x1e => ("pabsd|pabsd||", "P,Q"),
+        0x20 => ("|pmovsxbw||", "V,Wq"),
+        0x21 => ("|pmovsxbd||", "V,Wd"),
+        0x22 => ("|pmovsxbq||", "V,Ww"),
+        0x23 => ("|pmovsxwd||", "V,Wq"),
+        0x24 => ("|pmovsxwq||", "V,Wd"),
+        0x25 => ("|pmovsxdq||", "V,Wq"),
+        0x28 => ("|pmuldq||", "V,W"),
+        0x29 => ("|pcmpeqq||", "V,W"),
+        0x2a => ("|movntdqa||", "V,M"),
+        0x2b => ("|packusdw||", "V,W"),
+        0x30 => ("|pmovzxbw||", "V,Wq"),
+        0x31 => ("|pmovzxbd||", "V,Wd"),
+        0x32 => ("|pmovzxbq||", "V,Ww"),
+        0x33 => ("|pmovzxwd||", "V,Wq"),
+        0x34 => ("|pmovzxwq||", "V,Wd"),
+        0x35 => ("|pmovzxdq||", "V,Wq"),
+        0x37 => ("|pcmpgtq||", "V,W"),
+        0x38 => ("|pminsb||", "V,W"),
+        0x39 => ("|pminsd||", "V,W"),
+        0x3a => ("|pminuw||", "V,W"),
+        0x3b => ("|pminud||", "V,W"),
+        0x3c => ("|pmaxsb||", "V,W"),
+        0x3d => ("|pmaxsd||", "V,W"),
+        0x3e => ("|pmaxuw||", "V,W"),
+        0x3f => ("|pmaxud||", "V,W"),
+        0x40 => ("|pmulld||", "V,W"),
+        0x41 => ("|phminposuw||", "V,W"),
+        0xc8 => ("sha1nexte|||", "V,W"),
+        0xc9 => ("sha1msg1|||", "V,W"),
+        0xca => ("sha1msg2|||", "V,W"),
+        0xcb => ("sha256rnds2|||", "V,W,XMM0"),
+        0xcc => ("sha256msg1|||", "V,W"),
+        0xcd => ("sha256msg2|||", "V,W"),
+        0xdb => ("|aesimc||", "V,W"),
+        0xdc => ("|aesenc||", "V,W"),
+        0xdd => ("|aesenclast||", "V,W"),
+        0xde => ("|aesdec||", "V,W"),
+        0xdf => ("|aesdeclast||", "V,W"),
+        0xf0 => ("movbe|movbe||crc32", "Gv,Mv|Gv,Mv||Gy,Eb"),
+        0xf1 => ("movbe|movbe||crc32", "Mv,Gv|Mv,Gv||Gy,Ev"),
+        _ => ("(bad)", "E"),
+    }
+}
+
+/* 0f 3a, all with modrm and an imm8 */
+fn three_byte_3a(op: u8) -> Entry {
+    match op {
+        0x08 => ("|roundps||", "V,W,Ib"),
+        0x09 => ("|roundpd||", "V,W,Ib"),
+        0x0a => ("|roundss||", "V,Wd,Ib"),
+        0x0b => ("|roundsd||", "V,Wq,Ib"),
+        0x0c => ("|blendps||", "V,W,Ib"),
+        0x0d => ("|blendpd||", "V,W,Ib"),
+        0x0e => ("|pblendw||", "V,W,Ib"),
+        0x0f => ("palignr|palignr||", "P,Q,Ib"),
+        0x14 => ("|pextrb||", "Ed,V,Ib"),
+        0x15 => ("|pextrw||", "Ed,V,Ib"),
+        0x16 => ("|pextrd/pextrq||", "Ey,V,Ib"),
+        0x17 => ("|extractps||", "Ed,V,Ib"),
+        0x20 => ("|pinsrb||", "V,Ed,Ib"),
+        0x21 => ("|insertps||", "V,Wd,Ib"),
+        0x22 => ("|pinsrd/pinsrq||", "V,Ey,Ib"),
+        0x40 => ("|dpps||", "V,W,Ib"),
+        0x41 => ("|dppd||", "V,W,Ib"),
+        0x42 => ("|mpsadbw||", "V,W,Ib"),
+        0x44 => ("|pclmulqdq||", "V,W,Ib"),
+        0x60 => ("|pcmpestrm||", "V,W,Ib"),
+        0x61 => ("|pcmpestri||", "V,W,Ib"),
+        0x62 => ("|pcmpistrm||", "V,W,Ib"),
+        0x63 => ("|pcmpistri||", "V,W,Ib"),
+        0xcc => ("sha1rnds4|||", "V,W,Ib"),
+        0xdf => ("|aeskeygenassist||", "V,W,Ib"),
+        _ => ("(bad)", "E,Ib"),
+    }
+}
+
+/* vex and evex only, None falls back to the sse tables */
+fn vex_only(map: u8, op: u8, pp: usize, w: bool) -> Option<Entry> {
+
+    let entry = match (map, op, pp) {
+        (1, 0x77, 0) => ("vzeroupper/vzeroall", ""),
+        (1, 0x41, _) | (1, 0x42, _) | (1, 0x45, _) | (1, 0x46, _) | (1, 0x47, _) | (1, 0x4a, _) if pp < 2 => {
+            return Some((kop(op, pp, w), "KG,KH,KR"));
+        },
+        (1, 0x44, _) if pp < 2 => (kop(op, pp, w), "KG,KR"),
+        (1, 0x4b, 1) if !w => ("kunpckbw", "KG,KH,KR"),
+        (1, 0x4b, 0) => (if w { "kunpckdq" } else { "kunpckwd" }, "KG,KH,KR"),
+        (1, 0x90, 0) => (if w { "kmovq" } else { "kmovw" }, "KG,KE"),
+        (1, 0x90, 1) => (if w { "kmovd" } else { "kmovb" }, "KG,KE"),
+        (1, 0x91, 0) => (if w { "kmovq" } else { "kmovw" }, "M,KG"),
+        (1, 0x91, 1) => (if w { "kmovd" } else { "kmovb" }, "M,KG"),
+        (1, 0x92, 0) => ("kmovw", "KG,Rd"),
+        (1, 0x92, 1) => ("kmovb", "KG,Rd"),
+        (1, 0x92, 3) => (if w { "kmovq" } else { "kmovd" }, "KG,Ry"),
+        (1, 0x93, 0) => ("kmovw", "Gd,KR"),
+        (1, 0x93, 1) => ("kmovb", "Gd,KR"),
+        (1, 0x93, 3) => (if w { "kmovq" } else { "kmovd" }, "Gy,KR"),
+        (1, 0x98, 0) => (if w { "kortestq" } else { "kortestw" }, "KG,KR"),
+        (1, 0x98, 1) => (if w { "kortestd" } else { "kortestb" }, "KG,KR"),
+        (1, 0x99, 0) => (if w { "ktestq" } else { "ktestw" }, "KG,KR"),
+        (1, 0x99, 1) => (if w { "ktestd" } else { "ktestb" }, "KG,KR"),
+        (2, 0x0c, 1) => ("vpermilps", "V,H,W"),
+        (2, 0x0d, 1) => ("vpermilpd", "V,H,W"),
+        (2, 0x16, 1) => ("vpermps", "V,H,W"),
+        (2, 0x18, 1) => ("vbroadcastss", "V,Wd"),
+        (2, 0x19, 1) => ("vbroadcastsd", "V,Wq"),
+        (2, 0x1a, 1) => ("vbroadcastf128", "V,Mo"),
+        (2, 0x26, 1) => (if w { "vptestmw" } else { "vptestmb" }, "KG,H,W"),
+        (2, 0x26, 2) => (if w { "vptestnmw" } else { "vptestnmb" }, "KG,H,W"),
+        (2, 0x27, 1) => (if w { "vptestmq" } else { "vptestmd" }, "KG,H,W"),
+        (2, 0x27, 2) => (if w { "vptestnmq" } else { "vptestnmd" }, "KG,H,W"),
+        (2, 0x36, 1) => ("vpermd", "V,H,W"),
+        (2, 0x45, 1) => (if w { "vpsrlvq" } else { "vpsrlvd" }, "V,H,W"),
+        (2, 0x46, 1) => ("vpsravd", "V,H,W"),
+        (2, 0x47, 1) => (if w { "vpsllvq" } else { "vpsllvd" }, "V,H,W"),
+        (2, 0x58, 1) => ("vpbroadcastd", "V,Wd"),
+        (2, 0x59, 1) => ("vpbroadcastq", "V,Wq"),
+        (2, 0x5a, 1) => ("vbroadcasti128", "V,Mo"),
+        (2, 0x64, 1) => (if w { "vpblendmq" } else { "vpblendmd" }, "V,H,W"),
+        (2, 0x66, 1) => (if w { "vpblendmw" } else { "vpblendmb" }, "V,H,W"),
+        (2, 0x78, 1) => ("vpbroadcastb", "V,Wb"),
+        (2, 0x79, 1) => ("vpbroadcastw", "V,Ww"),
+        (2, 0x7a, 1) => ("vpbroadcastb", "V,Rd"),
+        (2, 0x7b, 1) => ("vpbroadcastw", "V,Rd"),
+        (2, 0x7c, 1) => (if w { "vpbroadcastq" } else { "vpbroadcastd" }, "V,Ry"),
+        (2, 0x8c, 1) => (if w { "vpmaskmovq" } else { "vpmaskmovd" }, "V,H,M"),
+        (2, 0x8e, 1) => (if w { "vpmaskmovq" } else { "vpmaskmovd" }, "M,H,V"),
+        (2, 0xf2, 0) => ("andn", "Gy,By,Ey"),
+        (2, 0xf3, 0) => ("#17", "By,Ey"),
+        (2, 0xf5, 0) => ("bzhi", "Gy,Ey,By"),
+        (2, 0xf5, 2) => ("pext", "Gy,By,Ey"),
+        (2, 0xf5, 3) => ("pdep", "Gy,By,Ey"),
+        (2, 0xf6, 3) => ("mulx", "Gy,By,Ey"),
+        (2, 0xf7, 0) => ("bextr", "Gy,Ey,By"),
+        (2, 0xf7, 1) => ("shlx", "Gy,Ey,By"),
+        (2, 0xf7, 2) => ("sarx", "Gy,Ey,By"),
+        (2, 0xf7, 3) => ("shrx", "Gy,Ey,By"),
+        (3, 0x00, 1) => ("vpermq", "V,W,Ib"),
+        (3, 0x01, 1) => ("vpermpd", "V,W,Ib"),
+        (3, 0x02, 1) => ("vpblendd", "V,H,W,Ib"),
+        (3, 0x04, 1) => ("vpermilps", "V,W,Ib"),
+        (3, 0x05, 1) => ("vpermilpd", "V,W,Ib"),
+        (3, 0x06, 1) => ("vperm2f128", "V,H,W,Ib"),
+        (3, 0x18, 1) => ("vinsertf128", "V,H,Wo,Ib"),
+        (3, 0x19, 1) => ("vextractf128", "Wo,V,Ib"),
+        (3, 0x1d, 1) => ("vcvtps2ph", "Wq,V,Ib"),
+        (3, 0x1e, 1) => (if w { "vpcmpuq" } else { "vpcmpud" }, "KG,H,W,Ib"),
+        (3, 0x1f, 1) => (if w { "vpcmpq" } else { "vpcmpd" }, "KG,H,W,Ib"),
+        (3, 0x25, 1) => (if w { "vpternlogq" } else { "vpternlogd" }, "V,H,W,Ib"),
+        (3, 0x38, 1) => ("vinserti128", "V,H,Wo,Ib"),
+        (3, 0x39, 1) => ("vextracti128", "Wo,V,Ib"),
+        (3, 0x3e, 1) => (if w { "vpcmpuw" } else { "vpcmpub" }, "KG,H,W,Ib"),
+        (3, 0x3f, 1) => (if w { "vpcmpw" } else { "vpcmpb" }, "KG,H,W,Ib"),
+        (3, 0x46, 1) => ("vperm2i128", "V,H,W,Ib"),
+        (3, 0x4a, 1) => ("vblendvps", "V,H,W,L"),
+        (3, 0x4b, 1) => ("vblendvpd", "V,H,W,L"),
+        (3, 0x4c, 1) => ("vpblendvb", "V,H,W,L"),
+        (3, 0xf0, 3) => ("rorx", "Gy,Ey,Ib"),
+        (2, 0x96..=0x9f, 1) | (2, 0xa6..=0xaf, 1) | (2, 0xb6..=0xbf, 1) => {
+            return Some((fma(op, w), "V,H,W"));
+        },
+        _ => { return None; },
+    };
+
+    Some(entry)
+}
+
+/* mask register logic, the size is spelled by the prefix and vex.w */
+fn kop(op: u8, pp: usize, w: bool) -> &'static str {
+
+    let names = match op {
+        0x41 => ["kandw", "kandq", "kandb", "kandd"],
+        0x42 => ["kandnw", "kandnq", "kandnb", "kandnd"],
+        0x44 => ["knotw", "knotq", "knotb", "knotd"],
+        0x45 => ["korw", "korq", "korb", "kord"],
+        0x46 => ["kxnorw", "kxnorq", "kxnorb", "kxnord"],
+        0x47 => ["kxorw", "kxorq", "kxorb", "kxord"],
+        _ => ["kaddw", "kaddq", "kaddb", "kaddd"],
+    };
+
+    names[pp * 2 + w as usize]
+}
+
+/* vfmadd132ps and friends, the order is in the high nibble */
+fn fma(op: u8, w: bool) -> &'static str {
+
+    static NAMES: [[&'static str; 4]; 30] = [
+        ["vfmaddsub132ps", "vfmaddsub132pd", "vfmaddsub132ps", "vfmaddsub132pd"],
+        ["vfmsubadd132ps", "vfmsubadd132pd", "vfmsubadd132ps", "vfmsubadd132pd"],
+        ["vfmadd132ps", "vfmadd132pd", "vfmadd132ps", "vfmadd132pd"],
+        ["vfmadd132ss", "vfmadd132sd", "vfmadd132ss", "vfmadd132sd"],
+        ["vfmsub132ps", "vfmsub132pd", "vfmsub132ps", "vfmsub132pd"],
+        ["vfmsub132ss", "vfmsub132sd", "vfmsub132ss", "vfmsub132sd"],
+        ["vfnmadd132ps", "vfnmadd132pd", "vfnmadd132ps", "vfnmadd132pd"],
+        ["vfnmadd132ss", "vfnmadd132sd", "vfnmadd132ss", "vfnmadd132sd"],
+        ["vfnmsub132ps", "vfnmsub132pd", "vfnmsub132ps", "vfnmsub132pd"],
+        ["vfnmsub132ss", "vfnmsub132sd", "vfnmsub132ss", "vfnmsub132sd"],
+        ["vfmaddsub213ps", "vfmaddsub213pd", "vfmaddsub213ps", "vfmaddsub213pd"],
+        ["vfmsubadd213ps", "vfmsubadd213pd", "vfmsubadd213ps", "vfmsubadd213pd"],
+        ["vfmadd213ps", "vfmadd213pd", "vfmadd213ps", "vfmadd213pd"],
+        ["vfmadd213ss", "vfmadd213sd", "vfmadd213ss", "vfmadd213sd"],
+        ["vfmsub213ps", "vfmsub213pd", "vfmsub213ps", "vfmsub213pd"],
+        ["vfmsub213ss", "vfmsub213sd", "vfmsub213ss", "vfmsub213sd"],
+        ["vfnmadd213ps", "vfnmadd213pd", "vfnmadd213ps", "vfnmadd213pd"],
+        ["vfnmadd213ss", "vfnmadd213sd", "vfnmadd213ss", "vfnmadd213sd"],
+        ["vfnmsub213ps", "vfnmsub213pd", "vfnmsub213ps", "vfnmsub213pd"],
+        ["vfnmsub213ss", "vfnmsub213sd", "vfnmsub213ss", "vfnmsub213sd"],
+        ["vfmaddsub231ps", "vfmaddsub231pd", "vfmaddsub231ps", "vfmaddsub231pd"],
+        ["vfmsubadd231ps", "vfmsubadd231pd", "vfmsubadd231ps", "vfmsubadd231pd"],
+        ["vfmadd231ps", "vfmadd231pd", "vfmadd231ps", "vfmadd231pd"],
+        ["vfmadd231ss", "vfmadd231sd", "vfmadd231ss", "vfmadd231sd"],
+        ["vfmsub231ps", "vfmsub231pd", "vfmsub231ps", "vfmsub231pd"],
+        ["vfmsub231ss", "vfmsub231sd", "vfmsub231ss", "vfmsub231sd"],
+        ["vfnmadd231ps", "vfnmadd231pd", "vfnmadd231ps", "vfnmadd231pd"],
+        ["vfnmadd231ss", "vfnmadd231sd", "vfnmadd231ss", "vfnmadd231sd"],
+        ["vfnmsub231ps", "vfnmsub231pd", "vfnmsub231ps", "vfnmsub231pd"],
+        ["vfnmsub231ss", "vfnmsub231sd", "vfnmsub231ss", "vfnmsub231sd"],
+    ];
+
+    let row = ((op >> 4) - 0x9) as usize * 10 + ((op & 0xf) - 0x6) as usize;
+    NAMES[row][w as usize]
+}
+
+/* vex forms that don't take a second source in vvvv */
+fn vex_two_operand(map: u8, op: u8, pp: usize, register: bool) -> bool {
+    match (map, op) {
+        /* the scalar moves merge into vvvv between registers only */
+        (1, 0x10) | (1, 0x11) => pp < 2 || !register,
+        (1, 0x51) | (1, 0x52) | (1, 0x53) => pp < 2,
+        (1, 0x5a) => pp < 2,
+        (1, 0x12) | (1, 0x16) => pp >= 2,
+        (1, 0x13) | (1, 0x17) | (1, 0x28) | (1, 0x29) | (1, 0x2b) | (1, 0x2c) | (1, 0x2d) |
+        (1, 0x2e) | (1, 0x2f) | (1, 0x50) | (1, 0x5b) | (1, 0x6e) | (1, 0x6f) | (1, 0x70) |
+        (1, 0x7e) | (1, 0x7f) | (1, 0xc5) | (1, 0xd6) | (1, 0xd7) | (1, 0xe6) | (1, 0xe7) |
+        (1, 0xf0) | (1, 0xf7) => true,
+        (2, 0x17) | (2, 0x1c) | (2, 0x1d) | (2, 0x1e) | (2, 0x2a) | (2, 0x41) |
+        (2, 0x20..=0x25) | (2, 0x30..=0x35) | (2, 0xdb) => true,
+        (3, 0x08) | (3, 0x09) | (3, 0x14..=0x17) | (3, 0x60..=0x63) | (3, 0xdf) => true,
+        _ => false,
+    }
+}
+
+struct Vex {
+    /* 1 0f, 2 0f38, 3 0f3a */
+    map: u8,
+    /* implied prefix, 0 none, 1 66, 2 f3, 3 f2 */
+    pp: usize,
+    /* vector length in bytes */
+    len: usize,
+    w: bool,
+    vvvv: usize,
+    evex: bool,
+    /* evex only */
+    mask: usize,
+    zero: bool,
+    broadcast: bool,
+    /* high bits of the reg and rm register numbers */
+    r_hi: usize,
+    x_hi: usize,
+}
+
+struct Decoder<'a> {
+    code: &'a [u8],
+    addr: u64,
+    pos: usize,
+    opsize: bool,
+    adsize: bool,
+    /* last of f2 or f3 */
+    rep: u8,
+    lock: bool,
+    seg: Option<&'static str>,
+    rex: u8,
+    vex: Option<Vex>,
+    /* which of none, 66, f3 and f2 picked an sse form */
+    sse: Option<usize>,
+    modrm: Option<u8>,
+    mem: Option<Mem>,
+    /* evex compressed displacement, scaled once the access size is known */
+    disp8: bool,
+    /* pushes and pops default to 64 bits */
+    stack: bool,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(code: &'a [u8], addr: u64) -> Self {
+        Decoder {
+            code: code,
+            addr: addr,
+            pos: 0,
+            opsize: false,
+            adsize: false,
+            rep: 0,
+            lock: false,
+            seg: None,
+            rex: 0,
+            vex: None,
+            sse: None,
+            modrm: None,
+            mem: None,
+            disp8: false,
+            stack: false,
+        }
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        if self.pos >= MAX_LEN {
+            return None;
+        }
+
+        let b = self.code.get(self.pos).cloned();
+        self.pos += 1;
+        b
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.code.get(self.pos).cloned()
+    }
+
+    /* little endian, zero extended */
+    fn imm(&mut self, size: usize) -> Option<u64> {
+        let mut value = 0;
+
+        for i in 0..size {
+            value |= (self.byte()? as u64) << (i * 8);
+        }
+
+        Some(value)
+    }
+
+    fn rex_w(&self) -> bool {
+        self.rex & 8 != 0 || self.vex.as_ref().map_or(false, |vex| vex.w)
+    }
+
+    /* operand size in bytes */
+    fn opsize(&self) -> usize {
+        if self.rex_w() || (self.stack && !self.opsize) {
+            8
+        } else if self.opsize {
+            2
+        } else {
+            4
+        }
+    }
+
+    fn decode(&mut self) -> Option<Instruction> {
+
+        /* legacy prefixes, then rex right before the opcode */
+        loop {
+            match self.peek()? {
+                0x66 => self.opsize = true,
+                0x67 => self.adsize = true,
+                0xf0 => self.lock = true,
+                0xf2 | 0xf3 => self.rep = self.peek()?,
+                0x2e => self.seg = Some("cs"),
+                0x36 => self.seg = Some("ss"),
+                0x3e => self.seg = Some("ds"),
+                0x26 => self.seg = Some("es"),
+                0x64 => self.seg = Some("fs"),
+                0x65 => self.seg = Some("gs"),
+                _ => { break; },
+            }
+
+            self.byte()?;
+        }
+
+        if self.peek()? & 0xf0 == 0x40 {
+            self.rex = self.byte()?;
+        }
+
+        let op = self.byte()?;
+
+        let (name, spec, map, op) = match op {
+            0x0f => {
+                let op = self.byte()?;
+                match op {
+                    0x38 => { let op = self.byte()?; let (n, s) = three_byte_38(op); (n, s, 2, op) },
+                    0x3a => { let op = self.byte()?; let (n, s) = three_byte_3a(op); (n, s, 3, op) },
+                    _ => { let (n, s) = two_byte(op); (n, s, 1, op) },
+                }
+            },
+            0xc4 | 0xc5 | 0x62 if self.rex == 0 => {
+                return self.decode_vex(op);
+            },
+            _ => {
+                self.stack = match op {
+                    0x50..=0x5f | 0x68 | 0x6a | 0x9c | 0x9d => true,
+                    _ => false,
+                };
+                let (n, s) = one_byte(op);
+                (n, s, 0, op)
+            },
+        };
+
+        let (name, spec) = self.select(name, spec);
+        self.finish(name, spec, map, op)
+    }
+
+    /* pick the form for the prefix in front of an sse instruction */
+    fn select(&mut self, name: &'static str, spec: &'static str) -> (&'static str, &'static str) {
+
+        if !name.contains('|') {
+            return (name, spec);
+        }
+
+        let idx = match self.rep {
+            0xf3 => 2,
+            0xf2 => 3,
+            _ if self.opsize => 1,
+            _ => 0,
+        };
+
+        let names: Vec<&'static str> = name.split('|').collect();
+        let specs: Vec<&'static str> = spec.split('|').collect();
+
+        /* the prefix was part of the opcode */
+        self.sse = Some(idx);
+
+        let spec = *specs.get(idx).unwrap_or(&specs[0]);
+        let spec = if spec.is_empty() { specs[0] } else { spec };
+
+        match names[idx] {
+            "" => ("(bad)", spec),
+            name => (name, spec),
+        }
+    }
+
+    fn decode_vex(&mut self, op: u8) -> Option<Instruction> {
+
+        let p0 = self.byte()?;
+
+        let vex = match op {
+            0xc5 => Vex {
+                map: 1,
+                pp: (p0 & 3) as usize,
+                len: if p0 & 4 != 0 { 32 } else { 16 },
+                w: false,
+                vvvv: (!p0 >> 3 & 0xf) as usize,
+                evex: false,
+                mask: 0,
+                zero: false,
+                broadcast: false,
+                r_hi: 0,
+                x_hi: 0,
+            },
+            0xc4 => {
+                let p1 = self.byte()?;
+                self.rex = 0x40 | (!p0 >> 5 & 7);
+                Vex {
+                    map: p0 & 0x1f,
+                    pp: (p1 & 3) as usize,
+                    len: if p1 & 4 != 0 { 32 } else { 16 },
+                    w: p1 & 0x80 != 0,
+                    vvvv: (!p1 >> 3 & 0xf) as usize,
+                    evex: false,
+                    mask: 0,
+                    zero: false,
+                    broadcast: false,
+                    r_hi: 0,
+                    x_hi: 0,
+                }
+            },
+            _ => {
+                let p1 = self.byte()?;
+                let p2 = self.byte()?;
+                self.rex = 0x40 | (!p0 >> 5 & 7);
+                Vex {
+                    map: p0 & 3,
+                    pp: (p1 & 3) as usize,
+                    len: 16 << (p2 >> 5 & 3),
+                    w: p1 & 0x80 != 0,
+                    vvvv: (!p1 >> 3 & 0xf) as usize | if p2 & 8 == 0 { 16 } else { 0 },
+                    evex: true,
+                    mask: (p2 & 7) as usize,
+                    zero: p2 & 0x80 != 0,
+                    broadcast: p2 & 0x10 != 0,
+                    r_hi: if p0 & 0x10 == 0 { 16 } else { 0 },
+                    x_hi: if p0 & 0x40 == 0 { 16 } else { 0 },
+                }
+            },
+        };
+
+        /* c5 implies rex.r only */
+        if op == 0xc5 {
+            self.rex = 0x40 | (!p0 >> 5 & 4);
+        }
+
+        let (map, pp, w) = (vex.map, vex.pp, vex.w);
+        self.vex = Some(vex);
+
+        let op = self.byte()?;
+
+        if let Some((name, spec)) = vex_only(map, op, pp, w) {
+            return self.finish(name, spec, map + 0x10, op);
+        }
+
+        let (name, spec) = match map {
+            1 => two_byte(op),
+            2 => three_byte_38(op),
+            3 => three_byte_3a(op),
+            _ => ("(bad)", "E"),
+        };
+
+        /* only the sse forms and group 15 carry over from the legacy map */
+        let (name, spec) = match (map, name) {
+            (1, "#15") => (name, spec),
+            (1, _) if !name.contains('|') => ("(bad)", spec),
+            _ => (name, spec),
+        };
+
+        /* vex carries the prefix, there is no mmx form */
+        self.opsize = pp == 1;
+        self.rep = match pp { 2 => 0xf3, 3 => 0xf2, _ => 0 };
+
+        let (name, spec) = self.select(name, spec);
+
+        self.finish(name, spec, map + 0x10, op)
+    }
+
+    /* map is 0 one byte, 1 0f, 2 0f38, 3 0f3a, plus 0x10 under vex */
+    fn finish(&mut self, name: &'static str, spec: &'static str, map: u8, op: u8) -> Option<Instruction> {
+
+        let needs_modrm = spec.split(',').any(|s| !is_fixed(s) && match s.chars().next() {
+            Some('E') | Some('G') | Some('M') | Some('R') | Some('V') | Some('W') | Some('U') |
+            Some('C') | Some('D') | Some('P') | Some('Q') | Some('N') | Some('S') | Some('K') => true,
+            _ => false,
+        }) || (map >= 0x12 && map != 0x11);
+
+        if needs_modrm {
+            self.decode_modrm()?;
+        }
+
+        let modrm = self.modrm.unwrap_or(0);
+        let reg = (modrm >> 3 & 7) as usize;
+        let register = modrm >> 6 == 3;
+
+        let mut name = name.to_string();
+        let mut spec = spec;
+
+        /* groups pick the instruction with modrm.reg */
+        if name.starts_with("#") {
+            let (n, s) = self.group(&name, op, reg, register, spec);
+            name = n.to_string();
+            spec = s;
+        }
+
+        if name == "#x87" {
+            return self.x87(op, modrm);
+        }
+
+        let mut spec: Vec<String> = spec.split(',')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect();
+
+        /* sizes by operand size or rex.w, `a/b/c` or `a/b` */
+        if name.contains('/') {
+            let forms: Vec<&str> = name.split('/').collect();
+            let pick = if forms.len() == 3 {
+                match self.opsize() { 2 => 0, 4 => 1, _ => 2 }
+            } else if op == 0x77 {
+                /* vzeroupper or vzeroall by vex.l */
+                (self.vex.as_ref().map_or(16, |vex| vex.len) > 16) as usize
+            } else {
+                self.rex_w() as usize
+            };
+            name = forms[pick].to_string();
+        }
+
+        let vex = self.vex.is_some();
+
+        if vex && map >= 0x10 {
+            if !name.starts_with("v") && !name.starts_with("k") && name != "(bad)"
+                && !spec.iter().any(|s| s.starts_with("B")) && !is_gpr_only(&name)
+            {
+                name = format!("v{}", name);
+
+                let pp = self.vex.as_ref().unwrap().pp;
+                let third = !vex_two_operand(map - 0x10, op, pp, register)
+                    && !spec.iter().any(|s| s == "H") && spec.len() >= 2;
+
+                /* the shift groups write to vvvv */
+                if map == 0x11 && (op == 0x71 || op == 0x72 || op == 0x73) {
+                    spec.insert(0, "H".to_string());
+                } else if third {
+                    spec.insert(1, "H".to_string());
+                }
+            }
+
+            if self.vex.as_ref().unwrap().evex {
+                name = evex_name(&name, op, map - 0x10, self.vex.as_ref().unwrap().w);
+
+                /* evex compares write a mask */
+                if is_evex_compare(map - 0x10, op) {
+                    spec[0] = "KG".to_string();
+                }
+            }
+        }
+
+        let mut operands = vec![];
+
+        for s in spec.iter() {
+            operands.push(self.operand(s, op)?);
+        }
+
+        /* rip relative addresses count from the end of the instruction */
+        let next = self.addr + self.pos as u64;
+
+        for operand in operands.iter_mut() {
+            if let &mut Operand::Mem(ref mut mem) = operand {
+                if mem.base == Some("rip") || mem.base == Some("eip") {
+                    mem.target = Some(next.wrapping_add(mem.disp as u64));
+                }
+            }
+        }
+
+        let mut prefix = None;
+
+        if map == 0 {
+            match op {
+                0x90 if self.rep == 0xf3 => { name = "pause".to_string(); },
+                0xe3 if self.adsize => { name = "jecxz".to_string(); },
+                0xb8..=0xbf if self.rex_w() => { name = "movabs".to_string(); },
+                0x90 if self.rex & 1 != 0 => {
+                    name = "xchg".to_string();
+                    let reg = gpr(self.opsize(), 8, self.rex);
+                    operands = vec![Operand::Reg(reg), Operand::Reg(gpr(self.opsize(), 0, self.rex))];
+                },
+                0xa4..=0xa5 | 0xaa..=0xad | 0x6c..=0x6f if self.rep != 0 => { prefix = Some("rep"); },
+                0xa6..=0xa7 | 0xae..=0xaf if self.rep == 0xf3 => { prefix = Some("repz"); },
+                0xa6..=0xa7 | 0xae..=0xaf if self.rep == 0xf2 => { prefix = Some("repnz"); },
+                0xc3 | 0xc2 if self.rep == 0xf3 => { prefix = Some("repz"); },
+                0xc3 | 0xc2 | 0xe8 | 0xe9 | 0xeb | 0x70..=0x7f | 0xff if self.rep == 0xf2 => { prefix = Some("bnd"); },
+                _ => {},
+            }
+        }
+
+        if map == 1 && (op & 0xf0) == 0x80 && self.rep == 0xf2 {
+            prefix = Some("bnd");
+        }
+
+        if self.lock {
+            prefix = Some("lock");
+        }
+
+        let mask = match self.vex {
+            Some(ref vex) if vex.evex && vex.mask != 0 => Some((MASK[vex.mask], vex.zero)),
+            _ => None,
+        };
+
+        /* cmpps xmm0,xmm1,0x1 is cmpltps xmm0,xmm1 */
+        if map == 1 && op == 0xc2 && self.vex.is_none() {
+            if let Some(&Operand::Imm(pred, _)) = operands.last() {
+                if pred < 8 {
+                    static PRED: [&'static str; 8] = ["eq", "lt", "le", "unord", "neq", "nlt", "nle", "ord"];
+                    name = format!("cmp{}{}", PRED[pred as usize], &name[3..]);
+                    operands.pop();
+                }
+            }
+        }
+
+        /* undefined, or a register where only memory goes, e.g. lea eax,eax */
+        if name == "(bad)" || operands.contains(&Operand::Reg("(bad)")) {
+            return Some(bad(self.code, self.addr));
+        }
+
+        Some(Instruction {
+            addr: self.addr,
+            bytes: self.code[..self.pos].to_vec(),
+            mnemonic: name,
+            operands: operands,
+            prefix: prefix,
+            mask: mask,
+        })
+    }
+
+    /* resolve a `#` entry through modrm.reg */
+    fn group(&mut self, name: &str, op: u8, reg: usize, register: bool, spec: &'static str)
+        -> (&'static str, &'static str)
+    {
+        let modrm = self.modrm.unwrap_or(0);
+        let sse = if self.rep == 0xf3 { 2 } else if self.rep == 0xf2 { 3 } else if self.opsize { 1 } else { 0 };
+
+        match name {
+            "#1" => (ALU[reg], spec),
+            "#1a" => (if reg == 0 { "pop" } else { "(bad)" }, spec),
+            "#2" => (SHIFT[reg], spec),
+            "#3" => match reg {
+                0 | 1 => ("test", if op == 0xf6 { "Eb,Ib" } else { "Ev,Iz" }),
+                _ => (["", "", "not", "neg", "mul", "imul", "div", "idiv"][reg], spec),
+            },
+            "#4" => (["inc", "dec", "(bad)", "(bad)", "(bad)", "(bad)", "(bad)", "(bad)"][reg], spec),
+            "#5" => match reg {
+                0 => ("inc", "Ev"),
+                1 => ("dec", "Ev"),
+                2 => ("call", "Eq"),
+                3 => ("call far", "M"),
+                4 => ("jmp", "Eq"),
+                5 => ("jmp far", "M"),
+                6 => ("push", "Eq"),
+                _ => ("(bad)", "Ev"),
+            },
+            "#11" => match (reg, modrm) {
+                (0, _) => ("mov", spec),
+                (7, 0xf8) if op == 0xc6 => ("xabort", "Ib"),
+                (7, 0xf8) => ("xbegin", "Jz"),
+                _ => ("(bad)", spec),
+            },
+            "#6" => (["sldt", "str", "lldt", "ltr", "verr", "verw", "(bad)", "(bad)"][reg], spec),
+            "#7" if register => match modrm {
+                0xc1 => ("vmcall", ""),
+                0xc2 => ("vmlaunch", ""),
+                0xc3 => ("vmresume", ""),
+                0xc4 => ("vmxoff", ""),
+                0xc8 => ("monitor", ""),
+                0xc9 => ("mwait", ""),
+                0xca => ("clac", ""),
+                0xcb => ("stac", ""),
+                0xd0 => ("xgetbv", ""),
+                0xd1 => ("xsetbv", ""),
+                0xd5 => ("xend", ""),
+                0xd6 => ("xtest", ""),
+                0xee => ("rdpkru", ""),
+                0xef => ("wrpkru", ""),
+                0xf8 => ("swapgs", ""),
+                0xf9 => ("rdtscp", ""),
+                _ if reg == 4 => ("smsw", "Rv"),
+                _ if reg == 6 => ("lmsw", "Rw"),
+                _ => ("(bad)", ""),
+            },
+            "#7" => (["sgdt", "sidt", "lgdt", "lidt", "smsw", "(bad)", "lmsw", "invlpg"][reg],
+                if reg == 4 || reg == 6 { "Ew" } else { "M" }),
+            "#8" => (["(bad)", "(bad)", "(bad)", "(bad)", "bt", "bts", "btr", "btc"][reg], spec),
+            "#9" => match (reg, register) {
+                (1, false) => (if self.rex_w() { "cmpxchg16b" } else { "cmpxchg8b" }, if self.rex_w() { "Mo" } else { "Mq" }),
+                (6, true) => ("rdrand", "Rv"),
+                (7, true) if sse == 2 => ("rdpid", "Rq"),
+                (7, true) => ("rdseed", "Rv"),
+                (6, false) => ("vmptrld", "Mq"),
+                (7, false) => ("vmptrst", "Mq"),
+                _ => ("(bad)", ""),
+            },
+            "#12" => match sse {
+                0 => (if register { "movhlps" } else { "movlps" }, "V,Wq"),
+                1 => { self.sse = Some(1); ("movlpd", "V,Mq") },
+                2 => { self.sse = Some(2); ("movsldup", "V,W") },
+                _ => { self.sse = Some(3); ("movddup", "V,Wq") },
+            },
+            "#16" => match sse {
+                0 => (if register { "movlhps" } else { "movhps" }, "V,Wq"),
+                1 => { self.sse = Some(1); ("movhpd", "V,Mq") },
+                2 => { self.sse = Some(2); ("movshdup", "V,W") },
+                _ => ("(bad)", spec),
+            },
+            "#12s" | "#13s" | "#14s" => {
+                let names = match name {
+                    "#12s" => ["", "", "psrlw", "", "psraw", "", "psllw", ""],
+                    "#13s" => ["", "", "psrld", "", "psrad", "", "pslld", ""],
+                    _ => ["", "", "psrlq", "psrldq", "", "", "psllq", "pslldq"],
+                };
+                if self.opsize {
+                    self.sse = Some(1);
+                }
+                match names[reg] {
+                    "" => ("(bad)", spec),
+                    name => (name, "N,Ib"),
+                }
+            },
+            "#15" if register => match (sse, reg) {
+                (0, 5) => ("lfence", ""),
+                (0, 6) => ("mfence", ""),
+                (0, 7) => ("sfence", ""),
+                (2, 0) => { self.sse = Some(2); ("rdfsbase", "Ry") },
+                (2, 1) => { self.sse = Some(2); ("rdgsbase", "Ry") },
+                (2, 2) => { self.sse = Some(2); ("wrfsbase", "Ry") },
+                (2, 3) => { self.sse = Some(2); ("wrgsbase", "Ry") },
+                _ => ("(bad)", ""),
+            },
+            "#15" => match (sse, reg) {
+                (1, 6) => { self.sse = Some(1); ("clwb", "Mb") },
+                (1, 7) => { self.sse = Some(1); ("clflushopt", "Mb") },
+                (_, 2) => ("ldmxcsr", "Md"),
+                (_, 3) => ("stmxcsr", "Md"),
+                (_, 7) => ("clflush", "Mb"),
+                _ => ([if self.rex_w() { "fxsave64" } else { "fxsave" },
+                       if self.rex_w() { "fxrstor64" } else { "fxrstor" },
+                       "", "", "xsave", "xrstor", "xsaveopt", ""][reg], "M"),
+            },
+            "#16p" if !register && reg < 4 => (["prefetchnta", "prefetcht0", "prefetcht1", "prefetcht2"][reg], "Mb"),
+            "#16p" => ("nop", "Ev"),
+            "#p" => (if reg == 1 { "prefetchw" } else { "prefetch" }, "Mb"),
+            "#1e" if self.rep == 0xf3 && modrm == 0xfa => { self.sse = Some(2); ("endbr64", "") },
+            "#1e" if self.rep == 0xf3 && modrm == 0xfb => { self.sse = Some(2); ("endbr32", "") },
+            "#1e" => ("nop", "Ev"),
+            "#17" => (["(bad)", "blsr", "blsmsk", "blsi", "(bad)", "(bad)", "(bad)", "(bad)"][reg], spec),
+            "#x87" => ("#x87", spec),
+            _ => ("(bad)", spec),
+        }
+    }
+
+    fn decode_modrm(&mut self) -> Option<()> {
+
+        let modrm = self.byte()?;
+        self.modrm = Some(modrm);
+
+        let md = modrm >> 6;
+        let rm = (modrm & 7) as usize;
+
+        if md == 3 {
+            return Some(());
+        }
+
+        let b = (self.rex & 1) as usize * 8;
+        let x = (self.rex >> 1 & 1) as usize * 8;
+        let regs: &[&'static str; 16] = if self.adsize { &REG32 } else { &REG64 };
+
+        let mut mem = Mem {
+            size: 0,
+            seg: self.seg,
+            base: None,
+            index: None,
+            disp: 0,
+            target: None,
+        };
+
+        let disp_size = if rm == 4 {
+            let sib = self.byte()?;
+            let scale = 1 << (sib >> 6);
+            let index = (sib >> 3 & 7) as usize + x;
+            let base = (sib & 7) as usize;
+
+            if index != 4 {
+                mem.index = Some((regs[index], scale));
+            }
+
+            if base == 5 && md == 0 {
+                4
+            } else {
+                mem.base = Some(regs[base + b]);
+                [0, 1, 4][md as usize]
+            }
+        } else if rm == 5 && md == 0 {
+            mem.base = Some(if self.adsize { "eip" } else { "rip" });
+            4
+        } else {
+            mem.base = Some(regs[rm + b]);
+            [0, 1, 4][md as usize]
+        };
+
+        mem.disp = match disp_size {
+            1 => self.byte()? as i8 as i64,
+            4 => self.imm(4)? as u32 as i32 as i64,
+            _ => 0,
+        };
+
+        self.disp8 = disp_size == 1 && self.vex.as_ref().map_or(false, |vex| vex.evex);
+
+        self.mem = Some(mem);
+        Some(())
+    }
+
+    fn rm_reg(&self) -> usize {
+        (self.modrm.unwrap_or(0) & 7) as usize + (self.rex & 1) as usize * 8
+    }
+
+    fn reg_field(&self) -> usize {
+        (self.modrm.unwrap_or(0) >> 3 & 7) as usize + (self.rex >> 2 & 1) as usize * 8
+    }
+
+    fn vector_len(&self) -> usize {
+        self.vex.as_ref().map_or(16, |vex| vex.len)
+    }
+
+    /* vector register, the high bank only exists under evex */
+    fn vreg(&self, n: usize, size: usize) -> &'static str {
+        match size {
+            64 => ZMM[n & 31],
+            32 => YMM[n & 31],
+            _ => XMM[n & 31],
+        }
+    }
+
+    /* the memory operand as an access of size bytes */
+    fn sized(&self, mem: &Mem, size: usize) -> Operand {
+
+        let mut mem = Mem { size: size, ..mem.clone() };
+
+        /* evex scales a byte displacement by the size of the access */
+        if let (true, Some(ref vex)) = (self.disp8, self.vex.as_ref()) {
+            let element = if vex.w { 8 } else { 4 };
+            mem.disp *= if vex.broadcast { element } else if size > 0 { size } else { vex.len } as i64;
+        }
+
+        Operand::Mem(mem)
+    }
+
+    /* memory operand of the given size, or the r/m register */
+    fn rm<F: Fn(usize) -> &'static str>(&self, size: usize, reg: F) -> Operand {
+        match self.mem {
+            Some(ref mem) => self.sized(mem, size),
+            None => Operand::Reg(reg(self.rm_reg())),
+        }
+    }
+
+    fn operand(&mut self, spec: &str, op: u8) -> Option<Operand> {
+
+        let opsize = self.opsize();
+        let rex = self.rex;
+        let wide = self.rex_w();
+        let vlen = self.vector_len();
+
+        /* mmx unless the 66 form was picked, always xmm under vex */
+        let mmx = self.vex.is_none() && self.sse != Some(1);
+        let r_hi = self.vex.as_ref().map_or(0, |vex| vex.r_hi);
+        let x_hi = self.vex.as_ref().map_or(0, |vex| vex.x_hi);
+
+        let size = |c: Option<char>| match c {
+            Some('b') => 1,
+            Some('w') => 2,
+            Some('d') => 4,
+            Some('q') => 8,
+            Some('o') => 16,
+            Some('t') => 10,
+            Some('y') => if wide { 8 } else { 4 },
+            Some('x') => vlen,
+            Some('v') => opsize,
+            _ => 0,
+        };
+
+        match spec {
+            "AL" => { return Some(Operand::Reg("al")); },
+            "CL" => { return Some(Operand::Reg("cl")); },
+            "DX" => { return Some(Operand::Reg("dx")); },
+            "rAX" => { return Some(Operand::Reg(gpr(opsize, 0, rex))); },
+            "eAX" => { return Some(Operand::Reg(if opsize == 2 { "ax" } else { "eax" })); },
+            "XMM0" => { return Some(Operand::Reg("xmm0")); },
+            "1" => { return Some(Operand::Imm(1, 1)); },
+            _ => {},
+        }
+
+        let kind = spec.chars().next()?;
+        let sz = spec.chars().nth(1);
+
+        let operand = match kind {
+            'E' => {
+                let size = if sz.is_none() { opsize } else { size(sz) };
+                self.rm(size, |n| gpr(size, n, rex))
+            },
+            'M' => {
+                let size = size(sz);
+                match self.mem {
+                    Some(ref mem) => self.sized(mem, size),
+                    None => Operand::Reg("(bad)"),
+                }
+            },
+            'G' => Operand::Reg(gpr(size(sz), self.reg_field(), rex)),
+            'R' | 'U' if self.mem.is_some() => Operand::Reg("(bad)"),
+            'R' => Operand::Reg(gpr(size(sz), self.rm_reg(), rex)),
+            'B' => Operand::Reg(gpr(size(sz), self.vex.as_ref().map_or(0, |vex| vex.vvvv), rex)),
+            'Z' => {
+                let size = size(sz);
+                Operand::Reg(gpr(size, (op & 7) as usize + (rex & 1) as usize * 8, rex))
+            },
+            'S' => Operand::Reg(SEG[self.reg_field() & 7]),
+            'C' => Operand::Reg(CR[self.reg_field()]),
+            'D' => Operand::Reg(DR[self.reg_field()]),
+            'V' => Operand::Reg(self.vreg(self.reg_field() + r_hi, vlen)),
+            'H' => Operand::Reg(self.vreg(self.vex.as_ref().map_or(0, |vex| vex.vvvv), vlen)),
+            'U' => Operand::Reg(self.vreg(self.rm_reg() + x_hi, vlen)),
+            'W' => {
+                let bank = self.rm_reg() + x_hi;
+                match self.mem {
+                    Some(ref mem) if sz.is_none() => self.sized(mem, vlen),
+                    Some(ref mem) => self.sized(mem, size(sz)),
+                    None if sz.is_none() => Operand::Reg(self.vreg(bank, vlen)),
+                    /* scalars and broadcast sources are always xmm */
+                    None => Operand::Reg(self.vreg(bank, 16)),
+                }
+            },
+            'L' => {
+                /* is4, register in the high nibble of an imm8 */
+                let imm = self.byte()?;
+                Operand::Reg(self.vreg((imm >> 4) as usize, vlen))
+            },
+            'P' if mmx => Operand::Reg(MM[self.reg_field() & 7]),
+            'P' => Operand::Reg(self.vreg(self.reg_field() + r_hi, vlen)),
+            'Q' | 'N' if mmx => {
+                let n = self.rm_reg() & 7;
+                match self.mem {
+                    Some(ref mem) => self.sized(mem, 8),
+                    None => Operand::Reg(MM[n]),
+                }
+            },
+            'Q' | 'N' => {
+                let bank = self.rm_reg() + x_hi;
+                match self.mem {
+                    Some(ref mem) => self.sized(mem, vlen),
+                    None => Operand::Reg(self.vreg(bank, vlen)),
+                }
+            },
+            'K' => match sz {
+                Some('G') => Operand::Reg(MASK[self.reg_field() & 7]),
+                Some('H') => Operand::Reg(MASK[self.vex.as_ref().map_or(0, |vex| vex.vvvv) & 7]),
+                Some('R') if self.mem.is_some() => Operand::Reg("(bad)"),
+                _ => match self.mem {
+                    Some(ref mem) => self.sized(mem, 0),
+                    None => Operand::Reg(MASK[self.rm_reg() & 7]),
+                },
+            },
+            'I' => {
+                let (bytes, size) = match sz {
+                    Some('b') => (1, 1),
+                    Some('w') => (2, 2),
+                    Some('s') => (1, opsize),
+                    Some('z') => (if opsize == 2 { 2 } else { 4 }, opsize),
+                    Some('v') => (opsize, opsize),
+                    _ => (1, 1),
+                };
+
+                let value = self.imm(bytes)?;
+
+                /* sign extend to the operand size */
+                let value = if size > bytes {
+                    let shift = 64 - bytes * 8;
+                    (((value << shift) as i64) >> shift) as u64
+                } else {
+                    value
+                };
+
+                let value = if size < 8 { value & ((1 << (size * 8)) - 1) } else { value };
+
+                Operand::Imm(value, size)
+            },
+            'J' => {
+                let rel = match sz {
+                    Some('b') => self.byte()? as i8 as i64,
+                    _ => self.imm(4)? as u32 as i32 as i64,
+                };
+
+                Operand::Rel((self.addr + self.pos as u64).wrapping_add(rel as u64))
+            },
+            'O' => {
+                let addr = self.imm(if self.adsize { 4 } else { 8 })?;
+                Operand::Mem(Mem {
+                    size: if sz == Some('b') { 1 } else { opsize },
+                    seg: self.seg,
+                    base: None,
+                    index: None,
+                    disp: addr as i64,
+                    target: None,
+                })
+            },
+            _ => { return None; },
+        };
+
+        Some(operand)
+    }
+
+    fn x87(&mut self, op: u8, modrm: u8) -> Option<Instruction> {
+
+        let reg = (modrm >> 3 & 7) as usize;
+        let i = (modrm & 7) as usize;
+        let esc = (op - 0xd8) as usize;
+
+        let (name, operands): (&str, Vec<Operand>) = if modrm >> 6 != 3 {
+            let (name, size) = X87_MEM[esc][reg];
+            let mem = self.mem.clone().unwrap();
+            (name, vec![Operand::Mem(Mem { size: size, ..mem })])
+        } else {
+            let st = Operand::Reg("st");
+            let sti = Operand::Reg(ST[i]);
+
+            match (esc, reg) {
+                (0, _) => match reg {
+                    2 | 3 => (["fcom", "fcomp"][reg - 2], vec![sti]),
+                    _ => (["fadd", "fmul", "", "", "fsub", "fsubr", "fdiv", "fdivr"][reg], vec![st, sti]),
+                },
+                (1, 0) => ("fld", vec![sti]),
+                (1, 1) => ("fxch", vec![sti]),
+                (1, _) => (X87_D9[(modrm - 0xd0) as usize], vec![]),
+                (2, 0..=3) => (["fcmovb", "fcmove", "fcmovbe", "fcmovu"][reg], vec![st, sti]),
+                (2, 5) if i == 1 => ("fucompp", vec![]),
+                (3, 0..=3) => (["fcmovnb", "fcmovne", "fcmovnbe", "fcmovnu"][reg], vec![st, sti]),
+                (3, 4) if i == 2 => ("fnclex", vec![]),
+                (3, 4) if i == 3 => ("fninit", vec![]),
+                (3, 5) => ("fucomi", vec![st, sti]),
+                (3, 6) => ("fcomi", vec![st, sti]),
+                (4, 0) | (4, 1) | (4, 4..=7) => (["fadd", "fmul", "", "", "fsubr", "fsub", "fdivr", "fdiv"][reg], vec![sti, st]),
+                (5, 0) => ("ffree", vec![sti]),
+                (5, 2) => ("fst", vec![sti]),
+                (5, 3) => ("fstp", vec![sti]),
+                (5, 4) => ("fucom", vec![sti]),
+                (5, 5) => ("fucomp", vec![sti]),
+                (6, 3) if i == 1 => ("fcompp", vec![]),
+                (6, 0) | (6, 1) | (6, 4..=7) => (["faddp", "fmulp", "", "", "fsubrp", "fsubp", "fdivrp", "fdivp"][reg], vec![sti, st]),
+                (7, 0) => ("ffreep", vec![sti]),
+                (7, 4) if i == 0 => ("fnstsw", vec![Operand::Reg("ax")]),
+                (7, 5) => ("fucomip", vec![st, sti]),
+                (7, 6) => ("fcomip", vec![st, sti]),
+                _ => ("(bad)", vec![]),
+            }
+        };
+
+        let name = if name.is_empty() { "(bad)" } else { name };
+
+        Some(Instruction {
+            addr: self.addr,
+            bytes: self.code[..self.pos].to_vec(),
+            mnemonic: name.to_string(),
+            operands: if name == "(bad)" { vec![] } else { operands },
+            prefix: None,
+            mask: None,
+        })
+    }
+}
+
+/* d9 d0 to d9 ff */
+static X87_D9: [&'static str; 48] = [
+    "fnop", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    "fchs", "fabs", "", "", "ftst", "fxam", "", "",
+    "fld1", "fldl2t", "fldl2e", "fldpi", "fldlg2", "fldln2", "fldz", "",
+    "f2xm1", "fyl2x", "fptan", "fpatan", "fxtract", "fprem1", "fdecstp", "fincstp",
+    "fprem", "fyl2xp1", "fsqrt", "fsincos", "frndint", "fscale", "fsin", "fcos",
+];
+
+/* name and memory size by escape byte and modrm.reg */
+static X87_MEM: [[(&'static str, usize); 8]; 8] = [
+    [("fadd", 4), ("fmul", 4), ("fcom", 4), ("fcomp", 4), ("fsub", 4), ("fsubr", 4), ("fdiv", 4), ("fdivr", 4)],
+    [("fld", 4), ("(bad)", 0), ("fst", 4), ("fstp", 4), ("fldenv", 0), ("fldcw", 2), ("fnstenv", 0), ("fnstcw", 2)],
+    [("fiadd", 4), ("fimul", 4), ("ficom", 4), ("ficomp", 4), ("fisub", 4), ("fisubr", 4), ("fidiv", 4), ("fidivr", 4)],
+    [("fild", 4), ("fisttp", 4), ("fist", 4), ("fistp", 4), ("(bad)", 0), ("fld", 10), ("(bad)", 0), ("fstp", 10)],
+    [("fadd", 8), ("fmul", 8), ("fcom", 8), ("fcomp", 8), ("fsub", 8), ("fsubr", 8), ("fdiv", 8), ("fdivr", 8)],
+    [("fld", 8), ("fisttp", 8), ("fst", 8), ("fstp", 8), ("frstor", 0), ("(bad)", 0), ("fnsave", 0), ("fnstsw", 2)],
+    [("fiadd", 2), ("fimul", 2), ("ficom", 2), ("ficomp", 2), ("fisub", 2), ("fisubr", 2), ("fidiv", 2), ("fidivr", 2)],
+    [("fild", 2), ("fisttp", 2), ("fist", 2), ("fistp", 2), ("fbld", 10), ("fild", 8), ("fbstp", 10), ("fistp", 8)],
+];
+
+/* operands that are spelled out rather than encoded */
+fn is_fixed(spec: &str) -> bool {
+    match spec {
+        "AL" | "CL" | "DX" | "rAX" | "eAX" | "XMM0" | "1" => true,
+        _ => false,
+    }
+}
+
+fn gpr(size: usize, n: usize, rex: u8) -> &'static str {
+    match size {
+        1 if rex == 0 && n < 8 => REG8_LEGACY[n],
+        1 => REG8[n & 15],
+        2 => REG16[n & 15],
+        4 => REG32[n & 15],
+        _ => REG64[n & 15],
+    }
+}
+
+/* bmi and friends sit in the vex maps without being vector instructions */
+fn is_gpr_only(name: &str) -> bool {
+    match name {
+        "crc32" | "movbe" | "popcnt" | "lzcnt" | "tzcnt" | "rorx" => true,
+        _ => false,
+    }
+}
+
+/* evex spells the element size into some names */
+fn evex_name(name: &str, op: u8, map: u8, w: bool) -> String {
+
+    let size = if w { "64" } else { "32" };
+
+    match (map, op, name) {
+        (1, 0x6f, "vmovdqa") | (1, 0x7f, "vmovdqa") => format!("vmovdqa{}", size),
+        (1, 0x6f, "vmovdqu") | (1, 0x7f, "vmovdqu") => format!("vmovdqu{}", size),
+        /* f2 0f 6f, byte and word moves */
+        (1, 0x6f, "(bad)") | (1, 0x7f, "(bad)") => format!("vmovdqu{}", if w { "16" } else { "8" }),
+        (_, _, "vextractf128") | (_, _, "vextracti128") | (_, _, "vinsertf128") | (_, _, "vinserti128") |
+        (_, _, "vbroadcastf128") | (_, _, "vbroadcasti128") => {
+            name.replace("128", if w { "64x2" } else { "32x4" })
+        },
+        (1, 0xdb, _) | (1, 0xdf, _) | (1, 0xeb, _) | (1, 0xef, _) => {
+            format!("{}{}", name, if w { "q" } else { "d" })
+        },
+        _ => name.to_string(),
+    }
+}
+
+fn is_evex_compare(map: u8, op: u8) -> bool {
+    match (map, op) {
+        (1, 0x64..=0x66) | (1, 0x74..=0x76) | (2, 0x29) | (2, 0x37) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /* encodings, with the lengths objdump gives them */
+    const LENGTHS: &[(&[u8], usize, &str)] = &[
+        (&[0x90], 1, "nop"),
+        (&[0xc3], 1, "ret"),
+        (&[0xcc], 1, "int3"),
+        (&[0x55], 1, "push rbp"),
+        (&[0x41, 0x57], 2, "push r15"),
+        (&[0x48, 0x89, 0xe5], 3, "mov rbp, rsp"),
+        (&[0x48, 0x83, 0xec, 0x10], 4, "sub rsp, 0x10"),
+        (&[0x89, 0x7d, 0xfc], 3, "mov dword ptr [rbp-0x4], edi"),
+        (&[0xe8, 0x00, 0x00, 0x00, 0x00], 5, "call 0x1005"),
+        (&[0xeb, 0xfe], 2, "jmp 0x1000"),
+        (&[0x0f, 0x84, 0x10, 0x00, 0x00, 0x00], 6, "je 0x1016"),
+        (&[0x48, 0x8d, 0x05, 0x10, 0x00, 0x00, 0x00], 7, "lea rax, [rip+0x10] # 0x1017"),
+        (&[0x48, 0xb8, 1, 2, 3, 4, 5, 6, 7, 8], 10, "movabs rax, 0x807060504030201"),
+        (&[0x66, 0x0f, 0x1f, 0x44, 0x00, 0x00], 6, "nop word ptr [rax+rax*1]"),
+        (&[0xc7, 0x44, 0x24, 0x08, 0x01, 0x00, 0x00, 0x00], 8, "mov dword ptr [rsp+0x8], 0x1"),
+        (&[0xf3, 0x48, 0xab], 3, "rep stosq"),
+        (&[0x0f, 0x05], 2, "syscall"),
+        (&[0xf2, 0x0f, 0x59, 0xc1], 4, "mulsd xmm0, xmm1"),
+        (&[0xc5, 0xfd, 0x74, 0xc1], 4, "vpcmpeqb ymm0, ymm0, ymm1"),
+        (&[0xdb, 0x6d, 0x10], 3, "fld tbyte ptr [rbp+0x10]"),
+    ];
+
+    #[test]
+    fn lengths() {
+        for &(bytes, len, text) in LENGTHS {
+            let mut code = bytes.to_vec();
+            code.extend_from_slice(&[0x90; MAX_LEN]);
+
+            let insn = decode(&code, 0x1000).unwrap();
+            assert_eq!(insn.len(), len, "{:02x?}", bytes);
+            /* the mnemonic is padded to a column */
+            let words: Vec<String> = insn.to_string().split_whitespace().map(String::from).collect();
+            assert_eq!(words.join(" "), text, "{:02x?}", bytes);
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(decode(&[0x48, 0x89], 0).is_none());
+        assert!(decode(&[0xe8, 0x00, 0x00], 0).is_none());
+    }
+
+    #[test]
+    fn too_long() {
+        /* prefixes past the 15 byte limit */
+        let code = [0x66; 16];
+        let insn = decode(&code, 0).unwrap();
+        assert_eq!(insn.mnemonic, "(bad)");
+        assert_eq!(insn.len(), 1);
+    }
+
+    #[test]
+    fn branches() {
+        let insns = disassemble(&[0x55, 0xe8, 0xfa, 0xff, 0xff, 0xff, 0xc3], 0x1000, 10);
+        assert_eq!(insns.len(), 3);
+        assert!(insns[1].is_call());
+        assert_eq!(insns[1].target(), Some(0x1000));
+        assert_eq!(insns[2].mnemonic, "ret");
+        assert_eq!(insns[2].next(), 0x1007);
+    }
+}
@@ -0,0 +1,169 @@
+use error::DebugError;
+
+/* attribute forms, DWARF 5 section 7.5.6 */
+pub const DW_FORM_ADDR: u64 = 0x01;
+pub const DW_FORM_BLOCK2: u64 = 0x03;
+pub const DW_FORM_BLOCK4: u64 = 0x04;
+pub const DW_FORM_DATA2: u64 = 0x05;
+pub const DW_FORM_DATA4: u64 = 0x06;
+pub const DW_FORM_DATA8: u64 = 0x07;
+pub const DW_FORM_STRING: u64 = 0x08;
+pub const DW_FORM_BLOCK: u64 = 0x09;
+pub const DW_FORM_BLOCK1: u64 = 0x0a;
+pub const DW_FORM_DATA1: u64 = 0x0b;
+pub const DW_FORM_FLAG: u64 = 0x0c;
+pub const DW_FORM_SDATA: u64 = 0x0d;
+pub const DW_FORM_STRP: u64 = 0x0e;
+pub const DW_FORM_UDATA: u64 = 0x0f;
+pub const DW_FORM_REF_ADDR: u64 = 0x10;
+pub const DW_FORM_REF1: u64 = 0x11;
+pub const DW_FORM_REF2: u64 = 0x12;
+pub const DW_FORM_REF4: u64 = 0x13;
+pub const DW_FORM_REF8: u64 = 0x14;
+pub const DW_FORM_REF_UDATA: u64 = 0x15;
+pub const DW_FORM_INDIRECT: u64 = 0x16;
+pub const DW_FORM_SEC_OFFSET: u64 = 0x17;
+pub const DW_FORM_EXPRLOC: u64 = 0x18;
+pub const DW_FORM_FLAG_PRESENT: u64 = 0x19;
+pub const DW_FORM_STRX: u64 = 0x1a;
+pub const DW_FORM_ADDRX: u64 = 0x1b;
+pub const DW_FORM_REF_SUP4: u64 = 0x1c;
+pub const DW_FORM_STRP_SUP: u64 = 0x1d;
+pub const DW_FORM_DATA16: u64 = 0x1e;
+pub const DW_FORM_LINE_STRP: u64 = 0x1f;
+pub const DW_FORM_REF_SIG8: u64 = 0x20;
+pub const DW_FORM_IMPLICIT_CONST: u64 = 0x21;
+pub const DW_FORM_LOCLISTX: u64 = 0x22;
+pub const DW_FORM_RNGLISTX: u64 = 0x23;
+pub const DW_FORM_REF_SUP8: u64 = 0x24;
+pub const DW_FORM_STRX1: u64 = 0x25;
+pub const DW_FORM_STRX2: u64 = 0x26;
+pub const DW_FORM_STRX3: u64 = 0x27;
+pub const DW_FORM_STRX4: u64 = 0x28;
+pub const DW_FORM_ADDRX1: u64 = 0x29;
+pub const DW_FORM_ADDRX2: u64 = 0x2a;
+pub const DW_FORM_ADDRX3: u64 = 0x2b;
+pub const DW_FORM_ADDRX4: u64 = 0x2c;
+
+/* little endian cursor over a debug section */
+#[derive(Debug,Clone,Copy)]
+pub struct Reader<'a> {
+    pub data: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8], pos: usize) -> Self {
+        Reader {
+            data: data,
+            pos: pos,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], DebugError> {
+        let bytes = self.data.get(self.pos..self.pos + len)
+            .ok_or("Truncated debug information")?;
+
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn skip(&mut self, len: usize) -> Result<(), DebugError> {
+        self.bytes(len).map(|_| ())
+    }
+
+    /* n byte little endian value, n up to 8 */
+    pub fn uint(&mut self, len: usize) -> Result<u64, DebugError> {
+        Ok(self.bytes(len)?.iter().rev().fold(0, |value, &b| value << 8 | b as u64))
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DebugError> {
+        Ok(self.uint(1)? as u8)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, DebugError> {
+        Ok(self.uint(2)? as u16)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, DebugError> {
+        Ok(self.uint(4)? as u32)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, DebugError> {
+        self.uint(8)
+    }
+
+    pub fn uleb(&mut self) -> Result<u64, DebugError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+
+        loop {
+            let b = self.u8()?;
+
+            if shift < 64 {
+                value |= ((b & 0x7f) as u64) << shift;
+            }
+            shift += 7;
+
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    pub fn sleb(&mut self) -> Result<i64, DebugError> {
+        let mut value = 0i64;
+        let mut shift = 0;
+
+        loop {
+            let b = self.u8()?;
+
+            if shift < 64 {
+                value |= ((b & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    value |= -1i64 << shift;
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    /* nul terminated */
+    pub fn cstr(&mut self) -> Result<&'a str, DebugError> {
+        let rest = self.data.get(self.pos..).ok_or("Truncated debug information")?;
+        let len = rest.iter().position(|&b| b == 0).ok_or("Unterminated string")?;
+
+        self.pos += len + 1;
+        Ok(::std::str::from_utf8(&rest[..len])?)
+    }
+
+    /* unit_length, the end of the unit and whether it is 64 bit DWARF */
+    pub fn unit_length(&mut self) -> Result<(usize, bool), DebugError> {
+        let len = self.u32()? as u64;
+
+        if len == 0xffffffff {
+            let len = self.u64()? as usize;
+            Ok((self.pos + len, true))
+        } else {
+            Ok((self.pos + len as usize, false))
+        }
+    }
+
+    /* section offsets are 4 bytes, 8 in 64 bit DWARF */
+    pub fn offset(&mut self, dwarf64: bool) -> Result<u64, DebugError> {
+        self.uint(if dwarf64 { 8 } else { 4 })
+    }
+}
+
+/* nul terminated string at offset into .debug_str or .debug_line_str */
+pub fn string_at(section: &[u8], offset: u64) -> Result<&str, DebugError> {
+    Reader::new(section, offset as usize).cstr()
+}
@@ -0,0 +1,308 @@
+use std::fs::File;
+use std::io::Read;
+
+use error::DebugError;
+
+/* just the 64 bit little endian ELF files x86_64 runs */
+
+pub const ET_EXEC: u16 = 2;
+pub const ET_DYN: u16 = 3;
+
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_NOBITS: u32 = 8;
+pub const SHT_DYNSYM: u32 = 11;
+
+pub const PT_LOAD: u32 = 1;
+pub const PT_DYNAMIC: u32 = 2;
+pub const PT_INTERP: u32 = 3;
+pub const PT_PHDR: u32 = 6;
+
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_GNU_IFUNC: u8 = 10;
+const STB_LOCAL: u8 = 0;
+const SHN_UNDEF: u16 = 0;
+
+#[derive(Debug,Clone)]
+pub struct Section {
+    pub name: String,
+    pub kind: u32,
+    pub flags: u64,
+    pub addr: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub entsize: u64,
+}
+
+#[derive(Debug,Clone)]
+pub struct Segment {
+    pub kind: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub filesz: u64,
+    pub memsz: u64,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum SymbolKind {
+    Function,
+    Object,
+    Other,
+}
+
+#[derive(Debug,Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub addr: u64,
+    pub size: u64,
+    pub kind: SymbolKind,
+    pub global: bool,
+    /* from .dynsym rather than .symtab */
+    pub dynamic: bool,
+}
+
+pub struct Elf {
+    pub kind: u16,
+    pub entry: u64,
+    pub phoff: u64,
+    pub sections: Vec<Section>,
+    pub segments: Vec<Segment>,
+    /* defined symbols only, sorted by address */
+    pub symbols: Vec<Symbol>,
+    data: Vec<u8>,
+}
+
+impl Elf {
+    pub fn load(path: &str) -> Result<Elf, DebugError> {
+
+        let mut data = vec![];
+        File::open(path)?.read_to_end(&mut data)?;
+
+        Elf::parse(data)
+    }
+
+    pub fn parse(data: Vec<u8>) -> Result<Elf, DebugError> {
+
+        if data.len() < 64 || &data[..4] != b"\x7fELF" {
+            return Err("Not an ELF file".into());
+        }
+
+        /* ELFCLASS64, ELFDATA2LSB */
+        if data[4] != 2 || data[5] != 1 {
+            return Err("Only 64 bit little endian ELF files are supported".into());
+        }
+
+        let kind = u16_at(&data, 16)?;
+        let entry = u64_at(&data, 24)?;
+        let phoff = u64_at(&data, 32)?;
+        let shoff = u64_at(&data, 40)? as usize;
+        let phentsize = u16_at(&data, 54)? as usize;
+        let phnum = u16_at(&data, 56)? as usize;
+        let shentsize = u16_at(&data, 58)? as usize;
+        let shnum = u16_at(&data, 60)? as usize;
+        let shstrndx = u16_at(&data, 62)? as usize;
+
+        let mut segments = vec![];
+
+        for i in 0..phnum {
+            let at = phoff as usize + i * phentsize;
+
+            segments.push(Segment {
+                kind: u32_at(&data, at)?,
+                flags: u32_at(&data, at + 4)?,
+                offset: u64_at(&data, at + 8)?,
+                vaddr: u64_at(&data, at + 16)?,
+                filesz: u64_at(&data, at + 32)?,
+                memsz: u64_at(&data, at + 40)?,
+            });
+        }
+
+        let mut sections = vec![];
+        let mut names = vec![];
+
+        /* stripped of section headers is still a valid file */
+        for i in 0..shnum {
+            let at = shoff + i * shentsize;
+
+            names.push(u32_at(&data, at)?);
+            sections.push(Section {
+                name: String::new(),
+                kind: u32_at(&data, at + 4)?,
+                flags: u64_at(&data, at + 8)?,
+                addr: u64_at(&data, at + 16)?,
+                offset: u64_at(&data, at + 24)?,
+                size: u64_at(&data, at + 32)?,
+                link: u32_at(&data, at + 40)?,
+                entsize: u64_at(&data, at + 56)?,
+            });
+        }
+
+        if let Some(strtab) = sections.get(shstrndx).cloned() {
+            for (section, &name) in sections.iter_mut().zip(names.iter()) {
+                section.name = string_at(&data, &strtab, name)?;
+            }
+        }
+
+        let mut elf = Elf {
+            kind: kind,
+            entry: entry,
+            phoff: phoff,
+            sections: sections,
+            segments: segments,
+            symbols: vec![],
+            data: data,
+        };
+
+        elf.symbols = elf.read_symbols()?;
+
+        Ok(elf)
+    }
+
+    fn read_symbols(&self) -> Result<Vec<Symbol>, DebugError> {
+
+        let mut symbols = vec![];
+
+        for section in self.sections.iter() {
+
+            if section.kind != SHT_SYMTAB && section.kind != SHT_DYNSYM {
+                continue;
+            }
+
+            let strtab = match self.sections.get(section.link as usize) {
+                Some(strtab) => strtab,
+                None => { continue; },
+            };
+
+            let entsize = if section.entsize > 0 { section.entsize } else { 24 };
+
+            for i in 1..(section.size / entsize) {
+                let at = (section.offset + i * entsize) as usize;
+
+                let info = *self.data.get(at + 4).ok_or("Truncated symbol table")?;
+                let shndx = u16_at(&self.data, at + 6)?;
+                let addr = u64_at(&self.data, at + 8)?;
+
+                if shndx == SHN_UNDEF || addr == 0 {
+                    continue;
+                }
+
+                let kind = match info & 0xf {
+                    STT_FUNC | STT_GNU_IFUNC => SymbolKind::Function,
+                    STT_OBJECT => SymbolKind::Object,
+                    _ => SymbolKind::Other,
+                };
+
+                let name = string_at(&self.data, strtab, u32_at(&self.data, at)?)?;
+
+                if name.is_empty() {
+                    continue;
+                }
+
+                symbols.push(Symbol {
+                    name: name,
+                    addr: addr,
+                    size: u64_at(&self.data, at + 16)?,
+                    kind: kind,
+                    global: info >> 4 != STB_LOCAL,
+                    dynamic: section.kind == SHT_DYNSYM,
+                });
+            }
+        }
+
+        /*
+         *  .dynsym repeats what .symtab has, the last one of an address wins
+         *  lookups: the largest, functions, globals, then malloc over __libc_malloc
+         */
+        symbols.sort_by_key(|sym| (sym.addr, sym.size, sym.kind == SymbolKind::Function,
+            sym.global, !sym.name.starts_with("_"), sym.name.clone()));
+        symbols.dedup_by(|a, b| a.addr == b.addr && a.name == b.name);
+
+        Ok(symbols)
+    }
+
+    pub fn is_pie(&self) -> bool {
+        self.kind == ET_DYN
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /* file contents of a section, empty for .bss and the like */
+    pub fn section_data(&self, section: &Section) -> &[u8] {
+
+        if section.kind == SHT_NOBITS {
+            return &[];
+        }
+
+        let start = section.offset as usize;
+        let end = start + section.size as usize;
+
+        self.data.get(start..end).unwrap_or(&[])
+    }
+
+    /* section containing a link time address */
+    pub fn section_at(&self, addr: u64) -> Option<&Section> {
+        self.sections.iter()
+            .find(|section| section.addr != 0 && addr >= section.addr && addr < section.addr + section.size)
+    }
+
+    /* functions first, e.g. for names that are also a local label */
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter()
+            .filter(|sym| sym.name == name)
+            .min_by_key(|sym| (sym.kind != SymbolKind::Function, !sym.global))
+    }
+
+    /* symbol covering addr and the offset into it */
+    pub fn lookup(&self, addr: u64) -> Option<(&Symbol, u64)> {
+
+        let end = match self.symbols.binary_search_by(|sym| sym.addr.cmp(&addr)) {
+            Ok(i) => self.symbols[i..].iter().take_while(|sym| sym.addr == addr).count() + i,
+            Err(i) => i,
+        };
+
+        /* nested symbols start later, so look back from the closest one */
+        self.symbols[..end].iter().rev()
+            .take_while(|sym| addr - sym.addr < 0x100000)
+            .find(|sym| sym.kind != SymbolKind::Other && (addr < sym.addr + sym.size || addr == sym.addr))
+            .map(|sym| (sym, addr - sym.addr))
+    }
+
+    /* main+0x1b, or None if no symbol covers addr */
+    pub fn symbolize(&self, addr: u64) -> Option<String> {
+        self.lookup(addr).map(|(sym, offset)| match offset {
+            0 => sym.name.clone(),
+            offset => format!("{}+0x{:x}", sym.name, offset),
+        })
+    }
+}
+
+fn u16_at(data: &[u8], at: usize) -> Result<u16, DebugError> {
+    let b = data.get(at..at + 2).ok_or("Truncated ELF file")?;
+    Ok(b[0] as u16 | (b[1] as u16) << 8)
+}
+
+fn u32_at(data: &[u8], at: usize) -> Result<u32, DebugError> {
+    let b = data.get(at..at + 4).ok_or("Truncated ELF file")?;
+    Ok(b.iter().rev().fold(0, |value, &b| value << 8 | b as u32))
+}
+
+fn u64_at(data: &[u8], at: usize) -> Result<u64, DebugError> {
+    let b = data.get(at..at + 8).ok_or("Truncated ELF file")?;
+    Ok(b.iter().rev().fold(0, |value, &b| value << 8 | b as u64))
+}
+
+/* nul terminated string at offset into a string table */
+fn string_at(data: &[u8], strtab: &Section, offset: u32) -> Result<String, DebugError> {
+
+    let start = strtab.offset as usize + offset as usize;
+    let table = data.get(start..(strtab.offset + strtab.size) as usize)
+        .ok_or("String outside of its table")?;
+
+    let len = table.iter().position(|&b| b == 0).unwrap_or(table.len());
+
+    Ok(String::from_utf8_lossy(&table[..len]).into_owned())
+}
@@ -0,0 +1,60 @@
+use nix::sys::signal::Signal;
+
+use status::PtraceEvent;
+use syscall::Syscall;
+use watchpoint::WatchKind;
+use solib::LibraryChange;
+
+/* the useful part of a siginfo_t */
+#[derive(Debug,Clone,Copy)]
+pub struct SigInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    /* faulting address for SIGSEGV, SIGBUS, SIGILL and SIGFPE */
+    pub addr: u64,
+}
+
+/* why the process stopped, or why it is gone */
+#[derive(Debug,Clone)]
+pub enum StopEvent {
+    Breakpoint { name: String, addr: u64 },
+    Step { addr: u64 },
+    /* addr is the watched address, not the instruction */
+    Watchpoint { name: String, addr: u64, kind: WatchKind },
+    /* software watchpoint, pc is the instruction that wrote */
+    WatchChange { name: String, addr: u64, pc: u64, old: Vec<u8>, new: Vec<u8> },
+    Signal { signo: i32, info: SigInfo },
+    Exited(i32),
+    Killed(i32),
+    Ptrace(PtraceEvent),
+    /* fretval is the low double of xmm0, only for calls made with phantom_call_float */
+    PhantomReturn { retval: u64, fretval: Option<f64> },
+    /* finish, the function returned to addr */
+    Return { addr: u64, retval: u64 },
+    /* run_until got there */
+    Reached { addr: u64 },
+    /* entry or exit, see Syscall::is_entry */
+    Syscall(Syscall),
+    /* dlopen or dlclose, see Debugger::stop_on_libraries */
+    Library(LibraryChange),
+}
+
+impl StopEvent {
+    pub fn signal(&self) -> Option<Signal> {
+        match self {
+            &StopEvent::Signal { signo, .. } | &StopEvent::Killed(signo) => {
+                Signal::from_c_int(signo).ok()
+            },
+            _ => None,
+        }
+    }
+
+    /* the process is gone, nothing left to continue */
+    pub fn is_exit(&self) -> bool {
+        match self {
+            &StopEvent::Exited(_) | &StopEvent::Killed(_) => true,
+            _ => false,
+        }
+    }
+}
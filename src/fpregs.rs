@@ -0,0 +1,442 @@
+use std::fmt;
+use std::arch::x86_64::__cpuid_count;
+
+use error::DebugError;
+
+/* regset of the XSAVE area, for PTRACE_GETREGSET */
+pub const NT_X86_XSTATE: usize = 0x202;
+
+/* user_fpregs_struct, the legacy FXSAVE area XSAVE starts with too */
+pub const FXSAVE_SIZE: usize = 512;
+
+/* big enough for every state component up to AVX-512, the kernel says how much it used */
+pub const XSAVE_MAX: usize = 0x3000;
+
+const FCW: usize = 0;
+const FSW: usize = 2;
+const FTW: usize = 4;
+const MXCSR: usize = 24;
+const ST_SPACE: usize = 32;
+const XMM_SPACE: usize = 160;
+/* xstate_bv in the XSAVE header */
+const XSTATE_BV: usize = 512;
+/* state components, x87, then xmm0-15, then the upper halves of ymm0-15 */
+const X87_COMPONENT: u32 = 0;
+const SSE_COMPONENT: u32 = 1;
+const YMM_COMPONENT: u32 = 2;
+
+/* where the non-compacted XSAVE layout keeps the ymm upper halves, None without AVX */
+pub fn ymm_offset() -> Option<usize> {
+
+    if __cpuid_count(0xd, 0).eax & (1 << YMM_COMPONENT) == 0 {
+        return None;
+    }
+
+    Some(__cpuid_count(0xd, YMM_COMPONENT).ebx as usize)
+}
+
+/* 16 bytes of an xmm register, or half of a ymm one, as typed lanes */
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub struct Vector(pub [u8; 16]);
+
+impl Vector {
+    pub fn from_u8x16(lanes: [u8; 16]) -> Self {
+        Vector(lanes)
+    }
+
+    pub fn from_f32x4(lanes: [f32; 4]) -> Self {
+        let mut bytes = [0u8; 16];
+
+        for (i, lane) in lanes.iter().enumerate() {
+            bytes[i*4..i*4+4].copy_from_slice(&lane.to_bits().to_le_bytes());
+        }
+
+        Vector(bytes)
+    }
+
+    pub fn from_f64x2(lanes: [f64; 2]) -> Self {
+        let mut bytes = [0u8; 16];
+
+        for (i, lane) in lanes.iter().enumerate() {
+            bytes[i*8..i*8+8].copy_from_slice(&lane.to_bits().to_le_bytes());
+        }
+
+        Vector(bytes)
+    }
+
+    /* a scalar double argument or return value, the upper lane zeroed */
+    pub fn from_f64(value: f64) -> Self {
+        Vector::from_f64x2([value, 0.0])
+    }
+
+    pub fn u8x16(&self) -> [u8; 16] {
+        self.0
+    }
+
+    pub fn f32x4(&self) -> [f32; 4] {
+        let mut lanes = [0f32; 4];
+
+        for (i, lane) in lanes.iter_mut().enumerate() {
+            *lane = f32::from_bits(le_u32(&self.0[i*4..]));
+        }
+
+        lanes
+    }
+
+    pub fn f64x2(&self) -> [f64; 2] {
+        let mut lanes = [0f64; 2];
+
+        for (i, lane) in lanes.iter_mut().enumerate() {
+            *lane = f64::from_bits(le_u64(&self.0[i*8..]));
+        }
+
+        lanes
+    }
+}
+
+/* lowest lane first, like gdb's v16_int8 */
+impl fmt::Display for Vector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+
+        for (i, byte) in self.0.iter().enumerate() {
+            write!(f, "{}0x{:02x}", if i == 0 { "" } else { ", " }, byte)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+fn le_u16(bytes: &[u8]) -> u16 {
+    bytes[0] as u16 | (bytes[1] as u16) << 8
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    let mut value = [0u8; 4];
+    value.copy_from_slice(&bytes[..4]);
+    u32::from_le_bytes(value)
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(value)
+}
+
+/* an 80 bit x87 extended value to the nearest double */
+pub fn extended_to_f64(raw: &[u8; 10]) -> f64 {
+
+    let mantissa = le_u64(&raw[..8]);
+    let sign_exp = le_u16(&raw[8..]);
+    let sign = if sign_exp & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = (sign_exp & 0x7fff) as i32;
+
+    let value = match exp {
+        0 if mantissa == 0 => 0.0,
+        0x7fff if mantissa << 1 == 0 => ::std::f64::INFINITY,
+        0x7fff => ::std::f64::NAN,
+        _ => {
+            /* the integer bit is explicit, so this is mantissa * 2^(exp - bias - 63) */
+            let scale = exp - 16383 - 63;
+            let value = mantissa as f64;
+
+            /* split so an intermediate power of two doesn't underflow on its own */
+            if scale < -1000 {
+                value * 2f64.powi(-1000) * 2f64.powi(scale + 1000)
+            } else {
+                value * 2f64.powi(scale)
+            }
+        },
+    };
+
+    sign * value
+}
+
+/* a double widened to the 80 bit x87 format, exactly */
+pub fn f64_to_extended(value: f64) -> [u8; 10] {
+
+    let bits = value.to_bits();
+    let sign = ((bits >> 63) as u16) << 15;
+    let exp = ((bits >> 52) & 0x7ff) as u16;
+    let fraction = bits & ((1 << 52) - 1);
+
+    let (exp, mantissa) = match exp {
+        0 if fraction == 0 => (0, 0),
+        /* subnormal doubles are normal numbers in the wider exponent */
+        0 => {
+            let shift = fraction.leading_zeros();
+            (15372 - shift as u16, fraction << shift)
+        },
+        0x7ff => (0x7fff, 1 << 63 | fraction << 11),
+        _ => (exp + 16383 - 1023, 1 << 63 | fraction << 11),
+    };
+
+    let mut raw = [0u8; 10];
+    raw[..8].copy_from_slice(&mantissa.to_le_bytes());
+    raw[8..].copy_from_slice(&(sign | exp).to_le_bytes());
+
+    raw
+}
+
+/*
+ *  x87, SSE and, when read through XSTATE, AVX registers of a thread.
+ *  Process::getfpregs fills the 512 byte FXSAVE image, Process::getxstate
+ *  the whole XSAVE area which starts with the same image
+ */
+#[derive(Debug,Clone)]
+pub struct FpRegisters {
+    area: Vec<u8>,
+    /* offset of the ymm upper halves in area, None if they weren't read */
+    ymm: Option<usize>,
+}
+
+impl FpRegisters {
+    pub fn from_fxsave(area: Vec<u8>) -> Result<Self, DebugError> {
+
+        if area.len() < FXSAVE_SIZE {
+            return Err("FXSAVE area too short".into());
+        }
+
+        Ok(FpRegisters {
+            area: area,
+            ymm: None,
+        })
+    }
+
+    pub fn from_xsave(area: Vec<u8>) -> Result<Self, DebugError> {
+
+        let mut regs = FpRegisters::from_fxsave(area)?;
+        regs.ymm = ymm_offset().filter(|&offset| offset + 16*16 <= regs.area.len());
+
+        Ok(regs)
+    }
+
+    /* the raw image, what setfpregs or setxstate write back */
+    pub fn area(&self) -> &[u8] {
+        &self.area
+    }
+
+    /* read through getxstate rather than getfpregs */
+    pub fn is_xstate(&self) -> bool {
+        self.area.len() > FXSAVE_SIZE
+    }
+
+    pub fn has_ymm(&self) -> bool {
+        self.ymm.is_some()
+    }
+
+    /* x87 control word */
+    pub fn fcw(&self) -> u16 {
+        le_u16(&self.area[FCW..])
+    }
+
+    /* x87 status word, top of stack is bits 11-13 */
+    pub fn fsw(&self) -> u16 {
+        le_u16(&self.area[FSW..])
+    }
+
+    /* abridged tag word, a bit per physical register set when it isn't empty */
+    pub fn ftw(&self) -> u8 {
+        self.area[FTW]
+    }
+
+    pub fn mxcsr(&self) -> u32 {
+        le_u32(&self.area[MXCSR..])
+    }
+
+    pub fn set_mxcsr(&mut self, mxcsr: u32) {
+        self.area[MXCSR..MXCSR+4].copy_from_slice(&mxcsr.to_le_bytes());
+    }
+
+    /* st(i) as the 80 bits it is stored as, relative to the top of stack */
+    pub fn st_raw(&self, i: usize) -> Result<[u8; 10], DebugError> {
+
+        if i >= 8 {
+            return Err("no such x87 register".into());
+        }
+
+        let mut raw = [0u8; 10];
+        raw.copy_from_slice(&self.area[ST_SPACE+i*16..ST_SPACE+i*16+10]);
+
+        Ok(raw)
+    }
+
+    pub fn st(&self, i: usize) -> Result<f64, DebugError> {
+        Ok(extended_to_f64(&self.st_raw(i)?))
+    }
+
+    /* also tags the register valid, otherwise the fpu would treat it as empty */
+    pub fn set_st_raw(&mut self, i: usize, raw: [u8; 10]) -> Result<(), DebugError> {
+
+        if i >= 8 {
+            return Err("no such x87 register".into());
+        }
+
+        self.area[ST_SPACE+i*16..ST_SPACE+i*16+10].copy_from_slice(&raw);
+
+        let top = (self.fsw() >> 11) as usize & 7;
+        self.area[FTW] |= 1 << ((top + i) % 8);
+        self.in_use(X87_COMPONENT);
+
+        Ok(())
+    }
+
+    pub fn set_st(&mut self, i: usize, value: f64) -> Result<(), DebugError> {
+        self.set_st_raw(i, f64_to_extended(value))
+    }
+
+    pub fn xmm(&self, i: usize) -> Result<Vector, DebugError> {
+
+        if i >= 16 {
+            return Err("no such xmm register".into());
+        }
+
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&self.area[XMM_SPACE+i*16..XMM_SPACE+i*16+16]);
+
+        Ok(Vector(bytes))
+    }
+
+    pub fn set_xmm(&mut self, i: usize, value: Vector) -> Result<(), DebugError> {
+
+        if i >= 16 {
+            return Err("no such xmm register".into());
+        }
+
+        self.area[XMM_SPACE+i*16..XMM_SPACE+i*16+16].copy_from_slice(&value.0);
+        self.in_use(SSE_COMPONENT);
+
+        Ok(())
+    }
+
+    /* bits 128-255 of ymm(i), only there when read through getxstate */
+    pub fn ymm_high(&self, i: usize) -> Result<Vector, DebugError> {
+
+        let offset = self.ymm_high_offset(i)?;
+
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&self.area[offset..offset+16]);
+
+        Ok(Vector(bytes))
+    }
+
+    /* marks the ymm state as in use, see in_use */
+    pub fn set_ymm_high(&mut self, i: usize, value: Vector) -> Result<(), DebugError> {
+
+        let offset = self.ymm_high_offset(i)?;
+        self.area[offset..offset+16].copy_from_slice(&value.0);
+        self.in_use(YMM_COMPONENT);
+
+        Ok(())
+    }
+
+    /* low half then high half */
+    pub fn ymm(&self, i: usize) -> Result<[Vector; 2], DebugError> {
+        Ok([self.xmm(i)?, self.ymm_high(i)?])
+    }
+
+    pub fn set_ymm(&mut self, i: usize, value: [Vector; 2]) -> Result<(), DebugError> {
+        self.set_xmm(i, value[0])?;
+        self.set_ymm_high(i, value[1])
+    }
+
+    /*
+     *  a component not marked in xstate_bv is in its init state, the
+     *  kernel ignores what was written there. The FXSAVE image has no header
+     */
+    fn in_use(&mut self, component: u32) {
+        if self.is_xstate() {
+            self.area[XSTATE_BV] |= 1 << component;
+        }
+    }
+
+    fn ymm_high_offset(&self, i: usize) -> Result<usize, DebugError> {
+
+        if i >= 16 {
+            return Err("no such ymm register".into());
+        }
+
+        match self.ymm {
+            Some(offset) => Ok(offset + i*16),
+            None => Err("ymm state not available, read it with getxstate".into()),
+        }
+    }
+}
+
+impl fmt::Display for FpRegisters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        writeln!(f, "fcw 0x{:04x} fsw 0x{:04x} ftw 0x{:02x} mxcsr 0x{:08x}",
+                 self.fcw(), self.fsw(), self.ftw(), self.mxcsr())?;
+
+        for i in 0..8 {
+            writeln!(f, "st{} {}", i, self.st(i).unwrap_or(0.0))?;
+        }
+
+        for i in 0..16 {
+            match self.ymm_high(i) {
+                Ok(high) => writeln!(f, "ymm{} {} {}", i, self.xmm(i).unwrap_or_default(), high)?,
+                Err(_) => writeln!(f, "xmm{} {}", i, self.xmm(i).unwrap_or_default())?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /* an XSAVE area with every component in its init state */
+    fn init_xstate() -> FpRegisters {
+        FpRegisters::from_xsave(vec![0; XSAVE_MAX]).unwrap()
+    }
+
+    #[test]
+    fn xmm_marks_sse_in_use() {
+        let mut regs = init_xstate();
+        assert_eq!(regs.area()[XSTATE_BV], 0);
+
+        regs.set_xmm(3, Vector::from_f64(2.5)).unwrap();
+        assert_eq!(regs.area()[XSTATE_BV], 1 << SSE_COMPONENT);
+
+        let regs = FpRegisters::from_xsave(regs.area().to_vec()).unwrap();
+        assert_eq!(regs.xmm(3).unwrap().f64x2(), [2.5, 0.0]);
+    }
+
+    #[test]
+    fn st_marks_x87_in_use() {
+        let mut regs = init_xstate();
+
+        regs.set_st(0, -1.25).unwrap();
+        assert_eq!(regs.area()[XSTATE_BV], 1 << X87_COMPONENT);
+
+        let regs = FpRegisters::from_xsave(regs.area().to_vec()).unwrap();
+        assert_eq!(regs.st(0).unwrap(), -1.25);
+        assert_eq!(regs.ftw(), 1);
+    }
+
+    #[test]
+    fn fxsave_has_no_header() {
+        let mut regs = FpRegisters::from_fxsave(vec![0; FXSAVE_SIZE]).unwrap();
+
+        regs.set_xmm(0, Vector::from_f32x4([1.0, 2.0, 3.0, 4.0])).unwrap();
+        regs.set_st(1, 3.0).unwrap();
+
+        assert_eq!(regs.area().len(), FXSAVE_SIZE);
+        assert_eq!(regs.xmm(0).unwrap().f32x4(), [1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(regs.st(1).unwrap(), 3.0);
+        assert!(regs.ymm(0).is_err());
+    }
+
+    #[test]
+    fn extended_round_trip() {
+        for &value in [0.0, 1.0, -2.5, 1e300, -1e-300, 5e-324, ::std::f64::INFINITY].iter() {
+            assert_eq!(extended_to_f64(&f64_to_extended(value)), value);
+        }
+
+        assert!(extended_to_f64(&f64_to_extended(::std::f64::NAN)).is_nan());
+    }
+}
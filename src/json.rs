@@ -0,0 +1,42 @@
+/* just enough JSON to write out what the debugger records */
+
+pub fn string(s: &str) -> String {
+
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+/* raw bytes go out as a hex string */
+pub fn bytes(data: &[u8]) -> String {
+    let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("\"{}\"", hex)
+}
+
+/* keys are written as given, values must already be JSON */
+pub fn object(fields: &[(&str, String)]) -> String {
+
+    let fields: Vec<String> = fields.iter()
+        .map(|&(key, ref value)| format!("{}:{}", string(key), value))
+        .collect();
+
+    format!("{{{}}}", fields.join(","))
+}
+
+pub fn array(values: &[String]) -> String {
+    format!("[{}]", values.join(","))
+}
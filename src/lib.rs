@@ -14,14 +14,33 @@ extern crate twoway;
 #[macro_use] extern crate bitflags;
 
 pub mod debugger;
+pub mod builder;
 pub mod registers;
+pub mod fpregs;
 pub mod error;
 pub mod processio;
 pub mod breakpoint;
+pub mod condition;
 pub mod process;
 pub mod status;
+pub mod event;
+pub mod signals;
+pub mod syscall;
+pub mod watchpoint;
+pub mod tracepoint;
+pub mod disasm;
+pub mod elf;
+pub mod module;
+pub mod solib;
+pub mod dwarf;
+pub mod line;
+pub mod debuginfo;
+pub mod unwind;
+pub mod crash;
 pub mod memory;
+pub mod thread;
 mod phantom;
+mod json;
 
 /*
 #[cfg(test)]
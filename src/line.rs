@@ -0,0 +1,565 @@
+use std::fmt;
+use std::path::Path;
+
+use dwarf::{self,Reader};
+use elf::Elf;
+use error::DebugError;
+
+/* standard opcodes */
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNS_SET_COLUMN: u8 = 5;
+const DW_LNS_NEGATE_STMT: u8 = 6;
+const DW_LNS_SET_BASIC_BLOCK: u8 = 7;
+const DW_LNS_CONST_ADD_PC: u8 = 8;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 9;
+
+/* extended opcodes */
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+const DW_LNE_DEFINE_FILE: u8 = 3;
+
+/* DWARF 5 directory and file entry contents */
+const DW_LNCT_PATH: u64 = 1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+
+/* a place in the source */
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: u64,
+    pub column: u64,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/* one row of the line number matrix */
+#[derive(Debug,Clone,Copy)]
+pub struct Row {
+    /* link time address */
+    pub addr: u64,
+    /* index into LineTable::files */
+    pub file: usize,
+    pub line: u64,
+    pub column: u64,
+    /* a recommended breakpoint location */
+    pub is_stmt: bool,
+    /* first address past a sequence, describes no instruction */
+    pub end: bool,
+}
+
+/* every line program of a file, rows sorted by address */
+pub struct LineTable {
+    pub files: Vec<String>,
+    pub rows: Vec<Row>,
+}
+
+impl LineTable {
+    pub fn parse(elf: &Elf) -> Result<LineTable, DebugError> {
+
+        let section = elf.section(".debug_line").ok_or("No .debug_line section, compile with -g")?;
+        let data = elf.section_data(section);
+        let strings = elf.section(".debug_str").map_or(&[][..], |s| elf.section_data(s));
+        let line_strings = elf.section(".debug_line_str").map_or(&[][..], |s| elf.section_data(s));
+
+        let mut table = LineTable {
+            files: vec![],
+            rows: vec![],
+        };
+
+        let mut reader = Reader::new(data, 0);
+
+        while !reader.is_empty() {
+            let (end, dwarf64) = reader.unit_length()?;
+
+            let mut program = Program {
+                reader: Reader::new(&data[..end.min(data.len())], reader.pos),
+                dwarf64: dwarf64,
+                strings: strings,
+                line_strings: line_strings,
+            };
+
+            program.run(&mut table)?;
+
+            reader.pos = end;
+        }
+
+        /* stable, so an end of sequence stays before a row starting at the same address */
+        table.rows.sort_by_key(|row| (row.addr, !row.end));
+
+        Ok(table)
+    }
+
+    /* the row an instruction belongs to */
+    pub fn row(&self, addr: u64) -> Option<&Row> {
+
+        let at = match self.rows.binary_search_by(|row| row.addr.cmp(&addr)) {
+            Ok(at) => at + self.rows[at..].iter().take_while(|row| row.addr == addr).count(),
+            Err(at) => at,
+        };
+
+        match self.rows[..at].last() {
+            Some(row) if !row.end => Some(row),
+            _ => None,
+        }
+    }
+
+    pub fn location(&self, addr: u64) -> Option<Location> {
+        self.row(addr).map(|row| Location {
+            file: self.files[row.file].clone(),
+            line: row.line,
+            column: row.column,
+        })
+    }
+
+    /*
+     *  addresses where a line starts, the first line after it with code if
+     *  it has none, file can be any trailing part of the path
+     */
+    pub fn addresses(&self, file: &str, line: u64) -> Vec<u64> {
+
+        let files: Vec<usize> = (0..self.files.len())
+            .filter(|&i| same_file(&self.files[i], file))
+            .collect();
+
+        let next = self.rows.iter()
+            .filter(|row| row.is_stmt && !row.end && files.contains(&row.file) && row.line >= line)
+            .map(|row| row.line)
+            .min();
+
+        let line = match next {
+            Some(line) => line,
+            None => { return vec![]; },
+        };
+
+        let mut addrs: Vec<u64> = vec![];
+
+        /* only where the line is entered, not every row it has */
+        for (i, row) in self.rows.iter().enumerate() {
+            if !row.is_stmt || row.end || row.line != line || !files.contains(&row.file) {
+                continue;
+            }
+
+            let continues = i > 0 && {
+                let prev = &self.rows[i - 1];
+                !prev.end && prev.line == line && prev.file == row.file
+            };
+
+            if !continues && !addrs.contains(&row.addr) {
+                addrs.push(row.addr);
+            }
+        }
+
+        addrs
+    }
+}
+
+/* rows naming a file that isn't there are dropped */
+fn emit(table: &mut LineTable, row: &Row) {
+    if row.file < table.files.len() {
+        table.rows.push(*row);
+    }
+}
+
+/* foo.c matches /src/foo.c and src/foo.c but not /src/barfoo.c */
+fn same_file(path: &str, file: &str) -> bool {
+    path == file || Path::new(path).ends_with(file)
+}
+
+/* state for one line number program */
+struct Program<'a> {
+    reader: Reader<'a>,
+    dwarf64: bool,
+    strings: &'a [u8],
+    line_strings: &'a [u8],
+}
+
+impl<'a> Program<'a> {
+    fn run(&mut self, table: &mut LineTable) -> Result<(), DebugError> {
+
+        let version = self.reader.u16()?;
+
+        if version < 2 || version > 5 {
+            return Err("Unsupported .debug_line version".into());
+        }
+
+        if version >= 5 {
+            /* address_size, segment_selector_size */
+            self.reader.skip(2)?;
+        }
+
+        let header_length = self.reader.offset(self.dwarf64)? as usize;
+        let program_start = self.reader.pos + header_length;
+
+        let min_inst_length = self.reader.u8()? as u64;
+        if version >= 4 {
+            /* maximum_operations_per_instruction, only VLIW cares */
+            self.reader.u8()?;
+        }
+        let default_is_stmt = self.reader.u8()? != 0;
+        let line_base = self.reader.u8()? as i8 as i64;
+        let line_range = self.reader.u8()? as u64;
+        let opcode_base = self.reader.u8()?;
+
+        if line_range == 0 {
+            return Err("Line program with a line_range of 0".into());
+        }
+
+        let opcode_lengths = self.reader.bytes(opcode_base.saturating_sub(1) as usize)?.to_vec();
+
+        /* line program file numbers are indices into files */
+        let files = if version >= 5 {
+            self.files_v5()?
+        } else {
+            self.files_v4()?
+        };
+
+        let base = table.files.len();
+        table.files.extend(files);
+
+        self.reader.pos = program_start;
+
+        let new_row = || Row {
+            addr: 0,
+            file: base + if version >= 5 { 0 } else { 1 },
+            line: 1,
+            column: 0,
+            is_stmt: default_is_stmt,
+            end: false,
+        };
+
+        let mut row = new_row();
+
+        while !self.reader.is_empty() {
+
+            let opcode = self.reader.u8()?;
+
+            if opcode >= opcode_base {
+                /* special opcode, advance both and append a row */
+                let adjusted = (opcode - opcode_base) as u64;
+
+                row.addr = row.addr.wrapping_add(adjusted / line_range * min_inst_length);
+                row.line = (row.line as i64 + line_base + (adjusted % line_range) as i64) as u64;
+                emit(table, &row);
+
+                continue;
+            }
+
+            match opcode {
+                0 => {
+                    let len = self.reader.uleb()? as usize;
+                    let next = self.reader.pos + len;
+
+                    match self.reader.u8()? {
+                        DW_LNE_END_SEQUENCE => {
+                            row.end = true;
+                            emit(table, &row);
+                            row = new_row();
+                        },
+                        DW_LNE_SET_ADDRESS => {
+                            row.addr = self.reader.uint(len.saturating_sub(1).min(8))?;
+                        },
+                        DW_LNE_DEFINE_FILE => {
+                            let name = self.reader.cstr()?.to_string();
+                            table.files.push(name);
+                        },
+                        /* set_discriminator and vendor extensions */
+                        _ => {},
+                    }
+
+                    self.reader.pos = next;
+                },
+                DW_LNS_COPY => {
+                    emit(table, &row);
+                },
+                DW_LNS_ADVANCE_PC => {
+                    row.addr = row.addr.wrapping_add(self.reader.uleb()? * min_inst_length);
+                },
+                DW_LNS_ADVANCE_LINE => {
+                    row.line = (row.line as i64 + self.reader.sleb()?) as u64;
+                },
+                DW_LNS_SET_FILE => {
+                    row.file = base + self.reader.uleb()? as usize;
+                },
+                DW_LNS_SET_COLUMN => {
+                    row.column = self.reader.uleb()?;
+                },
+                DW_LNS_NEGATE_STMT => {
+                    row.is_stmt = !row.is_stmt;
+                },
+                DW_LNS_SET_BASIC_BLOCK => {},
+                DW_LNS_CONST_ADD_PC => {
+                    let adjusted = (255 - opcode_base) as u64;
+                    row.addr = row.addr.wrapping_add(adjusted / line_range * min_inst_length);
+                },
+                DW_LNS_FIXED_ADVANCE_PC => {
+                    row.addr = row.addr.wrapping_add(self.reader.u16()? as u64);
+                },
+                /* prologue_end, epilogue_begin, set_isa and unknown ones */
+                _ => {
+                    for _ in 0..opcode_lengths[opcode as usize - 1] {
+                        self.reader.uleb()?;
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /* include_directories and file_names, 1 based, 0 is the compilation unit */
+    fn files_v4(&mut self) -> Result<Vec<String>, DebugError> {
+
+        let mut dirs = vec![String::new()];
+
+        loop {
+            let dir = self.reader.cstr()?;
+
+            if dir.is_empty() {
+                break;
+            }
+            dirs.push(dir.to_string());
+        }
+
+        let mut files = vec![String::new()];
+
+        loop {
+            let name = self.reader.cstr()?;
+
+            if name.is_empty() {
+                break;
+            }
+
+            let dir = self.reader.uleb()? as usize;
+            /* modification time and length */
+            self.reader.uleb()?;
+            self.reader.uleb()?;
+
+            files.push(join(dirs.get(dir).map_or("", |dir| dir.as_str()), name));
+        }
+
+        Ok(files)
+    }
+
+    /* self describing entries, 0 based */
+    fn files_v5(&mut self) -> Result<Vec<String>, DebugError> {
+
+        let dirs = self.entries()?.into_iter()
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>();
+
+        Ok(self.entries()?.into_iter()
+            .map(|(name, dir)| join(dirs.get(dir).map_or("", |dir| dir.as_str()), &name))
+            .collect())
+    }
+
+    /* path and directory index of each entry */
+    fn entries(&mut self) -> Result<Vec<(String, usize)>, DebugError> {
+
+        let format_count = self.reader.u8()?;
+        let mut format = vec![];
+
+        for _ in 0..format_count {
+            format.push((self.reader.uleb()?, self.reader.uleb()?));
+        }
+
+        let count = self.reader.uleb()?;
+        let mut entries = vec![];
+
+        for _ in 0..count {
+            let mut path = String::new();
+            let mut dir = 0;
+
+            for &(content, form) in format.iter() {
+                match content {
+                    DW_LNCT_PATH => { path = self.string(form)?; },
+                    DW_LNCT_DIRECTORY_INDEX => { dir = self.udata(form)? as usize; },
+                    /* timestamp, size, MD5 */
+                    _ => { self.skip_form(form)?; },
+                }
+            }
+
+            entries.push((path, dir));
+        }
+
+        Ok(entries)
+    }
+
+    fn string(&mut self, form: u64) -> Result<String, DebugError> {
+        let s = match form {
+            dwarf::DW_FORM_STRING => self.reader.cstr()?,
+            dwarf::DW_FORM_LINE_STRP => {
+                let offset = self.reader.offset(self.dwarf64)?;
+                dwarf::string_at(self.line_strings, offset)?
+            },
+            dwarf::DW_FORM_STRP => {
+                let offset = self.reader.offset(self.dwarf64)?;
+                dwarf::string_at(self.strings, offset)?
+            },
+            _ => { return Err("Unsupported form for a line table path".into()); },
+        };
+
+        Ok(s.to_string())
+    }
+
+    fn udata(&mut self, form: u64) -> Result<u64, DebugError> {
+        match form {
+            dwarf::DW_FORM_DATA1 => self.reader.uint(1),
+            dwarf::DW_FORM_DATA2 => self.reader.uint(2),
+            dwarf::DW_FORM_DATA4 => self.reader.uint(4),
+            dwarf::DW_FORM_DATA8 => self.reader.uint(8),
+            dwarf::DW_FORM_UDATA => self.reader.uleb(),
+            _ => Err("Unsupported form for a line table index".into()),
+        }
+    }
+
+    fn skip_form(&mut self, form: u64) -> Result<(), DebugError> {
+        match form {
+            dwarf::DW_FORM_DATA16 => self.reader.skip(16),
+            dwarf::DW_FORM_BLOCK => {
+                let len = self.reader.uleb()? as usize;
+                self.reader.skip(len)
+            },
+            dwarf::DW_FORM_STRING | dwarf::DW_FORM_LINE_STRP | dwarf::DW_FORM_STRP => {
+                self.string(form).map(|_| ())
+            },
+            _ => self.udata(form).map(|_| ()),
+        }
+    }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() || name.starts_with("/") {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir.trim_end_matches('/'), name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /* a version 4 unit for src/a.c, line_base -5, line_range 14, opcode_base 13 */
+    fn unit(program: &[u8]) -> Vec<u8> {
+
+        let mut header = vec![1, 1, 1, 0xfb, 14, 13, 0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+        header.extend_from_slice(b"src\0\0a.c\0\x01\0\0\0");
+
+        let mut data = vec![4, 0];
+        data.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        data.extend(header);
+        data.extend_from_slice(program);
+
+        data
+    }
+
+    fn run(data: &[u8]) -> Result<LineTable, DebugError> {
+
+        let mut table = LineTable {
+            files: vec![],
+            rows: vec![],
+        };
+
+        let mut program = Program {
+            reader: Reader::new(data, 0),
+            dwarf64: false,
+            strings: &[],
+            line_strings: &[],
+        };
+
+        program.run(&mut table)?;
+
+        Ok(table)
+    }
+
+    fn table() -> LineTable {
+        run(&unit(&[
+            /* set_address 0x1000, line 10 */
+            0, 9, DW_LNE_SET_ADDRESS, 0x00, 0x10, 0, 0, 0, 0, 0, 0,
+            DW_LNS_ADVANCE_LINE, 9,
+            DW_LNS_COPY,
+            /* special, address +3 and line +1 */
+            61,
+            DW_LNS_ADVANCE_PC, 2,
+            DW_LNS_ADVANCE_LINE, 0x7f,
+            DW_LNS_COPY,
+            /* address +17 */
+            DW_LNS_CONST_ADD_PC,
+            DW_LNS_NEGATE_STMT,
+            /* special, no advance */
+            18,
+            DW_LNS_NEGATE_STMT,
+            DW_LNS_FIXED_ADVANCE_PC, 0x10, 0,
+            0, 1, DW_LNE_END_SEQUENCE,
+        ])).unwrap()
+    }
+
+    #[test]
+    fn rows() {
+        let table = table();
+
+        assert_eq!(table.files, vec!["".to_string(), "src/a.c".to_string()]);
+
+        let rows: Vec<_> = table.rows.iter()
+            .map(|row| (row.addr, row.file, row.line, row.is_stmt, row.end))
+            .collect();
+
+        assert_eq!(rows, vec![
+            (0x1000, 1, 10, true, false),
+            (0x1003, 1, 11, true, false),
+            (0x1005, 1, 10, true, false),
+            (0x1016, 1, 10, false, false),
+            (0x1026, 1, 10, true, true),
+        ]);
+    }
+
+    #[test]
+    fn location() {
+        let table = table();
+
+        assert_eq!(table.location(0x1004).unwrap().to_string(), "src/a.c:11");
+        assert_eq!(table.location(0x1020).unwrap().line, 10);
+        assert!(table.location(0xfff).is_none());
+        assert!(table.location(0x1026).is_none());
+    }
+
+    #[test]
+    fn addresses() {
+        let table = table();
+
+        assert_eq!(table.addresses("a.c", 10), vec![0x1000, 0x1005]);
+        assert_eq!(table.addresses("src/a.c", 11), vec![0x1003]);
+        /* the next line with code */
+        assert_eq!(table.addresses("a.c", 1), vec![0x1000, 0x1005]);
+        assert!(table.addresses("a.c", 12).is_empty());
+        assert!(table.addresses("b.c", 10).is_empty());
+    }
+
+    #[test]
+    fn file_suffix() {
+        assert!(same_file("/src/foo.c", "foo.c"));
+        assert!(same_file("/src/foo.c", "src/foo.c"));
+        assert!(!same_file("/src/barfoo.c", "foo.c"));
+    }
+
+    #[test]
+    fn bad_header() {
+        /* line_range */
+        let mut data = unit(&[]);
+        data[10] = 0;
+        assert!(run(&data).is_err());
+
+        /* version */
+        data = unit(&[]);
+        data[0] = 6;
+        assert!(run(&data).is_err());
+    }
+}
@@ -282,6 +282,23 @@ impl Memory {
         Ok(buf)
     }
 
+    /* nul terminated, at most max bytes, stops at the end of the mapping */
+    pub fn read_str(&mut self, addr: usize, max: usize) -> Result<String, DebugError> {
+
+        let end = match self.maps.iter().find(|r| r.contains_value(addr) && r.permissions.read()) {
+            Some(region) => region.end(),
+            None => { return Err("Address is not mapped in memory".into()); },
+        };
+
+        let mut bytes = self.read(addr, max.min(end - addr))?;
+
+        if let Some(nul) = bytes.iter().position(|&b| b == 0) {
+            bytes.truncate(nul);
+        }
+
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
     pub fn find_chunk(&self, addr: usize, len: usize, permission: ChunkPermission) -> Option<&MemoryRegion> {
 
         /* coalesce chunks */
@@ -0,0 +1,157 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::rc::Rc;
+
+use vm_info::ProcessId;
+use vm_info::mapped_region;
+
+use elf::{Elf,PT_LOAD,PT_PHDR};
+use error::DebugError;
+
+const AT_PHDR: u64 = 3;
+const PAGE_MASK: u64 = !0xfff;
+
+/* an executable or library mapped into the process */
+#[derive(Clone)]
+pub struct Module {
+    pub path: String,
+    /* lowest mapped address, what module+offset counts from */
+    pub base: u64,
+    pub end: u64,
+    /* runtime minus link time addresses, 0 for a non PIE executable */
+    pub bias: u64,
+    pub elf: Option<Rc<Elf>>,
+}
+
+impl Module {
+    /* file name, libc.so.6 for /usr/lib/x86_64-linux-gnu/libc.so.6 */
+    pub fn name(&self) -> &str {
+        Path::new(&self.path).file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&self.path)
+    }
+
+    /* libc matches libc.so.6 and libc-2.31.so too */
+    pub fn is(&self, name: &str) -> bool {
+        let file = self.name();
+
+        self.path == name || file == name ||
+            (file.starts_with(name) && (file[name.len()..].starts_with(".") || file[name.len()..].starts_with("-")))
+    }
+
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.base && addr < self.end
+    }
+
+    /* runtime address of a symbol */
+    pub fn symbol(&self, name: &str) -> Option<u64> {
+        self.elf.as_ref()
+            .and_then(|elf| elf.symbol(name))
+            .map(|sym| sym.addr.wrapping_add(self.bias))
+    }
+
+    /* malloc+0x5 for a runtime address */
+    pub fn symbolize(&self, addr: u64) -> Option<String> {
+        self.elf.as_ref().and_then(|elf| elf.symbolize(addr.wrapping_sub(self.bias)))
+    }
+}
+
+/*
+ *  every file backed ELF mapping in the process, elf loads (or looks up)
+ *  the parsed file for a path
+ */
+pub fn modules<F>(pid: u32, mut elf: F) -> Result<Vec<Module>, DebugError>
+    where F: FnMut(&str) -> Option<Rc<Elf>>
+{
+
+    let mut regions = mapped_region::iter_mappings(ProcessId::Num(pid))?
+        .filter_map(|r| r.ok())
+        .filter(|r| r.pathname.as_ref().map_or(false, |path| path.starts_with("/")))
+        .collect::<Vec<_>>();
+
+    regions.sort_by_key(|r| r.start_address);
+
+    let mut modules: Vec<Module> = vec![];
+
+    for region in regions.iter() {
+
+        let path = region.pathname.clone().unwrap();
+        let start = region.start_address as u64;
+        let end = region.end_address as u64;
+
+        /* later segments of a module that is already listed */
+        if let Some(module) = modules.iter_mut().find(|m| m.path == path) {
+            if start >= module.base {
+                module.end = module.end.max(end);
+                continue;
+            }
+        }
+
+        /* the first mapping of an ELF file is its header */
+        if region.offset != 0 {
+            continue;
+        }
+
+        let elf = match elf(&path) {
+            Some(elf) => elf,
+            /* fonts, locale archives and other mapped data */
+            None => { continue; },
+        };
+
+        let bias = elf.segments.iter()
+            .find(|seg| seg.kind == PT_LOAD)
+            .map_or(start, |seg| start.wrapping_sub(seg.vaddr & PAGE_MASK)
+                .wrapping_add(seg.offset & PAGE_MASK));
+
+        modules.push(Module {
+            path: path,
+            base: start,
+            end: end,
+            bias: bias,
+            elf: Some(elf),
+        });
+    }
+
+    Ok(modules)
+}
+
+/* key and value pairs the kernel passed to the program */
+pub fn auxv(pid: u32) -> Result<Vec<(u64, u64)>, DebugError> {
+
+    let mut data = vec![];
+    File::open(format!("/proc/{}/auxv", pid))?.read_to_end(&mut data)?;
+
+    let words: Vec<u64> = data.chunks(8)
+        .filter(|word| word.len() == 8)
+        .map(|word| word.iter().rev().fold(0, |value, &b| value << 8 | b as u64))
+        .collect();
+
+    Ok(words.chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| (pair[0], pair[1]))
+        .take_while(|&(key, _)| key != 0)
+        .collect())
+}
+
+/* load bias of the executable from where the kernel put its program headers */
+pub fn exe_bias(pid: u32, elf: &Elf) -> Result<u64, DebugError> {
+
+    let phdr = auxv(pid)?.into_iter()
+        .find(|&(key, _)| key == AT_PHDR)
+        .map(|(_, value)| value)
+        .ok_or("No AT_PHDR in the auxiliary vector")?;
+
+    /* PT_PHDR if there is one, else the headers sit in the first segment */
+    let linked = match elf.segments.iter().find(|seg| seg.kind == PT_PHDR) {
+        Some(seg) => seg.vaddr,
+        None => {
+            let seg = elf.segments.iter()
+                .find(|seg| seg.kind == PT_LOAD && seg.offset <= elf.phoff && elf.phoff < seg.offset + seg.filesz)
+                .ok_or("Program headers are not loaded")?;
+            seg.vaddr + elf.phoff - seg.offset
+        },
+    };
+
+    Ok(phdr.wrapping_sub(linked))
+}
@@ -2,11 +2,14 @@ use libc::{user_regs_struct};
 
 use registers::{Register,Cast};
 use process::Process;
+use fpregs::FpRegisters;
 use error::DebugError;
 use breakpoint::Breakpoint;
 
 struct PhantomCall<T> {
     restore: T,
+    /* only saved when the call passes floating point arguments */
+    restore_fp: Option<FpRegisters>,
     exits: Vec<Breakpoint>,
 }
 
@@ -29,7 +32,7 @@ impl<T> PhantomManager<T>
         }
     }
 
-    pub fn push(&mut self, restore: T, exits: Vec<u64>) {
+    pub fn push(&mut self, restore: T, restore_fp: Option<FpRegisters>, exits: Vec<u64>) {
 
         let breakpoints = exits.iter().map(|&ex| {
             let mut bp = Breakpoint::new(
@@ -43,28 +46,83 @@ impl<T> PhantomManager<T>
         let call = PhantomCall {
             exits: breakpoints,
             restore: restore,
+            restore_fp: restore_fp,
         };
 
         self.stack.push(call);
     }
 
+    /* back at the breakpoint the call was made from, as if it was just hit */
     pub fn clean(&mut self, process: &Process<T>) -> Result<<T as Register>::Size, DebugError> {
 
         if !self.is_exit(process) {
             return Err("Not at exit for phantom call".into());
         }
 
-        /* dropped scope causes restore on instruction */
-        /* don't need to waste a for-loop */
-        let mut call = self.stack.pop().unwrap();
+        let call = self.stack.pop().unwrap();
 
-        let ip = call.restore.ip();
-        call.restore.set_ip((ip.cast()-1).cast());
+        for bp in call.exits.iter() {
+            bp.remove()?;
+        }
 
         process.setregs(&call.restore)?;
 
-        process.cont()?;
-        process.wait_stop()
+        if let Some(ref fp) = call.restore_fp {
+            process.setfpstate(fp)?;
+        }
+
+        Ok(call.restore.ip())
+    }
+
+    /* abandon every pending call, rewinding to before the first one */
+    pub fn clear(&mut self, process: &Process<T>) -> Result<(), DebugError> {
+
+        for call in self.stack.iter() {
+            for bp in call.exits.iter() {
+                bp.remove()?;
+            }
+        }
+
+        if let Some(mut call) = self.stack.drain(..).next() {
+            let ip = call.restore.ip();
+            call.restore.set_ip((ip.cast()-1).cast());
+
+            process.setregs(&call.restore)?;
+
+            if let Some(ref fp) = call.restore_fp {
+                process.setfpstate(fp)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /* the process image is gone, drop every call without writing */
+    pub fn forget(&mut self) {
+
+        for call in self.stack.iter() {
+            for bp in call.exits.iter() {
+                bp.invalidate();
+            }
+        }
+
+        self.stack.clear();
+    }
+
+    pub fn retarget(&mut self, pid: u64) {
+
+        for call in self.stack.iter() {
+            for bp in call.exits.iter() {
+                bp.retarget(pid as u32);
+            }
+        }
+
+        self.pid = pid;
+    }
+
+    /* the innermost call passes floating point arguments */
+    pub fn is_float(&self) -> bool {
+        self.stack.last().map_or(false, |call| call.restore_fp.is_some())
     }
 
     /* FIXME: needs way more sofistication, what about recursion etc.. */
@@ -1,31 +1,60 @@
-use libc::{user_regs_struct,c_void};
+use libc::{user_regs_struct,siginfo_t,c_void,iovec};
 use libc::ptrace;
 use libc::{
     PTRACE_GETREGS,
     PTRACE_SETREGS,
+    PTRACE_GETFPREGS,
+    PTRACE_SETFPREGS,
+    PTRACE_GETREGSET,
+    PTRACE_SETREGSET,
     PTRACE_CONT,
     PTRACE_PEEKTEXT,
     PTRACE_POKETEXT,
+    PTRACE_PEEKUSER,
+    PTRACE_POKEUSER,
     PTRACE_SINGLESTEP,
+    PTRACE_SYSCALL,
     PTRACE_ATTACH,
     PTRACE_DETACH,
+    PTRACE_SETOPTIONS,
+    PTRACE_GETEVENTMSG,
+    PTRACE_GETSIGINFO,
+    PTRACE_EVENT_FORK,
+    PTRACE_EVENT_VFORK,
+    PTRACE_EVENT_CLONE,
+    PTRACE_EVENT_EXEC,
+    PTRACE_EVENT_VFORK_DONE,
+    PTRACE_EVENT_EXIT,
 };
 use nix::{errno,Errno};
+use nix::sys::signal::Signal;
 
 use std::ptr;
+use std::fs::File;
+use std::io::Read;
 use std::mem;
 use std::result::Result;
 use std::cell::Cell;
 use std::marker::PhantomData;
 use std::fmt::Display;
 
-use status::Status;
+use status::{Status,PtraceEvent};
 use error::DebugError;
+use event::SigInfo;
+use signals::is_fault;
 use registers::{Register,Cast};
+use fpregs::{FpRegisters,FXSAVE_SIZE,XSAVE_MAX,NT_X86_XSTATE};
+
+/* not in every libc yet */
+const PTRACE_GET_SYSCALL_INFO: u32 = 0x420e;
+
+/* ptrace_syscall_info.op */
+pub const SYSCALL_INFO_ENTRY: u8 = 1;
+pub const SYSCALL_INFO_EXIT: u8 = 2;
 
 #[derive(Debug,Clone)]
 pub struct Process<T> {
-    pub pid: u32,
+    pub pid: Cell<u32>,
     pub status: Cell<Status>,
     marker: PhantomData<T>,
 }
@@ -48,16 +77,38 @@ impl<T> Process<T>
 
     pub fn new(pid: u32) -> Self {
         Process {
-            pid: pid,
+            pid: Cell::new(pid),
             status: Cell::new(Status::new(0)),
             marker: PhantomData,
         }
     }
 
+    pub fn pid(&self) -> u32 {
+        self.pid.get()
+    }
+
+    /* point this handle at another process, e.g. a followed fork child */
+    pub fn retarget(&self, pid: u32) {
+        self.pid.set(pid);
+    }
+
     pub fn status(&self) -> Status {
         self.status.get().clone()
     }
 
+    /* thread group id, equal to the pid for the main thread */
+    pub fn tgid(&self) -> Result<u32, DebugError> {
+
+        let mut status = String::new();
+        File::open(format!("/proc/{}/status", self.pid()))?
+            .read_to_string(&mut status)?;
+
+        match status.lines().find(|l| l.starts_with("Tgid:")) {
+            Some(line) => Ok(line[5..].trim().parse()?),
+            None => Err("No Tgid in process status".into()),
+        }
+    }
+
     pub fn wait_stop(&self) -> Result<rsize!(T), DebugError> {
         let stat = self.wait();
 
@@ -72,17 +123,48 @@ impl<T> Process<T>
     }
 
     pub fn wait(&self) -> Status {
-        let status = Status::wait(self.pid as i32);
+        let status = Status::wait(self.pid() as i32);
         self.status.set(status);
 
         status
     }
 
     pub fn step(&self) -> Result<i64, DebugError> {
+        self.step_signal(0)
+    }
+
+    /*
+     *  one instruction even if signals arrive first, the first one is held
+     *  back and returned, it is delivered only if the next resume passes it.
+     *  A fault of the instruction itself ends the step with the thread on it
+     */
+    pub fn step_deferring(&self) -> Result<Option<SigInfo>, DebugError> {
+
+        let mut deferred = None;
+
+        loop {
+            self.step()?;
+            let status = self.wait();
+
+            if !status.stopped() {
+                return Err(DebugError::from(status));
+            }
+
+            match status.stopsig() {
+                Some(sig) if status.trapped() || is_fault(sig) => { return Ok(deferred); },
+                Some(_) if deferred.is_none() => { deferred = Some(self.getsiginfo()?); },
+                Some(_) => {},
+                None => { return Ok(deferred); },
+            }
+        }
+    }
+
+    /* sig is delivered as the step starts, 0 for none */
+    pub fn step_signal(&self, sig: i32) -> Result<i64, DebugError> {
         unsafe {
             Errno::clear();
 
-            let res = ptrace(PTRACE_SINGLESTEP, self.pid, ptr::null::<c_void>(), ptr::null::<c_void>());
+            let res = ptrace(PTRACE_SINGLESTEP, self.pid(), ptr::null::<c_void>(), sig as i64);
 
             if errno::errno() != 0 {
                 Err(DebugError::from(Errno::last()))
@@ -96,7 +178,7 @@ impl<T> Process<T>
         unsafe {
             Errno::clear();
 
-            let res = ptrace(PTRACE_PEEKTEXT, self.pid, addr, ptr::null::<c_void>());
+            let res = ptrace(PTRACE_PEEKTEXT, self.pid(), addr, ptr::null::<c_void>());
 
             if errno::errno() != 0 {
                 Err(DebugError::from(Errno::last()))
@@ -106,6 +188,29 @@ impl<T> Process<T>
         }
     }
 
+    /* whole aligned words are peeked, reads stop short of the next page */
+    pub fn read(&self, addr: rsize!(T), len: usize) -> Result<Vec<u8>, DebugError> {
+
+        let word = mem::size_of::<rsize!(T)>();
+        let addr: usize = addr.cast();
+        let skip = addr % word;
+
+        let mut data = Vec::with_capacity(len + 2*word);
+        let mut at = addr - skip;
+
+        while data.len() < skip + len {
+            let value: usize = self.peek(at.cast())?.cast();
+
+            for i in 0..word {
+                data.push((value >> (i*8)) as u8);
+            }
+
+            at += word;
+        }
+
+        Ok(data[skip..skip+len].to_vec())
+    }
+
     pub fn setregs_user(&self, regs: &T) -> Result<i64, DebugError> {
 
         let mregs = self.getregs()?;
@@ -163,7 +268,7 @@ impl<T> Process<T>
         unsafe {
             Errno::clear();
 
-            let res = ptrace(PTRACE_POKETEXT, self.pid, addr, word);
+            let res = ptrace(PTRACE_POKETEXT, self.pid(), addr, word);
 
             if errno::errno() != 0 {
                 Err(DebugError::from(Errno::last()))
@@ -173,11 +278,76 @@ impl<T> Process<T>
         }
     }
 
+    /* what kind of syscall stop this is, SYSCALL_INFO_ENTRY or _EXIT, linux 5.3 and up */
+    pub fn syscall_op(&self) -> Result<u8, DebugError> {
+
+        /* struct ptrace_syscall_info, op is the first byte */
+        let mut info = [0u8; 88];
+        unsafe {
+            Errno::clear();
+
+            ptrace(PTRACE_GET_SYSCALL_INFO, self.pid(), info.len(), info.as_mut_ptr());
+
+            if errno::errno() != 0 {
+                return Err(DebugError::from(Errno::last()));
+            }
+        }
+
+        Ok(info[0])
+    }
+
+    /* offset into struct user, e.g. the debug registers */
+    pub fn peek_user(&self, offset: usize) -> Result<u64, DebugError> {
+        unsafe {
+            Errno::clear();
+
+            let res = ptrace(PTRACE_PEEKUSER, self.pid(), offset, ptr::null::<c_void>());
+
+            if errno::errno() != 0 {
+                Err(DebugError::from(Errno::last()))
+            } else {
+                Ok(res as u64)
+            }
+        }
+    }
+
+    pub fn poke_user(&self, offset: usize, data: u64) -> Result<(), DebugError> {
+        unsafe {
+            Errno::clear();
+
+            ptrace(PTRACE_POKEUSER, self.pid(), offset, data);
+
+            if errno::errno() != 0 {
+                Err(DebugError::from(Errno::last()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
     pub fn attach(&self) -> Result<i64, DebugError> {
         unsafe {
             Errno::clear();
 
-            let res = ptrace(PTRACE_ATTACH, self.pid, ptr::null::<c_void>(), ptr::null::<c_void>());
+            let res = ptrace(PTRACE_ATTACH, self.pid(), ptr::null::<c_void>(), ptr::null::<c_void>());
+
+            if errno::errno() != 0 {
+                Err(DebugError::from(Errno::last()))
+            } else {
+                Ok(res)
+            }
+        }
+    }
+
+    pub fn detach(&self, sig: Option<Signal>) -> Result<i64, DebugError> {
+
+        /* signal is delivered as the process resumes */
+        let sig = sig.map_or(0, |s| s as i64);
+
+        unsafe {
+            Errno::clear();
+
+            let res = ptrace(PTRACE_DETACH, self.pid(), ptr::null::<c_void>(), sig);
 
             if errno::errno() != 0 {
                 Err(DebugError::from(Errno::last()))
@@ -187,11 +357,11 @@ impl<T> Process<T>
         }
     }
 
-    pub fn detach(&self) -> Result<i64, DebugError> {
+    pub fn set_options(&self, options: i32) -> Result<i64, DebugError> {
         unsafe {
             Errno::clear();
 
-            let res = ptrace(PTRACE_DETACH, self.pid, ptr::null::<c_void>(), ptr::null::<c_void>());
+            let res = ptrace(PTRACE_SETOPTIONS, self.pid(), ptr::null::<c_void>(), options as i64);
 
             if errno::errno() != 0 {
                 Err(DebugError::from(Errno::last()))
@@ -201,13 +371,69 @@ impl<T> Process<T>
         }
     }
 
+    pub fn geteventmsg(&self) -> Result<u64, DebugError> {
+        unsafe {
+            let mut msg: u64 = 0;
+            Errno::clear();
+
+            ptrace(PTRACE_GETEVENTMSG, self.pid(), ptr::null::<c_void>(), &mut msg);
+
+            if errno::errno() != 0 {
+                Err(DebugError::from(Errno::last()))
+            } else {
+                Ok(msg)
+            }
+        }
+    }
+
+    pub fn getsiginfo(&self) -> Result<SigInfo, DebugError> {
+        unsafe {
+            let mut info: siginfo_t = mem::zeroed();
+            Errno::clear();
+
+            ptrace(PTRACE_GETSIGINFO, self.pid(), ptr::null::<c_void>(), &mut info);
+
+            if errno::errno() != 0 {
+                Err(DebugError::from(Errno::last()))
+            } else {
+                Ok(SigInfo {
+                    signo: info.si_signo,
+                    errno: info.si_errno,
+                    code: info.si_code,
+                    addr: info.si_addr() as u64,
+                })
+            }
+        }
+    }
+
+    /* decode the ptrace event of the last stop, if it was one */
+    pub fn event(&self) -> Result<Option<PtraceEvent>, DebugError> {
+
+        let event = match self.status().event() {
+            Some(event) => event,
+            None => { return Ok(None); },
+        };
+
+        let event = match event {
+            PTRACE_EVENT_FORK => PtraceEvent::Fork(self.geteventmsg()? as u32),
+            PTRACE_EVENT_VFORK => PtraceEvent::Vfork(self.geteventmsg()? as u32),
+            PTRACE_EVENT_CLONE => PtraceEvent::Clone(self.geteventmsg()? as u32),
+            PTRACE_EVENT_EXEC => PtraceEvent::Exec,
+            PTRACE_EVENT_VFORK_DONE => PtraceEvent::VforkDone,
+            PTRACE_EVENT_EXIT => PtraceEvent::Exit,
+            _ => { return Err("Unknown ptrace event".into()); }
+        };
+
+        Ok(Some(event))
+    }
+
     pub fn getregs(&self) -> Result<T, DebugError> {
 
         unsafe {
             let mut regs: user_regs_struct = T::default().into();
             Errno::clear();
 
-            ptrace(PTRACE_GETREGS, self.pid, ptr::null::<c_void>(), &mut regs);
+            ptrace(PTRACE_GETREGS, self.pid(), ptr::null::<c_void>(), &mut regs);
 
             if errno::errno() != 0 {
                 Err(DebugError::from(Errno::last()))
@@ -218,11 +444,16 @@ impl<T> Process<T>
     }
 
     pub fn cont(&self) -> Result<i64, DebugError> {
+        self.cont_signal(0)
+    }
+
+    /* sig is delivered as the process resumes, 0 for none */
+    pub fn cont_signal(&self, sig: i32) -> Result<i64, DebugError> {
 
         let ret = unsafe {
             Errno::clear();
 
-            let ret = ptrace(PTRACE_CONT, self.pid, ptr::null::<c_void>(), ptr::null::<c_void>());
+            let ret = ptrace(PTRACE_CONT, self.pid(), ptr::null::<c_void>(), sig as i64);
 
             if errno::errno() != 0 {
                 Err(DebugError::from(Errno::last()))
@@ -234,13 +465,105 @@ impl<T> Process<T>
         ret
     }
 
+    /* like cont, but stops on the next syscall entry or exit */
+    pub fn syscall(&self) -> Result<i64, DebugError> {
+        self.syscall_signal(0)
+    }
+
+    pub fn syscall_signal(&self, sig: i32) -> Result<i64, DebugError> {
+        unsafe {
+            Errno::clear();
+
+            let ret = ptrace(PTRACE_SYSCALL, self.pid(), ptr::null::<c_void>(), sig as i64);
+
+            if errno::errno() != 0 {
+                Err(DebugError::from(Errno::last()))
+            } else {
+                Ok(ret)
+            }
+        }
+    }
+
     pub fn setregs(&self, regs: &T) -> Result<i64, DebugError> {
 
         let urs: user_regs_struct = regs.clone().into();
         unsafe {
             Errno::clear();
 
-            let ret = ptrace(PTRACE_SETREGS, self.pid, ptr::null::<c_void>(), &urs);
+            let ret = ptrace(PTRACE_SETREGS, self.pid(), ptr::null::<c_void>(), &urs);
+
+            if errno::errno() != 0 {
+                Err(DebugError::from(Errno::last()))
+            } else {
+                Ok(ret)
+            }
+        }
+    }
+
+    /* x87 and SSE state, the 512 byte FXSAVE image */
+    pub fn getfpregs(&self) -> Result<FpRegisters, DebugError> {
+
+        let mut area = vec![0u8; FXSAVE_SIZE];
+        unsafe {
+            Errno::clear();
+
+            ptrace(PTRACE_GETFPREGS, self.pid(), ptr::null::<c_void>(), area.as_mut_ptr());
+
+            if errno::errno() != 0 {
+                return Err(DebugError::from(Errno::last()));
+            }
+        }
+
+        FpRegisters::from_fxsave(area)
+    }
+
+    pub fn setfpregs(&self, regs: &FpRegisters) -> Result<i64, DebugError> {
+        unsafe {
+            Errno::clear();
+
+            let ret = ptrace(PTRACE_SETFPREGS, self.pid(), ptr::null::<c_void>(), regs.area().as_ptr());
+
+            if errno::errno() != 0 {
+                Err(DebugError::from(Errno::last()))
+            } else {
+                Ok(ret)
+            }
+        }
+    }
+
+    /* the whole XSAVE area, getfpregs plus the ymm upper halves and whatever else the cpu has */
+    pub fn getxstate(&self) -> Result<FpRegisters, DebugError> {
+
+        let mut area = vec![0u8; XSAVE_MAX];
+        unsafe {
+            let mut iov = iovec {
+                iov_base: area.as_mut_ptr() as *mut c_void,
+                iov_len: area.len(),
+            };
+            Errno::clear();
+
+            ptrace(PTRACE_GETREGSET, self.pid(), NT_X86_XSTATE, &mut iov);
+
+            if errno::errno() != 0 {
+                return Err(DebugError::from(Errno::last()));
+            }
+
+            /* the kernel shrinks iov_len to what it wrote */
+            area.truncate(iov.iov_len);
+        }
+
+        FpRegisters::from_xsave(area)
+    }
+
+    pub fn setxstate(&self, regs: &FpRegisters) -> Result<i64, DebugError> {
+        unsafe {
+            let mut iov = iovec {
+                iov_base: regs.area().as_ptr() as *mut c_void,
+                iov_len: regs.area().len(),
+            };
+            Errno::clear();
+
+            let ret = ptrace(PTRACE_SETREGSET, self.pid(), NT_X86_XSTATE, &mut iov);
 
             if errno::errno() != 0 {
                 Err(DebugError::from(Errno::last()))
@@ -249,4 +572,18 @@ impl<T> Process<T>
             }
         }
     }
+
+    /* getxstate where the kernel supports it, getfpregs otherwise */
+    pub fn getfpstate(&self) -> Result<FpRegisters, DebugError> {
+        self.getxstate().or_else(|_| self.getfpregs())
+    }
+
+    /* writes back through whichever call read regs */
+    pub fn setfpstate(&self, regs: &FpRegisters) -> Result<i64, DebugError> {
+        if regs.is_xstate() {
+            self.setxstate(regs)
+        } else {
+            self.setfpregs(regs)
+        }
+    }
 }
@@ -0,0 +1,53 @@
+use nix::sys::signal::Signal;
+
+/* what to do when the process gets a signal, like gdb's `handle` */
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct SignalPolicy {
+    /* surface the stop to the caller */
+    pub stop: bool,
+    /* print a line when the signal arrives */
+    pub print: bool,
+    /* deliver the signal when the process resumes */
+    pub pass: bool,
+}
+
+impl SignalPolicy {
+    pub fn new(stop: bool, print: bool, pass: bool) -> Self {
+        SignalPolicy {
+            stop: stop,
+            print: print,
+            pass: pass,
+        }
+    }
+
+    /* same defaults as gdb */
+    pub fn default_for(signo: i32) -> Self {
+
+        use self::Signal::*;
+        match Signal::from_c_int(signo) {
+            /* used by the debugger itself */
+            Ok(SIGINT) | Ok(SIGTRAP) => SignalPolicy::new(true, true, false),
+            /* routine, programs use these for control flow */
+            Ok(SIGALRM) | Ok(SIGURG) | Ok(SIGCHLD) | Ok(SIGWINCH) |
+            Ok(SIGIO) | Ok(SIGVTALRM) | Ok(SIGPROF) => {
+                SignalPolicy::new(false, false, true)
+            },
+            _ => SignalPolicy::new(true, true, true),
+        }
+    }
+}
+
+/* raised by the instruction the thread is on, siginfo has the address */
+pub fn is_fault(signo: i32) -> bool {
+    match Signal::from_c_int(signo) {
+        Ok(Signal::SIGSEGV) | Ok(Signal::SIGBUS) | Ok(Signal::SIGILL) | Ok(Signal::SIGFPE) => true,
+        _ => false,
+    }
+}
+
+pub fn signal_name(signo: i32) -> String {
+    match Signal::from_c_int(signo) {
+        Ok(sig) => format!("{:?}", sig),
+        Err(_) => format!("signal {}", signo),
+    }
+}
@@ -0,0 +1,188 @@
+use process::Process;
+use registers::x86_64_Registers;
+use error::DebugError;
+
+/*
+ *  the dynamic linker keeps a list of loaded objects in _r_debug and calls
+ *  r_brk, an empty function, before and after every change to it
+ */
+
+/* r_state */
+pub const RT_CONSISTENT: u64 = 0;
+pub const RT_ADD: u64 = 1;
+pub const RT_DELETE: u64 = 2;
+
+/* paths longer than this are cut off */
+const MAX_PATH: usize = 4096;
+
+/* struct r_debug */
+#[derive(Debug,Clone,Copy)]
+pub struct RDebug {
+    pub version: u32,
+    /* first link_map entry, the executable */
+    pub map: u64,
+    pub brk: u64,
+    pub state: u64,
+    /* where the dynamic linker itself is loaded */
+    pub ldbase: u64,
+}
+
+impl RDebug {
+    pub fn read(process: &Process<x86_64_Registers>, addr: u64) -> Result<RDebug, DebugError> {
+        Ok(RDebug {
+            version: process.peek(addr)? as u32,
+            map: process.peek(addr + 8)?,
+            brk: process.peek(addr + 16)?,
+            state: process.peek(addr + 24)? & 0xffffffff,
+            ldbase: process.peek(addr + 32)?,
+        })
+    }
+
+    /* r_version is 0 until the dynamic linker sets it up */
+    pub fn is_ready(&self) -> bool {
+        self.version != 0
+    }
+
+    /* every shared object in load order, without the executable */
+    pub fn libraries(&self, process: &Process<x86_64_Registers>) -> Result<Vec<Library>, DebugError> {
+
+        let mut libraries = vec![];
+        let mut entry = self.map;
+
+        while entry != 0 {
+            /* struct link_map: l_addr, l_name, l_ld, l_next, l_prev */
+            let base = process.peek(entry)?;
+            let name = process.peek(entry + 8)?;
+            let dynamic = process.peek(entry + 16)?;
+
+            let path = read_string(process, name)?;
+
+            if !path.is_empty() {
+                libraries.push(Library {
+                    path: path,
+                    base: base,
+                    dynamic: dynamic,
+                    map: entry,
+                });
+            }
+
+            entry = process.peek(entry + 24)?;
+
+            /* a list that loops back on itself is being rewritten */
+            if libraries.len() > 0x10000 {
+                return Err("link_map does not end".into());
+            }
+        }
+
+        Ok(libraries)
+    }
+}
+
+/* one link_map entry */
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Library {
+    pub path: String,
+    /* l_addr, what addresses in the file are off by */
+    pub base: u64,
+    /* its _DYNAMIC */
+    pub dynamic: u64,
+    /* the link_map entry itself */
+    pub map: u64,
+}
+
+/* what changed between two consistent states of the list */
+#[derive(Debug,Clone,Default)]
+pub struct LibraryChange {
+    pub loaded: Vec<Library>,
+    pub unloaded: Vec<Library>,
+}
+
+impl LibraryChange {
+    pub fn between(old: &[Library], new: &[Library]) -> LibraryChange {
+        LibraryChange {
+            loaded: new.iter().filter(|lib| !old.contains(lib)).cloned().collect(),
+            unloaded: old.iter().filter(|lib| !new.contains(lib)).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.loaded.is_empty() && self.unloaded.is_empty()
+    }
+}
+
+/* state of the rendezvous as last seen */
+#[derive(Debug,Clone)]
+pub struct Rendezvous {
+    /* address of _r_debug */
+    pub addr: u64,
+    /* where the breakpoint went, r_brk or _dl_debug_state */
+    pub brk: u64,
+    pub libraries: Vec<Library>,
+}
+
+impl Rendezvous {
+    /* libraries loaded already count as there from the start */
+    pub fn new(process: &Process<x86_64_Registers>, addr: u64, brk: u64) -> Result<Rendezvous, DebugError> {
+
+        let r_debug = RDebug::read(process, addr)?;
+
+        let libraries = if r_debug.is_ready() && r_debug.state == RT_CONSISTENT {
+            r_debug.libraries(process)?
+        } else {
+            vec![]
+        };
+
+        Ok(Rendezvous {
+            addr: addr,
+            brk: brk,
+            libraries: libraries,
+        })
+    }
+
+    /* called at r_brk, None while the list is still changing */
+    pub fn update(&mut self, process: &Process<x86_64_Registers>) -> Result<Option<LibraryChange>, DebugError> {
+
+        let r_debug = RDebug::read(process, self.addr)?;
+
+        if r_debug.state != RT_CONSISTENT {
+            return Ok(None);
+        }
+
+        let libraries = r_debug.libraries(process)?;
+        let change = LibraryChange::between(&self.libraries, &libraries);
+
+        self.libraries = libraries;
+
+        if change.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(change))
+        }
+    }
+}
+
+/* nul terminated string in the process */
+fn read_string(process: &Process<x86_64_Registers>, addr: u64) -> Result<String, DebugError> {
+
+    let mut bytes = vec![];
+
+    if addr == 0 {
+        return Ok(String::new());
+    }
+
+    while bytes.len() < MAX_PATH {
+        let word = process.peek(addr + bytes.len() as u64)?;
+
+        for i in 0..8 {
+            let b = (word >> (i * 8)) as u8;
+
+            if b == 0 {
+                return Ok(String::from_utf8_lossy(&bytes).into_owned());
+            }
+
+            bytes.push(b);
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
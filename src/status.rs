@@ -1,6 +1,20 @@
-use libc::{waitpid, WIFSTOPPED, WIFEXITED, WIFSIGNALED, WSTOPSIG, WTERMSIG, WCOREDUMP};
+use libc::{SIGTRAP, waitpid, waitid, siginfo_t, P_ALL, WEXITED, WSTOPPED, WNOWAIT, WNOHANG, __WALL};
+use libc::{WIFSTOPPED, WIFEXITED, WIFSIGNALED, WSTOPSIG, WTERMSIG, WEXITSTATUS, WCOREDUMP};
 use nix::sys::signal::Signal;
 
+use std::mem;
+
+/* new pids are the process on the other side of the event */
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum PtraceEvent {
+    Fork(u32),
+    Vfork(u32),
+    Clone(u32),
+    Exec,
+    VforkDone,
+    Exit,
+}
+
 #[derive(Debug,Clone,Copy)]
 pub struct Status {
     status: i32
@@ -10,11 +24,40 @@ impl Status {
     pub fn wait(pid: i32) -> Self {
 
         let mut s: i32 = 0;
-        unsafe { waitpid(pid, &mut s, 0); }
+        /* __WALL so clone children can be waited on too */
+        unsafe { waitpid(pid, &mut s, __WALL); }
 
         Status { status: s }
     }
 
+    /* None if pid has nothing to report right now */
+    pub fn poll(pid: i32) -> Option<Self> {
+
+        let mut s: i32 = 0;
+
+        match unsafe { waitpid(pid, &mut s, __WALL | WNOHANG) } {
+            p if p == pid => Some(Status { status: s }),
+            _ => None,
+        }
+    }
+
+    /*
+     *  blocks until any child has something to report and returns its
+     *  pid, without reaping it, so children that aren't traced, e.g. ones
+     *  spawned with Command, are left for their owner. -1 on error
+     */
+    pub fn wait_any() -> i32 {
+
+        let mut info: siginfo_t = unsafe { mem::zeroed() };
+        let flags = WEXITED | WSTOPPED | WNOWAIT | __WALL;
+
+        if unsafe { waitid(P_ALL, 0, &mut info, flags) } < 0 {
+            return -1;
+        }
+
+        unsafe { info.si_pid() }
+    }
+
     pub fn new(sig: i32) -> Self {
         Status { status: sig }
     }
@@ -52,13 +95,35 @@ impl Status {
     }
 
     pub fn termsig(&self) -> Option<i32> {
-        if self.exited() {
+        if self.signaled() {
             Some(unsafe { WTERMSIG(self.status) })
         } else {
             None
         }
     }
 
+    /* PTRACE_EVENT_* in the high bits of a SIGTRAP stop */
+    pub fn event(&self) -> Option<i32> {
+        if self.trapped() && self.stopped() && self.status >> 16 != 0 {
+            Some(self.status >> 16)
+        } else {
+            None
+        }
+    }
+
+    /* PTRACE_O_TRACESYSGOOD sets bit 7 on syscall stops */
+    pub fn syscall_stop(&self) -> bool {
+        self.stopsig() == Some(SIGTRAP | 0x80)
+    }
+
+    pub fn exitcode(&self) -> Option<i32> {
+        if self.exited() {
+            Some(unsafe { WEXITSTATUS(self.status) })
+        } else {
+            None
+        }
+    }
+
     pub fn trapped(&self) -> bool {
         self.signal() == Some(Signal::SIGTRAP)
     }
@@ -0,0 +1,356 @@
+use std::fmt::Write;
+
+use nix::Errno;
+
+use memory::Memory;
+use registers::x86_64_Registers;
+
+/* how an argument is shown */
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Arg {
+    Int,
+    Hex,
+    Fd,
+    Ptr,
+    /* nul terminated path or name */
+    Str,
+    /* read by the kernel, length in the given argument */
+    Buf(usize),
+    /* filled in by the kernel, length is the return value */
+    OutBuf,
+}
+
+use self::Arg::*;
+
+/* x86_64 numbers, see arch/x86/entry/syscalls/syscall_64.tbl */
+static SYSCALLS: &'static [(u64, &'static str, &'static [Arg])] = &[
+    (0, "read", &[Fd, OutBuf, Int]),
+    (1, "write", &[Fd, Buf(2), Int]),
+    (2, "open", &[Str, Hex, Hex]),
+    (3, "close", &[Fd]),
+    (4, "stat", &[Str, Ptr]),
+    (5, "fstat", &[Fd, Ptr]),
+    (6, "lstat", &[Str, Ptr]),
+    (7, "poll", &[Ptr, Int, Int]),
+    (8, "lseek", &[Fd, Int, Int]),
+    (9, "mmap", &[Ptr, Hex, Hex, Hex, Fd, Hex]),
+    (10, "mprotect", &[Ptr, Hex, Hex]),
+    (11, "munmap", &[Ptr, Hex]),
+    (12, "brk", &[Ptr]),
+    (13, "rt_sigaction", &[Int, Ptr, Ptr, Int]),
+    (14, "rt_sigprocmask", &[Int, Ptr, Ptr, Int]),
+    (16, "ioctl", &[Fd, Hex, Hex]),
+    (17, "pread64", &[Fd, OutBuf, Int, Int]),
+    (18, "pwrite64", &[Fd, Buf(2), Int, Int]),
+    (19, "readv", &[Fd, Ptr, Int]),
+    (20, "writev", &[Fd, Ptr, Int]),
+    (21, "access", &[Str, Hex]),
+    (22, "pipe", &[Ptr]),
+    (23, "select", &[Int, Ptr, Ptr, Ptr, Ptr]),
+    (24, "sched_yield", &[]),
+    (32, "dup", &[Fd]),
+    (33, "dup2", &[Fd, Fd]),
+    (35, "nanosleep", &[Ptr, Ptr]),
+    (39, "getpid", &[]),
+    (41, "socket", &[Int, Int, Int]),
+    (42, "connect", &[Fd, Ptr, Int]),
+    (43, "accept", &[Fd, Ptr, Ptr]),
+    (44, "sendto", &[Fd, Buf(2), Int, Hex, Ptr, Int]),
+    (45, "recvfrom", &[Fd, OutBuf, Int, Hex, Ptr, Ptr]),
+    (49, "bind", &[Fd, Ptr, Int]),
+    (50, "listen", &[Fd, Int]),
+    (56, "clone", &[Hex, Ptr, Ptr, Ptr, Hex]),
+    (57, "fork", &[]),
+    (58, "vfork", &[]),
+    (59, "execve", &[Str, Ptr, Ptr]),
+    (60, "exit", &[Int]),
+    (61, "wait4", &[Int, Ptr, Hex, Ptr]),
+    (62, "kill", &[Int, Int]),
+    (63, "uname", &[Ptr]),
+    (72, "fcntl", &[Fd, Int, Hex]),
+    (74, "fsync", &[Fd]),
+    (77, "ftruncate", &[Fd, Int]),
+    (79, "getcwd", &[Ptr, Int]),
+    (80, "chdir", &[Str]),
+    (82, "rename", &[Str, Str]),
+    (83, "mkdir", &[Str, Hex]),
+    (84, "rmdir", &[Str]),
+    (85, "creat", &[Str, Hex]),
+    (86, "link", &[Str, Str]),
+    (87, "unlink", &[Str]),
+    (88, "symlink", &[Str, Str]),
+    (89, "readlink", &[Str, OutBuf, Int]),
+    (90, "chmod", &[Str, Hex]),
+    (96, "gettimeofday", &[Ptr, Ptr]),
+    (102, "getuid", &[]),
+    (104, "getgid", &[]),
+    (107, "geteuid", &[]),
+    (108, "getegid", &[]),
+    (110, "getppid", &[]),
+    (158, "arch_prctl", &[Hex, Ptr]),
+    (186, "gettid", &[]),
+    (202, "futex", &[Ptr, Int, Int, Ptr, Ptr, Int]),
+    (217, "getdents64", &[Fd, Ptr, Int]),
+    (218, "set_tid_address", &[Ptr]),
+    (228, "clock_gettime", &[Int, Ptr]),
+    (230, "clock_nanosleep", &[Int, Hex, Ptr, Ptr]),
+    (231, "exit_group", &[Int]),
+    (234, "tgkill", &[Int, Int, Int]),
+    (257, "openat", &[Fd, Str, Hex, Hex]),
+    (258, "mkdirat", &[Fd, Str, Hex]),
+    (262, "newfstatat", &[Fd, Str, Ptr, Hex]),
+    (263, "unlinkat", &[Fd, Str, Hex]),
+    (267, "readlinkat", &[Fd, Str, OutBuf, Int]),
+    (273, "set_robust_list", &[Ptr, Int]),
+    (293, "pipe2", &[Ptr, Hex]),
+    (302, "prlimit64", &[Int, Int, Ptr, Ptr]),
+    (318, "getrandom", &[OutBuf, Int, Hex]),
+    (322, "execveat", &[Fd, Str, Ptr, Ptr, Hex]),
+    (332, "statx", &[Fd, Str, Hex, Hex, Ptr]),
+    (334, "rseq", &[Ptr, Int, Hex, Hex]),
+    (435, "clone3", &[Ptr, Int]),
+];
+
+/* longest string or buffer shown */
+const MAX_SHOWN: usize = 32;
+
+pub fn syscall_name(nr: u64) -> Option<&'static str> {
+    SYSCALLS.iter().find(|s| s.0 == nr).map(|s| s.1)
+}
+
+pub fn syscall_number(name: &str) -> Option<u64> {
+    SYSCALLS.iter().find(|s| s.1 == name).map(|s| s.0)
+}
+
+/* a syscall stop, ret is only known on the way out */
+#[derive(Debug,Clone)]
+pub struct Syscall {
+    pub nr: u64,
+    pub args: [u64; 6],
+    pub ret: Option<i64>,
+}
+
+impl Syscall {
+    pub fn from_regs(regs: &x86_64_Registers, entry: bool) -> Self {
+        Syscall {
+            nr: regs.orig_rax,
+            args: [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9],
+            ret: if entry { None } else { Some(regs.rax as i64) },
+        }
+    }
+
+    pub fn is_entry(&self) -> bool {
+        self.ret.is_none()
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        syscall_name(self.nr)
+    }
+
+    /* strace style line, pointers are read out of pid */
+    pub fn describe(&self, pid: u32) -> String {
+
+        let mut memory = Memory::load(pid as usize).ok();
+
+        let kinds = SYSCALLS.iter().find(|s| s.0 == self.nr)
+            .map(|s| s.2);
+
+        let mut line = match self.name() {
+            Some(name) => name.to_string(),
+            None => format!("syscall_{}", self.nr),
+        };
+
+        let shown = match kinds {
+            Some(kinds) => kinds.iter().enumerate().map(|(i, kind)| {
+                self.describe_arg(self.args[i], *kind, memory.as_mut())
+            }).collect::<Vec<String>>(),
+            /* unknown, show everything */
+            None => self.args.iter().map(|a| format!("0x{:x}", a)).collect(),
+        };
+
+        let _ = write!(line, "({})", shown.join(", "));
+
+        match self.ret {
+            Some(ret) if ret < 0 && ret >= -4095 => {
+                let _ = write!(line, " = -1 ({})", Errno::from_i32(-ret as i32).desc());
+            },
+            Some(ret) if ret > 0xffff => { let _ = write!(line, " = 0x{:x}", ret); },
+            Some(ret) => { let _ = write!(line, " = {}", ret); },
+            None => {},
+        }
+
+        line
+    }
+
+    fn describe_arg(&self, value: u64, kind: Arg, memory: Option<&mut Memory>) -> String {
+
+        let bytes = |memory: Option<&mut Memory>, len: usize| {
+            memory.and_then(|m| m.read(value as usize, len.min(MAX_SHOWN)).ok())
+        };
+
+        match kind {
+            Int => (value as i64).to_string(),
+            Fd => (value as i32).to_string(),
+            Hex => format!("0x{:x}", value),
+            Ptr if value == 0 => "NULL".to_string(),
+            Ptr => format!("0x{:x}", value),
+            Str if value == 0 => "NULL".to_string(),
+            Str => match memory.and_then(|m| m.read_str(value as usize, MAX_SHOWN + 1).ok()) {
+                Some(s) => quote(s.as_bytes(), s.len() > MAX_SHOWN),
+                None => format!("0x{:x}", value),
+            },
+            Buf(len) => {
+                let len = self.args[len] as usize;
+                match bytes(memory, len) {
+                    Some(b) => quote(&b, len > MAX_SHOWN),
+                    None => format!("0x{:x}", value),
+                }
+            },
+            /* nothing in it until the kernel is done */
+            OutBuf => match self.ret {
+                Some(ret) if ret >= 0 => match bytes(memory, ret as usize) {
+                    Some(b) => quote(&b, ret as usize > MAX_SHOWN),
+                    None => format!("0x{:x}", value),
+                },
+                _ => format!("0x{:x}", value),
+            },
+        }
+    }
+}
+
+fn quote(bytes: &[u8], truncated: bool) -> String {
+
+    let mut s = "\"".to_string();
+
+    for &b in bytes.iter().take(MAX_SHOWN) {
+        match b {
+            b'\n' => s.push_str("\\n"),
+            b'\t' => s.push_str("\\t"),
+            b'"' => s.push_str("\\\""),
+            b'\\' => s.push_str("\\\\"),
+            0x20..=0x7e => s.push(b as char),
+            _ => { let _ = write!(s, "\\x{:02x}", b); },
+        }
+    }
+
+    s.push('"');
+
+    if truncated {
+        s.push_str("...");
+    }
+
+    s
+}
+
+/* handed to interceptors, changes are written back before the process resumes */
+pub struct SyscallContext {
+    pub pid: u32,
+    nr: u64,
+    regs: x86_64_Registers,
+    entry: bool,
+    dirty: bool,
+    skipped: Option<i64>,
+}
+
+impl SyscallContext {
+    pub fn new(pid: u32, nr: u64, regs: x86_64_Registers, entry: bool) -> Self {
+        SyscallContext {
+            pid: pid,
+            nr: nr,
+            regs: regs,
+            entry: entry,
+            dirty: false,
+            skipped: None,
+        }
+    }
+
+    pub fn is_entry(&self) -> bool {
+        self.entry
+    }
+
+    pub fn nr(&self) -> u64 {
+        self.nr
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        syscall_name(self.nr)
+    }
+
+    /* None past the sixth, syscalls take no more */
+    pub fn arg(&self, n: usize) -> Option<u64> {
+        self.syscall().args.get(n).cloned()
+    }
+
+    /* only means something on entry, the kernel has read them by the exit. */
+    /* the old value, None and nothing written past the sixth */
+    pub fn set_arg(&mut self, n: usize, value: u64) -> Option<u64> {
+
+        let old = self.arg(n)?;
+
+        match n {
+            0 => self.regs.rdi = value,
+            1 => self.regs.rsi = value,
+            2 => self.regs.rdx = value,
+            3 => self.regs.r10 = value,
+            4 => self.regs.r8 = value,
+            _ => self.regs.r9 = value,
+        }
+
+        self.dirty = true;
+
+        Some(old)
+    }
+
+    /* return value so far, None on entry unless skipped */
+    pub fn ret(&self) -> Option<i64> {
+        if self.entry {
+            self.skipped
+        } else {
+            Some(self.regs.rax as i64)
+        }
+    }
+
+    /* on entry the kernel never sees the call and ret is what the process gets */
+    pub fn skip(&mut self, ret: i64) {
+        if self.entry {
+            /* no syscall has number -1, the kernel just returns */
+            self.regs.orig_rax = -1i64 as u64;
+            self.skipped = Some(ret);
+            self.dirty = true;
+        } else {
+            self.set_return(ret);
+        }
+    }
+
+    pub fn set_return(&mut self, ret: i64) {
+        if self.entry {
+            self.skip(ret);
+        } else {
+            self.regs.rax = ret as u64;
+            self.dirty = true;
+        }
+    }
+
+    /* make the call fail with errno, on entry it is not made at all */
+    pub fn fail(&mut self, errno: Errno) {
+        self.skip(-(errno as i64));
+    }
+
+    pub fn syscall(&self) -> Syscall {
+        let mut syscall = Syscall::from_regs(&self.regs, self.entry);
+        syscall.nr = self.nr;
+        syscall
+    }
+
+    pub fn regs(&self) -> &x86_64_Registers {
+        &self.regs
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn skipped(&self) -> Option<i64> {
+        self.skipped
+    }
+}
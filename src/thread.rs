@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::Duration;
+
+use libc::{self, SIGSTOP};
+use nix::Errno;
+
+use process::Process;
+use status::Status;
+use error::DebugError;
+use registers::x86_64_Registers;
+
+struct Thread {
+    process: Process<x86_64_Registers>,
+    running: bool,
+    /* stop picked up while halting, reported before waiting again */
+    pending: Option<Status>,
+    /* a SIGSTOP we sent that has not been seen yet */
+    interrupted: bool,
+    /* delivered on the next resume */
+    signal: Option<i32>,
+}
+
+/* how stopped threads are let go */
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Resume {
+    Continue,
+    /* stop on syscall entry and exit too */
+    Syscall,
+    /* one instruction each */
+    Step,
+}
+
+impl Thread {
+    fn resume(&mut self, mode: Resume) -> Result<(), DebugError> {
+
+        let sig = self.signal.take().unwrap_or(0);
+
+        match mode {
+            Resume::Continue => { self.process.cont_signal(sig)?; },
+            Resume::Syscall => { self.process.syscall_signal(sig)?; },
+            Resume::Step => { self.process.step_signal(sig)?; },
+        }
+
+        self.running = true;
+
+        Ok(())
+    }
+}
+
+pub struct ThreadManager {
+    tgid: u32,
+    threads: BTreeMap<u32, Thread>,
+    /* how the last resume let them go */
+    mode: Resume,
+}
+
+impl ThreadManager {
+    pub fn new(tgid: u32) -> Self {
+        let mut mgr = ThreadManager {
+            tgid: tgid,
+            threads: BTreeMap::new(),
+            mode: Resume::Continue,
+        };
+
+        mgr.add(tgid);
+        mgr
+    }
+
+    /* threads are added stopped, they run on the next resume */
+    pub fn add(&mut self, tid: u32) {
+        self.threads.entry(tid).or_insert(Thread {
+            process: Process::new(tid),
+            running: false,
+            pending: None,
+            interrupted: false,
+            signal: None,
+        });
+    }
+
+    pub fn contains(&self, tid: u32) -> bool {
+        self.threads.contains_key(&tid)
+    }
+
+    pub fn tids(&self) -> Vec<u32> {
+        self.threads.keys().cloned().collect()
+    }
+
+    pub fn get(&self, tid: u32) -> Option<Process<x86_64_Registers>> {
+        self.threads.get(&tid).map(|t| t.process.clone())
+    }
+
+    pub fn set_signal(&mut self, tid: u32, sig: Option<i32>) {
+        if let Some(t) = self.threads.get_mut(&tid) {
+            t.signal = sig;
+        }
+    }
+
+    pub fn take_signal(&mut self, tid: u32) -> Option<i32> {
+        self.threads.get_mut(&tid).and_then(|t| t.signal.take())
+    }
+
+    /* after an exec only the leader is left */
+    pub fn reset(&mut self) {
+        let tgid = self.tgid;
+        self.threads.retain(|&tid, _| tid == tgid);
+    }
+
+    pub fn retarget(&mut self, tgid: u32) {
+        *self = ThreadManager::new(tgid);
+    }
+
+    pub fn set_options(&self, options: i32) -> Result<(), DebugError> {
+        for (_, t) in self.threads.iter() {
+            t.process.set_options(options)?;
+        }
+
+        Ok(())
+    }
+
+    /* continue every thread with nothing left to report */
+    pub fn resume(&mut self, mode: Resume) -> Result<(), DebugError> {
+
+        self.mode = mode;
+
+        for (_, t) in self.threads.iter_mut() {
+            if !t.running && t.pending.is_none() {
+                t.resume(mode)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /* next stop worth reporting, thread exits are absorbed, see wait_ours */
+    pub fn wait(&mut self) -> Result<(u32, Status), DebugError> {
+
+        let pending = self.threads.iter()
+            .find(|&(_, t)| t.pending.is_some())
+            .map(|(&tid, _)| tid);
+
+        if let Some(tid) = pending {
+            let t = self.threads.get_mut(&tid).unwrap();
+            let status = t.pending.take().unwrap();
+
+            /* the queued SIGSTOP stays queued, landing it now would */
+            /* replace the siginfo and event message of this stop */
+            t.process.status.set(status);
+
+            return Ok((tid, status));
+        }
+
+        loop {
+            let (tid, status) = match self.poll() {
+                Some(stop) => stop,
+                None => {
+                    self.wait_ours()?;
+                    continue;
+                },
+            };
+
+            if !status.stopped() {
+                /* the leader is reported last, it takes the process with it */
+                if tid == self.tgid {
+                    return Ok((tid, status));
+                }
+
+                self.threads.remove(&tid);
+                continue;
+            }
+
+            let t = self.threads.get_mut(&tid).unwrap();
+            t.running = false;
+            t.process.status.set(status);
+
+            if t.interrupted && status.stopsig() == Some(SIGSTOP) {
+                /* our own SIGSTOP landing late */
+                t.interrupted = false;
+                t.resume(self.mode)?;
+                continue;
+            }
+
+            return Ok((tid, status));
+        }
+    }
+
+    /* a thread of ours with something to report, reaped */
+    fn poll(&self) -> Option<(u32, Status)> {
+        self.threads.keys()
+            .filter_map(|&tid| Status::poll(tid as i32).map(|status| (tid, status)))
+            .next()
+    }
+
+    /*
+     *  blocks until a thread of ours may have something to report. Only
+     *  our threads are reaped, another child that changed state stays
+     *  unreaped, so until its owner waits for it this has to poll
+     */
+    fn wait_ours(&mut self) -> Result<(), DebugError> {
+
+        let pid = Status::wait_any();
+
+        if pid < 0 {
+            return Err(DebugError::from(Errno::last()));
+        }
+
+        let tid = pid as u32;
+
+        if self.threads.contains_key(&tid) {
+            return Ok(());
+        }
+
+        if Process::<x86_64_Registers>::new(tid).tgid().ok() == Some(self.tgid) {
+            /* new thread stopping before its clone event arrives */
+            self.add(tid);
+        } else {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        Ok(())
+    }
+
+    /* all-stop, halt every thread but the one that stopped */
+    pub fn stop(&mut self, except: u32) -> Result<(), DebugError> {
+
+        let tids = self.threads.iter()
+            .filter(|&(&tid, t)| t.running && tid != except)
+            .map(|(&tid, _)| tid)
+            .collect::<Vec<u32>>();
+
+        for &tid in tids.iter() {
+            let t = self.threads.get_mut(&tid).unwrap();
+
+            /* a second SIGSTOP would merge with the one still queued */
+            if !t.interrupted {
+                unsafe { libc::syscall(libc::SYS_tgkill, self.tgid, tid, SIGSTOP); }
+                t.interrupted = true;
+            }
+        }
+
+        for tid in tids {
+            let status = Status::wait(tid as i32);
+
+            if !status.stopped() {
+                self.threads.remove(&tid);
+                continue;
+            }
+
+            let t = self.threads.get_mut(&tid).unwrap();
+            t.running = false;
+            t.process.status.set(status);
+
+            if status.stopsig() == Some(SIGSTOP) {
+                t.interrupted = false;
+            } else {
+                /* stopped on its own first, our signal is still queued */
+                t.pending = Some(status);
+            }
+        }
+
+        Ok(())
+    }
+
+    /* land a queued SIGSTOP before tid is stepped by hand, signals are */
+    /* delivered before anything executes so it can't split the step */
+    pub fn settle(&mut self, tid: u32) -> Result<(), DebugError> {
+        if let Some(t) = self.threads.get_mut(&tid) {
+            if t.interrupted {
+                t.process.cont()?;
+                Status::wait(tid as i32);
+                t.interrupted = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    /* let queued SIGSTOPs land so nothing stops after a detach */
+    pub fn flush(&mut self) -> Result<(), DebugError> {
+        for (&tid, t) in self.threads.iter_mut() {
+            if t.interrupted {
+                t.process.cont()?;
+                let status = Status::wait(tid as i32);
+
+                /* keep what it stopped for, a trap may still need rewinding */
+                t.process.status.set(t.pending.take().unwrap_or(status));
+                t.interrupted = false;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use std::time::{Duration,SystemTime,UNIX_EPOCH};
+
+use process::Process;
+use error::DebugError;
+use condition::Expr;
+use registers::{Register,x86_64_Registers};
+use json;
+
+/* what a tracepoint records on each hit, e.g. `rdi, rsi, [rdi, 32]` */
+#[derive(Debug,Clone)]
+pub struct TraceSpec {
+    items: Vec<TraceItem>,
+}
+
+#[derive(Debug,Clone)]
+enum TraceItem {
+    /* a register, or any expression condition.rs understands */
+    Value(String, Expr),
+    /* source, address and length in bytes */
+    Read(String, Expr, usize),
+}
+
+/* one hit of a tracepoint */
+#[derive(Debug,Clone)]
+pub struct TraceRecord {
+    pub name: String,
+    pub addr: u64,
+    pub tid: u32,
+    /* since the epoch */
+    pub time: Duration,
+    /* None if a memory read inside the expression failed */
+    pub values: Vec<(String, Option<u64>)>,
+    /* source, address and bytes, None if the memory couldn't be read */
+    pub reads: Vec<(String, u64, Option<Vec<u8>>)>,
+}
+
+impl TraceSpec {
+    pub fn parse(spec: &str) -> Result<TraceSpec, DebugError> {
+
+        let mut items = vec![];
+
+        for item in split(spec).into_iter().map(|item| item.trim()) {
+
+            if item.is_empty() {
+                continue;
+            }
+
+            if item.starts_with("[") && item.ends_with("]") {
+                let inner = &item[1..item.len()-1];
+
+                /* [addr, len], the length defaults to a word */
+                let (addr, len) = match split(inner).as_slice() {
+                    &[addr] => (addr, 8),
+                    &[addr, len] => (addr, parse_len(len.trim())?),
+                    _ => { return Err("Tracepoint reads are [addr, len]".into()); },
+                };
+
+                items.push(TraceItem::Read(item.to_string(), Expr::parse(addr)?, len));
+            } else {
+                items.push(TraceItem::Value(item.to_string(), Expr::parse(item)?));
+            }
+        }
+
+        Ok(TraceSpec { items: items })
+    }
+
+    /* thread sits just past the trap at addr */
+    pub fn record(&self, name: &str, addr: u64, thread: &Process<x86_64_Registers>)
+        -> Result<TraceRecord, DebugError>
+    {
+
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::from_secs(0));
+
+        /* report rip as the traced instruction, not the byte after the trap */
+        let mut regs = thread.getregs()?;
+        regs.set_ip(addr);
+
+        let mut values = vec![];
+        let mut reads = vec![];
+
+        for item in self.items.iter() {
+            match item {
+                &TraceItem::Value(ref src, ref expr) => {
+                    values.push((src.clone(), expr.eval(&regs, thread).ok()));
+                },
+                &TraceItem::Read(ref src, ref expr, len) => {
+                    match expr.eval(&regs, thread) {
+                        Ok(at) => reads.push((src.clone(), at, thread.read(at, len).ok())),
+                        Err(_) => reads.push((src.clone(), 0, None)),
+                    }
+                },
+            }
+        }
+
+        Ok(TraceRecord {
+            name: name.to_string(),
+            addr: addr,
+            tid: thread.pid(),
+            time: time,
+            values: values,
+            reads: reads,
+        })
+    }
+}
+
+impl TraceRecord {
+    /* one line of JSON, numbers that may not fit a double are hex strings */
+    pub fn to_json(&self) -> String {
+
+        let values: Vec<(&str, String)> = self.values.iter()
+            .map(|&(ref src, value)| (src.as_str(), match value {
+                Some(value) => json::string(&format!("0x{:x}", value)),
+                None => "null".to_string(),
+            }))
+            .collect();
+
+        let reads: Vec<String> = self.reads.iter()
+            .map(|&(ref src, addr, ref data)| json::object(&[
+                ("expr", json::string(src)),
+                ("addr", json::string(&format!("0x{:x}", addr))),
+                ("data", match data {
+                    &Some(ref data) => json::bytes(data),
+                    &None => "null".to_string(),
+                }),
+            ]))
+            .collect();
+
+        json::object(&[
+            ("name", json::string(&self.name)),
+            ("addr", json::string(&format!("0x{:x}", self.addr))),
+            ("tid", self.tid.to_string()),
+            ("time", format!("{}.{:09}", self.time.as_secs(), self.time.subsec_nanos())),
+            ("values", json::object(&values)),
+            ("reads", json::array(&reads)),
+        ])
+    }
+}
+
+/* split on commas outside of brackets and parentheses */
+fn split(spec: &str) -> Vec<&str> {
+
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in spec.char_indices() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&spec[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+
+    parts.push(&spec[start..]);
+    parts
+}
+
+fn parse_len(len: &str) -> Result<usize, DebugError> {
+
+    let len = if len.starts_with("0x") {
+        usize::from_str_radix(&len[2..], 16)?
+    } else {
+        len.parse()?
+    };
+
+    if len == 0 {
+        return Err("Tracepoint reads need a length".into());
+    }
+
+    Ok(len)
+}
@@ -0,0 +1,733 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use debuginfo::{self,Place};
+use dwarf::Reader;
+use elf::{Elf,Section};
+use error::DebugError;
+use line::Location;
+use registers::x86_64_Registers;
+
+/*
+ *  call frame information, the tables .eh_frame and .debug_frame keep of
+ *  where each function has its canonical frame address and the registers
+ *  it saved at every instruction
+ */
+
+/* DW_EH_PE_*, how .eh_frame encodes pointers */
+const DW_EH_PE_OMIT: u8 = 0xff;
+const DW_EH_PE_ULEB128: u8 = 0x01;
+const DW_EH_PE_UDATA2: u8 = 0x02;
+const DW_EH_PE_UDATA4: u8 = 0x03;
+const DW_EH_PE_UDATA8: u8 = 0x04;
+const DW_EH_PE_SLEB128: u8 = 0x09;
+const DW_EH_PE_SDATA2: u8 = 0x0a;
+const DW_EH_PE_SDATA4: u8 = 0x0b;
+const DW_EH_PE_SDATA8: u8 = 0x0c;
+const DW_EH_PE_PCREL: u8 = 0x10;
+const DW_EH_PE_DATAREL: u8 = 0x30;
+
+/* frames a backtrace gives up after, for corrupt or recursive stacks */
+pub const MAX_FRAMES: usize = 256;
+
+/* the return address column on x86_64 */
+pub const RETURN_ADDRESS: u64 = 16;
+const RSP: u64 = 7;
+
+/* how to get the CFA */
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum Cfa {
+    /* register plus offset, rsp+8 on entry to a function */
+    Register(u64, i64),
+    Expression(Vec<u8>),
+}
+
+/* how to get the caller's value of a register */
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum Rule {
+    Undefined,
+    SameValue,
+    /* saved at CFA+n */
+    Offset(i64),
+    /* is CFA+n */
+    ValOffset(i64),
+    /* in another register */
+    Register(u64),
+    Expression(Vec<u8>),
+    ValExpression(Vec<u8>),
+}
+
+/* the rules at one instruction */
+#[derive(Debug,Clone)]
+pub struct UnwindRow {
+    pub cfa: Cfa,
+    /* registers without a rule keep their value */
+    pub rules: HashMap<u64, Rule>,
+    pub return_address: u64,
+}
+
+impl UnwindRow {
+    pub fn rule(&self, register: u64) -> Rule {
+        self.rules.get(&register).cloned().unwrap_or(Rule::SameValue)
+    }
+}
+
+#[derive(Debug,Clone)]
+struct Cie {
+    code_align: u64,
+    data_align: i64,
+    return_address: u64,
+    /* pointer encoding of the FDEs using this CIE */
+    encoding: u8,
+    addr_size: usize,
+    /* 'z', FDEs carry augmentation data too */
+    augmented: bool,
+    instructions: Vec<u8>,
+}
+
+/* frame description entry, covering [start, end) of one function */
+#[derive(Debug,Clone)]
+struct Fde {
+    start: u64,
+    end: u64,
+    cie: usize,
+    instructions: Vec<u8>,
+}
+
+/* what .eh_frame and .debug_frame of one file say, link time addresses */
+pub struct CallFrameInfo {
+    cies: Vec<Cie>,
+    /* sorted by start */
+    fdes: Vec<Fde>,
+}
+
+impl CallFrameInfo {
+    pub fn parse(elf: &Elf) -> Result<CallFrameInfo, DebugError> {
+
+        let mut cfi = CallFrameInfo {
+            cies: vec![],
+            fdes: vec![],
+        };
+
+        if let Some(section) = elf.section(".eh_frame") {
+            cfi.read(elf, section, true)?;
+        }
+
+        /* only functions .eh_frame doesn't cover */
+        if let Some(section) = elf.section(".debug_frame") {
+            cfi.read(elf, section, false)?;
+        }
+
+        if cfi.fdes.is_empty() {
+            return Err("No call frame information".into());
+        }
+
+        cfi.fdes.sort_by_key(|fde| fde.start);
+
+        Ok(cfi)
+    }
+
+    fn read(&mut self, elf: &Elf, section: &Section, eh: bool) -> Result<(), DebugError> {
+
+        let data = elf.section_data(section);
+        let got = elf.section(".got").map_or(0, |got| got.addr);
+
+        let mut reader = Reader::new(data, 0);
+        let mut cies: HashMap<usize, usize> = HashMap::new();
+
+        while !reader.is_empty() {
+            let offset = reader.pos;
+            let (end, dwarf64) = reader.unit_length()?;
+
+            /* zero terminator */
+            if end == reader.pos {
+                if eh {
+                    break;
+                }
+                continue;
+            }
+
+            let id_pos = reader.pos;
+            let id = reader.offset(dwarf64)?;
+
+            let is_cie = if eh { id == 0 } else if dwarf64 { id == !0 } else { id == 0xffffffff };
+
+            if is_cie {
+                /* FDEs of a CIE we can't read are left out */
+                if let Ok(cie) = read_cie(&mut Reader::new(&data[..end.min(data.len())], reader.pos), eh) {
+                    cies.insert(offset, self.cies.len());
+                    self.cies.push(cie);
+                }
+            } else {
+                /* .eh_frame counts back from the id, .debug_frame from the start */
+                let cie_offset = if eh { id_pos.wrapping_sub(id as usize) } else { id as usize };
+
+                let cie = match cies.get(&cie_offset) {
+                    Some(&cie) => cie,
+                    None => {
+                        reader.pos = end;
+                        continue;
+                    },
+                };
+
+                let mut fde_reader = Reader::new(&data[..end.min(data.len())], reader.pos);
+                let (encoding, addr_size, augmented) = {
+                    let cie = &self.cies[cie];
+                    (cie.encoding, cie.addr_size, cie.augmented)
+                };
+
+                let start = if eh {
+                    read_pointer(&mut fde_reader, encoding, section.addr, got)?
+                } else {
+                    fde_reader.uint(addr_size)?
+                };
+
+                /* the range is a plain length in the same size */
+                let len = if eh {
+                    read_pointer(&mut fde_reader, encoding & 0x0f, 0, 0)?
+                } else {
+                    fde_reader.uint(addr_size)?
+                };
+
+                if augmented {
+                    let len = fde_reader.uleb()? as usize;
+                    fde_reader.skip(len)?;
+                }
+
+                let instructions = fde_reader.data[fde_reader.pos..].to_vec();
+
+                let covered = !eh && self.fdes.iter().any(|fde| start >= fde.start && start < fde.end);
+
+                if len > 0 && !covered {
+                    self.fdes.push(Fde {
+                        start: start,
+                        end: start.wrapping_add(len),
+                        cie: cie,
+                        instructions: instructions,
+                    });
+                }
+            }
+
+            reader.pos = end;
+        }
+
+        Ok(())
+    }
+
+    fn fde(&self, pc: u64) -> Option<&Fde> {
+        let i = match self.fdes.binary_search_by(|fde| fde.start.cmp(&pc)) {
+            Ok(i) => i,
+            Err(0) => { return None; },
+            Err(i) => i - 1,
+        };
+
+        Some(&self.fdes[i]).filter(|fde| pc < fde.end)
+    }
+
+    /* rules at a link time pc, None outside of every function described */
+    pub fn row(&self, pc: u64) -> Result<Option<UnwindRow>, DebugError> {
+
+        let fde = match self.fde(pc) {
+            Some(fde) => fde,
+            None => { return Ok(None); },
+        };
+
+        let cie = &self.cies[fde.cie];
+
+        let mut row = UnwindRow {
+            cfa: Cfa::Register(RSP, 8),
+            rules: HashMap::new(),
+            return_address: cie.return_address,
+        };
+
+        execute(&cie.instructions, cie, &mut row, None, fde.start, !0)?;
+
+        let initial = row.rules.clone();
+        execute(&fde.instructions, cie, &mut row, Some(&initial), fde.start, pc)?;
+
+        Ok(Some(row))
+    }
+}
+
+fn read_cie(reader: &mut Reader, eh: bool) -> Result<Cie, DebugError> {
+
+    let version = reader.u8()?;
+    let augmentation = reader.cstr()?.to_string();
+
+    let addr_size = if !eh && version >= 4 {
+        let size = reader.u8()? as usize;
+        /* segment selector size */
+        reader.u8()?;
+        size
+    } else {
+        8
+    };
+
+    let code_align = reader.uleb()?;
+    let data_align = reader.sleb()?;
+    let return_address = if version == 1 { reader.u8()? as u64 } else { reader.uleb()? };
+
+    let mut encoding = 0;
+
+    if augmentation.starts_with("z") {
+        let len = reader.uleb()? as usize;
+        let end = reader.pos + len;
+
+        for c in augmentation[1..].chars() {
+            match c {
+                'R' => { encoding = reader.u8()?; },
+                'L' => { reader.u8()?; },
+                'P' => {
+                    let personality = reader.u8()?;
+                    read_pointer(reader, personality & 0x7f, 0, 0)?;
+                },
+                _ => {},
+            }
+        }
+
+        reader.pos = end;
+    } else if augmentation == "eh" {
+        /* the old GCC exception table pointer */
+        reader.u64()?;
+    } else if !augmentation.is_empty() {
+        return Err("Unknown CIE augmentation".into());
+    }
+
+    Ok(Cie {
+        code_align: code_align,
+        data_align: data_align,
+        return_address: return_address,
+        encoding: encoding,
+        addr_size: addr_size,
+        augmented: augmentation.starts_with("z"),
+        instructions: reader.data[reader.pos..].to_vec(),
+    })
+}
+
+/* a DW_EH_PE encoded pointer, base is the section's address for pcrel */
+fn read_pointer(reader: &mut Reader, encoding: u8, base: u64, got: u64) -> Result<u64, DebugError> {
+
+    if encoding == DW_EH_PE_OMIT {
+        return Ok(0);
+    }
+
+    let at = base.wrapping_add(reader.pos as u64);
+
+    let value = match encoding & 0x0f {
+        DW_EH_PE_ULEB128 => reader.uleb()?,
+        DW_EH_PE_UDATA2 => reader.uint(2)?,
+        DW_EH_PE_UDATA4 => reader.uint(4)?,
+        DW_EH_PE_UDATA8 => reader.u64()?,
+        DW_EH_PE_SLEB128 => reader.sleb()? as u64,
+        DW_EH_PE_SDATA2 => reader.uint(2)? as i16 as i64 as u64,
+        DW_EH_PE_SDATA4 => reader.uint(4)? as i32 as i64 as u64,
+        DW_EH_PE_SDATA8 => reader.u64()?,
+        /* DW_EH_PE_absptr */
+        0 => reader.u64()?,
+        _ => { return Err("Unknown pointer encoding".into()); },
+    };
+
+    Ok(match encoding & 0x70 {
+        DW_EH_PE_PCREL => at.wrapping_add(value),
+        DW_EH_PE_DATAREL => got.wrapping_add(value),
+        _ => value,
+    })
+}
+
+/* run CFA instructions from loc up to and including pc */
+fn execute(instructions: &[u8], cie: &Cie, row: &mut UnwindRow,
+           initial: Option<&HashMap<u64, Rule>>, mut loc: u64, pc: u64)
+    -> Result<(), DebugError>
+{
+    let mut reader = Reader::new(instructions, 0);
+    let mut stack: Vec<(Cfa, HashMap<u64, Rule>)> = vec![];
+
+    let factored = |n: u64| (n as i64).wrapping_mul(cie.data_align);
+
+    while !reader.is_empty() {
+        let op = reader.u8()?;
+
+        let advance = match op >> 6 {
+            /* DW_CFA_advance_loc */
+            1 => Some((op & 0x3f) as u64),
+            /* DW_CFA_offset */
+            2 => {
+                let offset = factored(reader.uleb()?);
+                row.rules.insert((op & 0x3f) as u64, Rule::Offset(offset));
+                None
+            },
+            /* DW_CFA_restore */
+            3 => {
+                restore(row, initial, (op & 0x3f) as u64);
+                None
+            },
+            _ => match op {
+                /* DW_CFA_nop */
+                0x00 => None,
+                /* DW_CFA_set_loc */
+                0x01 => {
+                    loc = reader.uint(cie.addr_size)?;
+                    if loc > pc {
+                        break;
+                    }
+                    None
+                },
+                /* DW_CFA_advance_loc1, 2, 4 */
+                0x02 => Some(reader.uint(1)?),
+                0x03 => Some(reader.uint(2)?),
+                0x04 => Some(reader.uint(4)?),
+                /* DW_CFA_offset_extended */
+                0x05 => {
+                    let register = reader.uleb()?;
+                    let offset = factored(reader.uleb()?);
+                    row.rules.insert(register, Rule::Offset(offset));
+                    None
+                },
+                /* DW_CFA_restore_extended */
+                0x06 => {
+                    let register = reader.uleb()?;
+                    restore(row, initial, register);
+                    None
+                },
+                /* DW_CFA_undefined, same_value */
+                0x07 => { row.rules.insert(reader.uleb()?, Rule::Undefined); None },
+                0x08 => { row.rules.insert(reader.uleb()?, Rule::SameValue); None },
+                /* DW_CFA_register */
+                0x09 => {
+                    let register = reader.uleb()?;
+                    let other = reader.uleb()?;
+                    row.rules.insert(register, Rule::Register(other));
+                    None
+                },
+                /* DW_CFA_remember_state, restore_state */
+                0x0a => { stack.push((row.cfa.clone(), row.rules.clone())); None },
+                0x0b => {
+                    let (cfa, rules) = stack.pop().ok_or("CFA state stack underflow")?;
+                    row.cfa = cfa;
+                    row.rules = rules;
+                    None
+                },
+                /* DW_CFA_def_cfa */
+                0x0c => {
+                    let register = reader.uleb()?;
+                    let offset = reader.uleb()? as i64;
+                    row.cfa = Cfa::Register(register, offset);
+                    None
+                },
+                /* DW_CFA_def_cfa_register */
+                0x0d => {
+                    let register = reader.uleb()?;
+                    let offset = match row.cfa { Cfa::Register(_, offset) => offset, _ => 0 };
+                    row.cfa = Cfa::Register(register, offset);
+                    None
+                },
+                /* DW_CFA_def_cfa_offset */
+                0x0e => {
+                    let offset = reader.uleb()? as i64;
+                    if let Cfa::Register(register, _) = row.cfa {
+                        row.cfa = Cfa::Register(register, offset);
+                    }
+                    None
+                },
+                /* DW_CFA_def_cfa_expression */
+                0x0f => {
+                    let len = reader.uleb()? as usize;
+                    row.cfa = Cfa::Expression(reader.bytes(len)?.to_vec());
+                    None
+                },
+                /* DW_CFA_expression, val_expression */
+                0x10 | 0x16 => {
+                    let register = reader.uleb()?;
+                    let len = reader.uleb()? as usize;
+                    let expr = reader.bytes(len)?.to_vec();
+                    row.rules.insert(register, if op == 0x10 { Rule::Expression(expr) } else { Rule::ValExpression(expr) });
+                    None
+                },
+                /* DW_CFA_offset_extended_sf */
+                0x11 => {
+                    let register = reader.uleb()?;
+                    let offset = reader.sleb()?.wrapping_mul(cie.data_align);
+                    row.rules.insert(register, Rule::Offset(offset));
+                    None
+                },
+                /* DW_CFA_def_cfa_sf */
+                0x12 => {
+                    let register = reader.uleb()?;
+                    let offset = reader.sleb()?.wrapping_mul(cie.data_align);
+                    row.cfa = Cfa::Register(register, offset);
+                    None
+                },
+                /* DW_CFA_def_cfa_offset_sf */
+                0x13 => {
+                    let offset = reader.sleb()?.wrapping_mul(cie.data_align);
+                    if let Cfa::Register(register, _) = row.cfa {
+                        row.cfa = Cfa::Register(register, offset);
+                    }
+                    None
+                },
+                /* DW_CFA_val_offset, val_offset_sf */
+                0x14 => {
+                    let register = reader.uleb()?;
+                    let offset = factored(reader.uleb()?);
+                    row.rules.insert(register, Rule::ValOffset(offset));
+                    None
+                },
+                0x15 => {
+                    let register = reader.uleb()?;
+                    let offset = reader.sleb()?.wrapping_mul(cie.data_align);
+                    row.rules.insert(register, Rule::ValOffset(offset));
+                    None
+                },
+                /* DW_CFA_GNU_args_size */
+                0x2e => { reader.uleb()?; None },
+                /* DW_CFA_GNU_negative_offset_extended */
+                0x2f => {
+                    let register = reader.uleb()?;
+                    let offset = factored(reader.uleb()?);
+                    row.rules.insert(register, Rule::Offset(-offset));
+                    None
+                },
+                _ => { return Err("Unknown CFA instruction".into()); },
+            },
+        };
+
+        if let Some(delta) = advance {
+            loc = loc.wrapping_add(delta * cie.code_align);
+
+            if loc > pc {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn restore(row: &mut UnwindRow, initial: Option<&HashMap<u64, Rule>>, register: u64) {
+    match initial.and_then(|rules| rules.get(&register)) {
+        Some(rule) => { row.rules.insert(register, rule.clone()); },
+        None => { row.rules.remove(&register); },
+    }
+}
+
+/* the CFA of a frame with these registers, link time pc for expressions */
+pub fn cfa(row: &UnwindRow, regs: &x86_64_Registers, pc: u64) -> Result<u64, DebugError> {
+    match row.cfa {
+        Cfa::Register(register, offset) => {
+            let value = debuginfo::register(regs, register).ok_or("Unsupported DWARF register")?;
+            Ok(value.wrapping_add(offset as u64))
+        },
+        Cfa::Expression(ref expr) => {
+            let frame = debuginfo::Frame { pc: pc, regs: *regs, cfa: 0 };
+
+            match debuginfo::evaluate(expr, &frame, 0, None)? {
+                Place::Memory(cfa) | Place::Value(cfa) => Ok(cfa),
+                Place::Register(_) => Err("CFA expression names a register".into()),
+            }
+        },
+    }
+}
+
+/*
+ *  the caller's registers, rip being the return address and rsp the CFA,
+ *  peek reads a word of the stack
+ */
+pub fn caller<F>(row: &UnwindRow, regs: &x86_64_Registers, cfa: u64, peek: F)
+    -> Result<Option<x86_64_Registers>, DebugError>
+    where F: Fn(u64) -> Result<u64, DebugError>
+{
+    let mut caller = *regs;
+
+    /* the outermost frame, _start says so */
+    if row.rule(row.return_address) == Rule::Undefined {
+        return Ok(None);
+    }
+
+    for register in 0..RETURN_ADDRESS + 1 {
+        let value = match row.rule(register) {
+            Rule::Undefined | Rule::SameValue => { continue; },
+            Rule::Offset(offset) => peek(cfa.wrapping_add(offset as u64))?,
+            Rule::ValOffset(offset) => cfa.wrapping_add(offset as u64),
+            Rule::Register(other) => debuginfo::register(regs, other).ok_or("Unsupported DWARF register")?,
+            Rule::Expression(_) | Rule::ValExpression(_) => {
+                return Err("Register expressions are not supported".into());
+            },
+        };
+
+        if let Some(slot) = debuginfo::register_mut(&mut caller, register) {
+            *slot = value;
+        }
+    }
+
+    /* the return address rule filled in rip */
+    if row.return_address != RETURN_ADDRESS {
+        let ra = debuginfo::register(&caller, row.return_address).ok_or("Unsupported DWARF register")?;
+        caller.rip = ra;
+    }
+
+    caller.rsp = cfa;
+
+    Ok(Some(caller))
+}
+
+/* one frame of a backtrace, innermost first */
+#[derive(Debug,Clone)]
+pub struct StackFrame {
+    pub index: usize,
+    /* where the frame is, the return address for all but the innermost */
+    pub pc: u64,
+    pub cfa: u64,
+    /* main+0x1b or libc.so.6!abort+0x17, see Debugger::symbolize */
+    pub symbol: String,
+    /* file name of the module pc is in and how far into it, the same every */
+    /* run, offset is pc itself outside of every module */
+    pub module: Option<String>,
+    pub offset: u64,
+    pub location: Option<Location>,
+    /* registers the frame had, as far as they could be recovered */
+    pub regs: x86_64_Registers,
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:<2} 0x{:016x} in {}", self.index, self.pc, self.symbol)?;
+
+        if let Some(ref location) = self.location {
+            write!(f, " at {}", location)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /* what gcc emits, CFA rsp+8 and the return address at CFA-8 */
+    const CIE: &[u8] = &[1, b'z', b'R', 0, 1, 0x78, 16, 1, 0x1b, 0x0c, 7, 8, 0x90, 1];
+
+    /* push rbp, mov rbp,rsp, ..., leave at 0x103e, ret */
+    const FRAME: &[u8] = &[0x41, 0x0e, 16, 0x86, 2, 0x43, 0x0d, 6, 0x7a, 0x0c, 7, 8];
+
+    /* a push that is popped again on an early return path */
+    const STATE: &[u8] = &[0x41, 0x0e, 16, 0x83, 2, 0x0a, 0x42, 0x0e, 8, 0xc3, 0x41, 0x0b];
+
+    fn cfi() -> CallFrameInfo {
+
+        let cie = read_cie(&mut Reader::new(CIE, 0), true).unwrap();
+
+        CallFrameInfo {
+            cies: vec![cie],
+            fdes: vec![
+                Fde { start: 0x1000, end: 0x1040, cie: 0, instructions: FRAME.to_vec() },
+                Fde { start: 0x2000, end: 0x2010, cie: 0, instructions: STATE.to_vec() },
+            ],
+        }
+    }
+
+    #[test]
+    fn cie() {
+        let cie = read_cie(&mut Reader::new(CIE, 0), true).unwrap();
+
+        assert_eq!(cie.code_align, 1);
+        assert_eq!(cie.data_align, -8);
+        assert_eq!(cie.return_address, RETURN_ADDRESS);
+        assert_eq!(cie.encoding, 0x1b);
+        assert!(cie.augmented);
+        assert_eq!(cie.instructions, vec![0x0c, 7, 8, 0x90, 1]);
+    }
+
+    #[test]
+    fn rows() {
+        let cfi = cfi();
+
+        let row = cfi.row(0x1000).unwrap().unwrap();
+        assert_eq!(row.cfa, Cfa::Register(RSP, 8));
+        assert_eq!(row.rule(RETURN_ADDRESS), Rule::Offset(-8));
+        assert_eq!(row.rule(6), Rule::SameValue);
+
+        let row = cfi.row(0x1001).unwrap().unwrap();
+        assert_eq!(row.cfa, Cfa::Register(RSP, 16));
+        assert_eq!(row.rule(6), Rule::Offset(-16));
+
+        let row = cfi.row(0x1020).unwrap().unwrap();
+        assert_eq!(row.cfa, Cfa::Register(6, 16));
+        assert_eq!(row.rule(6), Rule::Offset(-16));
+
+        let row = cfi.row(0x103f).unwrap().unwrap();
+        assert_eq!(row.cfa, Cfa::Register(RSP, 8));
+
+        assert!(cfi.row(0xfff).unwrap().is_none());
+        assert!(cfi.row(0x1040).unwrap().is_none());
+    }
+
+    #[test]
+    fn remember_and_restore() {
+        let cfi = cfi();
+
+        let row = cfi.row(0x2001).unwrap().unwrap();
+        assert_eq!(row.cfa, Cfa::Register(RSP, 16));
+        assert_eq!(row.rule(3), Rule::Offset(-16));
+
+        /* after the pop, rbx back to its initial rule */
+        let row = cfi.row(0x2003).unwrap().unwrap();
+        assert_eq!(row.cfa, Cfa::Register(RSP, 8));
+        assert_eq!(row.rule(3), Rule::SameValue);
+
+        let row = cfi.row(0x2004).unwrap().unwrap();
+        assert_eq!(row.cfa, Cfa::Register(RSP, 16));
+        assert_eq!(row.rule(3), Rule::Offset(-16));
+    }
+
+    #[test]
+    fn unwind_one_frame() {
+        let row = cfi().row(0x1020).unwrap().unwrap();
+
+        let mut regs = x86_64_Registers::default();
+        regs.rip = 0x1020;
+        regs.rsp = 0x6f00;
+        regs.rbp = 0x7000;
+        regs.rbx = 0x1234;
+
+        let cfa = cfa(&row, &regs, 0x1020).unwrap();
+        assert_eq!(cfa, 0x7010);
+
+        let peek = |addr: u64| -> Result<u64, DebugError> {
+            match addr {
+                0x7000 => Ok(0x8000),
+                0x7008 => Ok(0x4242),
+                _ => Err("unmapped".into()),
+            }
+        };
+
+        let caller = caller(&row, &regs, cfa, peek).unwrap().unwrap();
+        assert_eq!(caller.rip, 0x4242);
+        assert_eq!(caller.rbp, 0x8000);
+        assert_eq!(caller.rsp, 0x7010);
+        assert_eq!(caller.rbx, 0x1234);
+    }
+
+    #[test]
+    fn outermost() {
+        let mut row = cfi().row(0x1000).unwrap().unwrap();
+        row.rules.insert(RETURN_ADDRESS, Rule::Undefined);
+
+        let regs = x86_64_Registers::default();
+        assert!(caller(&row, &regs, 0x7000, |_| Ok(0)).unwrap().is_none());
+    }
+
+    #[test]
+    fn unbalanced_restore_state() {
+        let cie = read_cie(&mut Reader::new(CIE, 0), true).unwrap();
+
+        let mut row = UnwindRow {
+            cfa: Cfa::Register(RSP, 8),
+            rules: HashMap::new(),
+            return_address: RETURN_ADDRESS,
+        };
+
+        assert!(execute(&[0x0b], &cie, &mut row, None, 0, !0).is_err());
+    }
+}
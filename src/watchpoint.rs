@@ -0,0 +1,182 @@
+use std::mem;
+
+use libc::user;
+
+use process::Process;
+use error::DebugError;
+use registers::x86_64_Registers;
+
+/* DR0 to DR3 hold addresses, DR6 is status and DR7 control */
+pub const DEBUG_SLOTS: usize = 4;
+const DR6: usize = 6;
+const DR7: usize = 7;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum WatchKind {
+    Execute,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    /* R/W field of DR7 */
+    fn bits(&self) -> u64 {
+        match self {
+            &WatchKind::Execute => 0b00,
+            &WatchKind::Write => 0b01,
+            &WatchKind::ReadWrite => 0b11,
+        }
+    }
+}
+
+/* debug registers are per thread, every thread is armed on its own */
+/* without a slot the process is single stepped and memory compared */
+#[derive(Debug,Clone)]
+pub struct Watchpoint {
+    pub addr: u64,
+    pub name: String,
+    pub kind: WatchKind,
+    pub len: usize,
+    slot: Option<usize>,
+}
+
+/* what a debug register can cover */
+pub fn fits_debugreg(addr: u64, len: usize, kind: WatchKind) -> bool {
+    match (kind, len) {
+        (WatchKind::Execute, _) => true,
+        (_, 1) | (_, 2) | (_, 4) | (_, 8) => addr % len as u64 == 0,
+        _ => false,
+    }
+}
+
+impl Watchpoint {
+    pub fn new(name: String, slot: Option<usize>, addr: u64, len: usize, kind: WatchKind)
+        -> Result<Watchpoint, DebugError>
+    {
+
+        /* instruction fetches are always one byte */
+        let len = if kind == WatchKind::Execute { 1 } else { len };
+
+        match slot {
+            Some(slot) => {
+                if slot >= DEBUG_SLOTS {
+                    return Err("No debug register left".into());
+                }
+
+                if !fits_debugreg(addr, len, kind) {
+                    return Err("Watchpoint must be 1, 2, 4 or 8 bytes and aligned".into());
+                }
+            },
+            None => {
+                /* memory only tells us it changed */
+                if kind == WatchKind::ReadWrite {
+                    return Err("Reads can only be watched with a debug register".into());
+                }
+
+                if len == 0 {
+                    return Err("Watchpoint length must not be zero".into());
+                }
+            },
+        }
+
+        Ok(Watchpoint {
+            addr: addr,
+            name: name,
+            kind: kind,
+            len: len,
+            slot: slot,
+        })
+    }
+
+    #[inline]
+    pub fn name(&mut self, name: &'static str) -> &mut Watchpoint {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn slot(&self) -> Option<usize> {
+        self.slot
+    }
+
+    pub fn is_hardware(&self) -> bool {
+        self.slot.is_some()
+    }
+
+    /* LEN field of DR7, 8 bytes is out of order */
+    fn len_bits(&self) -> u64 {
+        match self.len {
+            2 => 0b01,
+            4 => 0b11,
+            8 => 0b10,
+            _ => 0b00,
+        }
+    }
+
+    /* enable, R/W and LEN bits owned by slot */
+    fn control_mask(slot: usize) -> u64 {
+        (0b11 << (slot * 2)) | (0b1111 << (16 + slot * 4))
+    }
+
+    fn control_bits(&self, slot: usize) -> u64 {
+        let bits = self.kind.bits() | (self.len_bits() << 2);
+
+        /* local enable */
+        (1 << (slot * 2)) | (bits << (16 + slot * 4))
+    }
+
+    /* software watchpoints have nothing to arm */
+    pub fn arm(&self, thread: &Process<x86_64_Registers>) -> Result<(), DebugError> {
+
+        let slot = match self.slot {
+            Some(slot) => slot,
+            None => { return Ok(()); },
+        };
+
+        /* the address has to be in place before the kernel validates DR7 */
+        set_debugreg(thread, slot, self.addr)?;
+
+        let dr7 = debugreg(thread, DR7)?;
+        set_debugreg(thread, DR7, (dr7 & !Watchpoint::control_mask(slot)) | self.control_bits(slot))
+    }
+
+    pub fn disarm(&self, thread: &Process<x86_64_Registers>) -> Result<(), DebugError> {
+
+        let slot = match self.slot {
+            Some(slot) => slot,
+            None => { return Ok(()); },
+        };
+
+        let dr7 = debugreg(thread, DR7)?;
+        set_debugreg(thread, DR7, dr7 & !Watchpoint::control_mask(slot))
+    }
+}
+
+/* slots that fired since the last call, DR6 is sticky so it is cleared */
+pub fn triggered(thread: &Process<x86_64_Registers>) -> Result<Vec<usize>, DebugError> {
+
+    let dr6 = debugreg(thread, DR6)?;
+
+    if dr6 & 0xf != 0 {
+        set_debugreg(thread, DR6, dr6 & !0xf)?;
+    }
+
+    Ok((0..DEBUG_SLOTS).filter(|slot| dr6 & (1 << slot) != 0).collect())
+}
+
+fn debugreg_offset(n: usize) -> usize {
+    unsafe {
+        let u: user = mem::zeroed();
+        let base = &u as *const user as usize;
+        let regs = &u.u_debugreg as *const _ as usize;
+
+        regs - base + n * mem::size_of::<u64>()
+    }
+}
+
+pub fn debugreg(thread: &Process<x86_64_Registers>, n: usize) -> Result<u64, DebugError> {
+    thread.peek_user(debugreg_offset(n))
+}
+
+pub fn set_debugreg(thread: &Process<x86_64_Registers>, n: usize, value: u64) -> Result<(), DebugError> {
+    thread.poke_user(debugreg_offset(n), value)
+}